@@ -8,7 +8,8 @@
     clippy::not_unsafe_ptr_arg_deref,
     clippy::uninlined_format_args,
     unused_variables,
-    dead_code
+    dead_code,
+    static_mut_refs
 )]
 // ^ these lints don't matter in the generated code
 
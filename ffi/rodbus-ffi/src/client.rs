@@ -92,14 +92,65 @@ pub(crate) unsafe fn client_channel_create_rtu(
     })))
 }
 
-#[cfg(not(feature = "tls"))]
 pub(crate) unsafe fn client_channel_create_tls(
+    runtime: *mut crate::Runtime,
+    host: &std::ffi::CStr,
+    port: u16,
+    max_queued_requests: u16,
+    retry_strategy: ffi::RetryStrategy,
+    tls_config: ffi::TlsClientConfig,
+    decode_level: ffi::DecodeLevel,
+    listener: ffi::ClientStateListener,
+) -> Result<*mut crate::ClientChannel, ffi::ParamError> {
+    client_channel_create_tls_impl(
+        runtime,
+        host,
+        port,
+        max_queued_requests,
+        retry_strategy,
+        tls_config,
+        None,
+        decode_level,
+        listener,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) unsafe fn client_channel_create_tls_with_credentials(
+    runtime: *mut crate::Runtime,
+    host: &std::ffi::CStr,
+    port: u16,
+    max_queued_requests: u16,
+    retry_strategy: ffi::RetryStrategy,
+    tls_config: ffi::TlsClientConfig,
+    credentials: *mut crate::TlsCredentials,
+    decode_level: ffi::DecodeLevel,
+    listener: ffi::ClientStateListener,
+) -> Result<*mut crate::ClientChannel, ffi::ParamError> {
+    let credentials = credentials.as_ref().ok_or(ffi::ParamError::NullParameter)?;
+    client_channel_create_tls_impl(
+        runtime,
+        host,
+        port,
+        max_queued_requests,
+        retry_strategy,
+        tls_config,
+        Some(credentials),
+        decode_level,
+        listener,
+    )
+}
+
+#[cfg(not(feature = "tls"))]
+#[allow(clippy::too_many_arguments)]
+unsafe fn client_channel_create_tls_impl(
     _runtime: *mut crate::Runtime,
     _host: &std::ffi::CStr,
     _port: u16,
     _max_queued_requests: u16,
     _retry_strategy: ffi::RetryStrategy,
     _tls_config: ffi::TlsClientConfig,
+    _credentials: Option<&crate::TlsCredentials>,
     _decode_level: ffi::DecodeLevel,
     _listener: ffi::ClientStateListener,
 ) -> Result<*mut crate::ClientChannel, ffi::ParamError> {
@@ -107,19 +158,21 @@ pub(crate) unsafe fn client_channel_create_tls(
 }
 
 #[cfg(feature = "tls")]
-pub(crate) unsafe fn client_channel_create_tls(
+#[allow(clippy::too_many_arguments)]
+unsafe fn client_channel_create_tls_impl(
     runtime: *mut crate::Runtime,
     host: &std::ffi::CStr,
     port: u16,
     max_queued_requests: u16,
     retry_strategy: ffi::RetryStrategy,
     tls_config: ffi::TlsClientConfig,
+    credentials: Option<&crate::TlsCredentials>,
     decode_level: ffi::DecodeLevel,
     listener: ffi::ClientStateListener,
 ) -> Result<*mut crate::ClientChannel, ffi::ParamError> {
     let runtime = runtime.as_ref().ok_or(ffi::ParamError::NullParameter)?;
 
-    let tls_config: rodbus::client::TlsClientConfig = tls_config.try_into()?;
+    let tls_config = create_tls_config(tls_config, credentials)?;
 
     let host_addr = get_host_addr(host, port)?;
 
@@ -336,6 +389,123 @@ pub(crate) unsafe fn client_channel_set_decode_level(
     Ok(())
 }
 
+pub(crate) unsafe fn client_channel_get_statistics(
+    channel: *mut crate::ClientChannel,
+    reset: bool,
+) -> Result<ffi::ClientStatistics, ffi::ParamError> {
+    let channel = channel.as_ref().ok_or(ffi::ParamError::NullParameter)?;
+    let stats = channel
+        .runtime
+        .block_on(channel.inner.get_statistics(reset))?;
+    Ok(stats.into())
+}
+
+pub(crate) unsafe fn client_channel_get_unit_statistics(
+    channel: *mut crate::ClientChannel,
+    unit_id: u8,
+) -> Result<ffi::RequestStatistics, ffi::ParamError> {
+    let channel = channel.as_ref().ok_or(ffi::ParamError::NullParameter)?;
+    let stats = channel
+        .runtime
+        .block_on(channel.inner.get_statistics(false))?;
+    let unit = stats
+        .units
+        .get(&rodbus::UnitId::new(unit_id))
+        .cloned()
+        .unwrap_or_default();
+    Ok(unit.into())
+}
+
+impl From<rodbus::client::RequestStatistics> for ffi::RequestStatistics {
+    fn from(x: rodbus::client::RequestStatistics) -> Self {
+        ffi::RequestStatisticsFields {
+            requests_sent: x.requests_sent,
+            responses_received: x.responses_received,
+            timeouts: x.timeouts,
+            exceptions: x.exceptions.values().sum(),
+            exception_counts: exception_counts(&x.exceptions),
+            mean_latency: x.latency.mean().unwrap_or_default(),
+            max_latency: x.latency.max(),
+            latency_histogram: x.latency.into(),
+        }
+        .into()
+    }
+}
+
+fn exception_counts(
+    exceptions: &std::collections::BTreeMap<rodbus::ExceptionCode, u64>,
+) -> ffi::ExceptionCounts {
+    let mut counts = ffi::ExceptionCountsFields {
+        illegal_function: 0,
+        illegal_data_address: 0,
+        illegal_data_value: 0,
+        server_device_failure: 0,
+        acknowledge: 0,
+        server_device_busy: 0,
+        memory_parity_error: 0,
+        gateway_path_unavailable: 0,
+        gateway_target_device_failed_to_respond: 0,
+        unknown: 0,
+    };
+    for (code, count) in exceptions {
+        let field = match code {
+            rodbus::ExceptionCode::IllegalFunction => &mut counts.illegal_function,
+            rodbus::ExceptionCode::IllegalDataAddress => &mut counts.illegal_data_address,
+            rodbus::ExceptionCode::IllegalDataValue => &mut counts.illegal_data_value,
+            rodbus::ExceptionCode::ServerDeviceFailure => &mut counts.server_device_failure,
+            rodbus::ExceptionCode::Acknowledge => &mut counts.acknowledge,
+            rodbus::ExceptionCode::ServerDeviceBusy => &mut counts.server_device_busy,
+            rodbus::ExceptionCode::MemoryParityError => &mut counts.memory_parity_error,
+            rodbus::ExceptionCode::GatewayPathUnavailable => &mut counts.gateway_path_unavailable,
+            rodbus::ExceptionCode::GatewayTargetDeviceFailedToRespond => {
+                &mut counts.gateway_target_device_failed_to_respond
+            }
+            rodbus::ExceptionCode::Unknown(_) => &mut counts.unknown,
+        };
+        *field += count;
+    }
+    counts.into()
+}
+
+impl From<rodbus::client::LatencyHistogram> for ffi::LatencyHistogram {
+    fn from(x: rodbus::client::LatencyHistogram) -> Self {
+        // the schema defines one field per bucket in ascending order
+        let mut buckets = [0; 11];
+        for (count, bucket) in buckets.iter_mut().zip(x.buckets()) {
+            *count = bucket.count;
+        }
+        let [up_to_5_ms, up_to_10_ms, up_to_25_ms, up_to_50_ms, up_to_100_ms, up_to_250_ms, up_to_500_ms, up_to_1000_ms, up_to_2500_ms, up_to_5000_ms, over_5000_ms] =
+            buckets;
+        ffi::LatencyHistogramFields {
+            up_to_5_ms,
+            up_to_10_ms,
+            up_to_25_ms,
+            up_to_50_ms,
+            up_to_100_ms,
+            up_to_250_ms,
+            up_to_500_ms,
+            up_to_1000_ms,
+            up_to_2500_ms,
+            up_to_5000_ms,
+            over_5000_ms,
+        }
+        .into()
+    }
+}
+
+impl From<rodbus::client::ClientStatistics> for ffi::ClientStatistics {
+    fn from(x: rodbus::client::ClientStatistics) -> Self {
+        ffi::ClientStatisticsFields {
+            connects: x.connects,
+            connect_failures: x.connect_failures,
+            bytes_sent: x.bytes_sent,
+            bytes_received: x.bytes_received,
+            bad_frames: x.bad_frames,
+            total: x.total.into(),
+        }
+    }
+}
+
 impl From<ClientState> for ffi::ClientState {
     fn from(x: ClientState) -> Self {
         match x {
@@ -398,55 +568,72 @@ impl From<ffi::PortStateListener> for Box<dyn Listener<rodbus::client::PortState
     }
 }
 
+/// Build the TLS configuration from the files of the FFI configuration, or from the credentials
+/// held in memory if they are specified
 #[cfg(feature = "tls")]
-impl TryFrom<ffi::TlsClientConfig> for rodbus::client::TlsClientConfig {
-    type Error = ffi::ParamError;
-
-    fn try_from(value: ffi::TlsClientConfig) -> Result<Self, Self::Error> {
-        use std::path::Path;
-
-        let optional_password = match value.password().to_str()? {
-            "" => None,
-            password => Some(password),
-        };
-
-        let peer_cert_path = Path::new(value.peer_cert_path().to_str()?);
-        let local_cert_path = Path::new(value.local_cert_path().to_str()?);
-        let private_key_path = Path::new(value.private_key_path().to_str()?);
+fn create_tls_config(
+    value: ffi::TlsClientConfig,
+    credentials: Option<&crate::TlsCredentials>,
+) -> Result<rodbus::client::TlsClientConfig, ffi::ParamError> {
+    use std::path::Path;
+
+    let optional_password = match value.password().to_str()? {
+        "" => None,
+        password => Some(password),
+    };
+    let min_tls_version = value.min_tls_version().into();
 
-        let config = match value.certificate_mode() {
-            ffi::CertificateMode::AuthorityBased => {
-                let expected_subject_name = value.dns_name().to_str()?;
+    let config = match value.certificate_mode() {
+        ffi::CertificateMode::AuthorityBased => {
+            let expected_subject_name = value.dns_name().to_str()?;
 
-                let expected_subject_name =
-                    if value.allow_server_name_wildcard && expected_subject_name == "*" {
-                        None
-                    } else {
-                        Some(expected_subject_name.to_string())
-                    };
+            let expected_subject_name =
+                if value.allow_server_name_wildcard && expected_subject_name == "*" {
+                    None
+                } else {
+                    Some(expected_subject_name.to_string())
+                };
 
-                rodbus::client::TlsClientConfig::full_pki(
+            match credentials {
+                None => rodbus::client::TlsClientConfig::full_pki(
                     expected_subject_name,
-                    peer_cert_path,
-                    local_cert_path,
-                    private_key_path,
+                    Path::new(value.peer_cert_path().to_str()?),
+                    Path::new(value.local_cert_path().to_str()?),
+                    Path::new(value.private_key_path().to_str()?),
                     optional_password,
-                    value.min_tls_version().into(),
-                )
+                    min_tls_version,
+                ),
+                Some(x) => rodbus::client::TlsClientConfig::full_pki_from_bytes(
+                    expected_subject_name,
+                    &x.peer_certs,
+                    &x.local_certs,
+                    &x.private_key,
+                    optional_password,
+                    min_tls_version,
+                ),
             }
-            ffi::CertificateMode::SelfSigned => rodbus::client::TlsClientConfig::self_signed(
-                peer_cert_path,
-                local_cert_path,
-                private_key_path,
+        }
+        ffi::CertificateMode::SelfSigned => match credentials {
+            None => rodbus::client::TlsClientConfig::self_signed(
+                Path::new(value.peer_cert_path().to_str()?),
+                Path::new(value.local_cert_path().to_str()?),
+                Path::new(value.private_key_path().to_str()?),
                 optional_password,
-                value.min_tls_version().into(),
+                min_tls_version,
             ),
-        }
-        .map_err(|err| {
-            tracing::error!("TLS error: {}", err);
-            err
-        })?;
-
-        Ok(config)
+            Some(x) => rodbus::client::TlsClientConfig::self_signed_from_bytes(
+                &x.peer_certs,
+                &x.local_certs,
+                &x.private_key,
+                optional_password,
+                min_tls_version,
+            ),
+        },
     }
+    .map_err(|err| {
+        tracing::error!("TLS error: {}", err);
+        err
+    })?;
+
+    Ok(config)
 }
@@ -0,0 +1,41 @@
+use crate::ffi;
+
+pub struct TlsCredentials {
+    pub(crate) peer_certs: Vec<u8>,
+    pub(crate) local_certs: Vec<u8>,
+    pub(crate) private_key: Vec<u8>,
+}
+
+pub(crate) unsafe fn tls_credentials_from_pem(
+    peer_certs: &std::ffi::CStr,
+    local_certs: &std::ffi::CStr,
+    private_key: &std::ffi::CStr,
+) -> *mut crate::TlsCredentials {
+    Box::into_raw(Box::new(TlsCredentials {
+        peer_certs: peer_certs.to_bytes().to_vec(),
+        local_certs: local_certs.to_bytes().to_vec(),
+        private_key: private_key.to_bytes().to_vec(),
+    }))
+}
+
+pub(crate) unsafe fn tls_credentials_from_der(
+    peer_cert: *mut crate::ByteList,
+    local_cert: *mut crate::ByteList,
+    private_key: *mut crate::ByteList,
+) -> Result<*mut crate::TlsCredentials, ffi::ParamError> {
+    let peer_cert = peer_cert.as_ref().ok_or(ffi::ParamError::NullParameter)?;
+    let local_cert = local_cert.as_ref().ok_or(ffi::ParamError::NullParameter)?;
+    let private_key = private_key.as_ref().ok_or(ffi::ParamError::NullParameter)?;
+
+    Ok(Box::into_raw(Box::new(TlsCredentials {
+        peer_certs: peer_cert.inner.clone(),
+        local_certs: local_cert.inner.clone(),
+        private_key: private_key.inner.clone(),
+    })))
+}
+
+pub(crate) unsafe fn tls_credentials_destroy(credentials: *mut crate::TlsCredentials) {
+    if !credentials.is_null() {
+        drop(Box::from_raw(credentials));
+    };
+}
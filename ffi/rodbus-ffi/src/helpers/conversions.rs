@@ -148,7 +148,9 @@ impl From<rodbus::client::TlsError> for ffi::ParamError {
                 ffi::ParamError::InvalidLocalCertificate
             }
             rodbus::client::TlsError::InvalidPrivateKey(_) => ffi::ParamError::InvalidPrivateKey,
-            rodbus::client::TlsError::BadConfig(_) => ffi::ParamError::BadTlsConfig,
+            rodbus::client::TlsError::BadConfig(_)
+            | rodbus::client::TlsError::InvalidRevocationList(_)
+            | rodbus::client::TlsError::InvalidCryptoPolicy(_) => ffi::ParamError::BadTlsConfig,
         }
     }
 }
@@ -2,6 +2,7 @@
 #![allow(dead_code)]
 
 mod client;
+mod credentials;
 mod database;
 mod error;
 mod iterator;
@@ -19,6 +20,7 @@ pub(crate) mod helpers {
 
 pub(crate) use crate::tracing::*;
 pub use client::*;
+pub use credentials::*;
 pub use database::*;
 pub use iterator::*;
 pub use list::*;
@@ -41,3 +41,25 @@ pub(crate) unsafe fn register_list_add(list: *mut crate::RegisterList, item: u16
         list.inner.push(item)
     }
 }
+
+pub struct ByteList {
+    pub(crate) inner: Vec<u8>,
+}
+
+pub(crate) unsafe fn byte_list_create(size_hint: u32) -> *mut crate::ByteList {
+    Box::into_raw(Box::new(ByteList {
+        inner: Vec::with_capacity(size_hint as usize),
+    }))
+}
+
+pub(crate) unsafe fn byte_list_destroy(list: *mut crate::ByteList) {
+    if !list.is_null() {
+        drop(Box::from_raw(list));
+    };
+}
+
+pub(crate) unsafe fn byte_list_add(list: *mut crate::ByteList, item: u8) {
+    if let Some(list) = list.as_mut() {
+        list.inner.push(item)
+    }
+}
@@ -378,6 +378,7 @@ pub(crate) unsafe fn server_create_tls(
         endpoints,
         tls_config,
         None,
+        None,
         decode_level,
     )
 }
@@ -402,6 +403,62 @@ pub(crate) unsafe fn server_create_tls_with_authz(
         max_sessions,
         endpoints,
         tls_config,
+        None,
+        Some(auth_handler),
+        decode_level,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) unsafe fn server_create_tls_with_credentials(
+    runtime: *mut crate::Runtime,
+    ip_addr: &std::ffi::CStr,
+    port: u16,
+    filter: *mut crate::AddressFilter,
+    max_sessions: u16,
+    endpoints: *mut crate::DeviceMap,
+    tls_config: ffi::TlsServerConfig,
+    credentials: *mut crate::TlsCredentials,
+    decode_level: ffi::DecodeLevel,
+) -> Result<*mut crate::Server, ffi::ParamError> {
+    let credentials = credentials.as_ref().ok_or(ffi::ParamError::NullParameter)?;
+    server_create_tls_impl(
+        runtime,
+        ip_addr,
+        port,
+        filter,
+        max_sessions,
+        endpoints,
+        tls_config,
+        Some(credentials),
+        None,
+        decode_level,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) unsafe fn server_create_tls_with_authz_and_credentials(
+    runtime: *mut crate::Runtime,
+    ip_addr: &std::ffi::CStr,
+    port: u16,
+    filter: *mut crate::AddressFilter,
+    max_sessions: u16,
+    endpoints: *mut crate::DeviceMap,
+    tls_config: ffi::TlsServerConfig,
+    credentials: *mut crate::TlsCredentials,
+    auth_handler: ffi::AuthorizationHandler,
+    decode_level: ffi::DecodeLevel,
+) -> Result<*mut crate::Server, ffi::ParamError> {
+    let credentials = credentials.as_ref().ok_or(ffi::ParamError::NullParameter)?;
+    server_create_tls_impl(
+        runtime,
+        ip_addr,
+        port,
+        filter,
+        max_sessions,
+        endpoints,
+        tls_config,
+        Some(credentials),
         Some(auth_handler),
         decode_level,
     )
@@ -417,6 +474,7 @@ pub(crate) unsafe fn server_create_tls_impl(
     _max_sessions: u16,
     _endpoints: *mut crate::DeviceMap,
     _tls_config: ffi::TlsServerConfig,
+    _credentials: Option<&crate::TlsCredentials>,
     _auth_handler: Option<ffi::AuthorizationHandler>,
     _decode_level: ffi::DecodeLevel,
 ) -> Result<*mut crate::Server, ffi::ParamError> {
@@ -433,6 +491,7 @@ pub(crate) unsafe fn server_create_tls_impl(
     max_sessions: u16,
     endpoints: *mut crate::DeviceMap,
     tls_config: ffi::TlsServerConfig,
+    credentials: Option<&crate::TlsCredentials>,
     auth_handler: Option<ffi::AuthorizationHandler>,
     decode_level: ffi::DecodeLevel,
 ) -> Result<*mut crate::Server, ffi::ParamError> {
@@ -449,14 +508,24 @@ pub(crate) unsafe fn server_create_tls_impl(
         password => Some(password),
     };
 
-    let tls_config = TlsServerConfig::new(
-        Path::new(tls_config.peer_cert_path().to_string_lossy().as_ref()),
-        Path::new(tls_config.local_cert_path().to_string_lossy().as_ref()),
-        Path::new(tls_config.private_key_path().to_string_lossy().as_ref()),
-        optional_password,
-        tls_config.min_tls_version().into(),
-        tls_config.certificate_mode().into(),
-    )
+    let tls_config = match credentials {
+        None => TlsServerConfig::new(
+            Path::new(tls_config.peer_cert_path().to_string_lossy().as_ref()),
+            Path::new(tls_config.local_cert_path().to_string_lossy().as_ref()),
+            Path::new(tls_config.private_key_path().to_string_lossy().as_ref()),
+            optional_password,
+            tls_config.min_tls_version().into(),
+            tls_config.certificate_mode().into(),
+        ),
+        Some(x) => TlsServerConfig::from_bytes(
+            &x.peer_certs,
+            &x.local_certs,
+            &x.private_key,
+            optional_password,
+            tls_config.min_tls_version().into(),
+            tls_config.certificate_mode().into(),
+        ),
+    }
     .map_err(|err| {
         tracing::error!("TLS error: {}", err);
         err
@@ -509,7 +578,12 @@ pub(crate) unsafe fn server_create_tls_impl(
 
 pub(crate) unsafe fn server_destroy(server: *mut crate::Server) {
     if !server.is_null() {
-        drop(Box::from_raw(server));
+        let server = Box::from_raw(server);
+        let Server { inner, runtime, .. } = *server;
+        if let Err(err) = runtime.block_on(inner.shutdown(ShutdownMode::Abort)) {
+            // the handle was dropped by the failed call, so the server still shuts down
+            tracing::warn!("unable to wait for the server to shutdown: {:?}", err);
+        }
     }
 }
 
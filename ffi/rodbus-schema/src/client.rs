@@ -1,6 +1,6 @@
 use oo_bindgen::model::*;
 
-use crate::common::CommonDefinitions;
+use crate::common::{CommonDefinitions, MODBUS_EXCEPTION};
 
 pub(crate) fn build(lib: &mut LibraryBuilder, common: &CommonDefinitions) -> BackTraced<()> {
     let channel = lib.declare_class("client_channel")?;
@@ -112,7 +112,11 @@ pub(crate) fn build(lib: &mut LibraryBuilder, common: &CommonDefinitions) -> Bac
             common.retry_strategy.clone(),
             "Reconnection timing strategy",
         )?
-        .param("tls_config", tls_client_config, "TLS client configuration")?
+        .param(
+            "tls_config",
+            tls_client_config.clone(),
+            "TLS client configuration",
+        )?
         .param(
             "decode_level",
             common.decode_level.clone(),
@@ -120,7 +124,7 @@ pub(crate) fn build(lib: &mut LibraryBuilder, common: &CommonDefinitions) -> Bac
         )?
         .param(
             "listener",
-            client_state_listener,
+            client_state_listener.clone(),
             "TCP connection listener used to receive updates on the status of the channel",
         )?
         .returns(
@@ -131,6 +135,57 @@ pub(crate) fn build(lib: &mut LibraryBuilder, common: &CommonDefinitions) -> Bac
         .doc("Create a new TLS channel instance")?
         .build_static("create_tls")?;
 
+    let tls_client_create_with_credentials_fn = lib
+        .define_function("client_channel_create_tls_with_credentials")?
+        .param(
+            "runtime",
+            common.runtime_handle.clone(),
+            "Runtime on which to create the channel",
+        )?
+        .param(
+            "host",
+            StringType,
+            "IP (v4/v6) or host name remote endpoint",
+        )?
+        .param("port", Primitive::U16, "remote port")?
+        .param(
+            "max_queued_requests",
+            Primitive::U16,
+            "Maximum number of requests to queue before failing the next request",
+        )?
+        .param(
+            "retry_strategy",
+            common.retry_strategy.clone(),
+            "Reconnection timing strategy",
+        )?
+        .param("tls_config", tls_client_config, "TLS client configuration")?
+        .param(
+            "credentials",
+            common.tls_credentials.declaration(),
+            "Certificates and private key used instead of the files of the TLS configuration",
+        )?
+        .param(
+            "decode_level",
+            common.decode_level.clone(),
+            "Decode levels for this client",
+        )?
+        .param(
+            "listener",
+            client_state_listener,
+            "TCP connection listener used to receive updates on the status of the channel",
+        )?
+        .returns(
+            channel.clone(),
+            "Pointer to the created channel or {null} if an error occurred",
+        )?
+        .fails_with(common.error_type.clone())?
+        .doc(
+            doc("Create a new TLS channel instance from credentials held in memory").details(
+                "The certificate and private key paths of the TLS configuration are ignored.",
+            ),
+        )?
+        .build_static("create_tls_with_credentials")?;
+
     let destroy_channel_fn = lib.define_destructor(
         channel.clone(),
         "Shutdown a {class:client_channel} and release all resources",
@@ -243,16 +298,52 @@ pub(crate) fn build(lib: &mut LibraryBuilder, common: &CommonDefinitions) -> Bac
         )?
         .build()?;
 
+    let request_statistics = define_request_statistics(lib)?;
+    let client_statistics = define_client_statistics(lib, request_statistics.clone())?;
+
+    let get_statistics_fn = lib
+        .define_method("get_statistics", channel.clone())?
+        .param(
+            "reset",
+            Primitive::Bool,
+            "If true, the statistics are reset after they are retrieved",
+        )?
+        .returns(client_statistics, "Statistics of the channel")?
+        .fails_with(common.error_type.clone())?
+        .doc(
+            doc("Retrieve the communication statistics of the channel")
+                .warning("May not be called from within the context of the runtime"),
+        )?
+        .build()?;
+
+    let get_unit_statistics_fn = lib
+        .define_method("get_unit_statistics", channel.clone())?
+        .param("unit_id", Primitive::U8, "Unit ID of the device")?
+        .returns(
+            request_statistics,
+            "Statistics of the requests sent to the unit, all zero if no request was sent to it",
+        )?
+        .fails_with(common.error_type.clone())?
+        .doc(
+            doc("Retrieve the statistics of the requests sent to a particular unit")
+                .warning("May not be called from within the context of the runtime"),
+        )?
+        .build()?;
+
     lib.define_class(&channel)?
         // abstract factory methods
         .static_method(tcp_client_create_fn)?
         .static_method(rtu_client_create_fn)?
         .static_method(tls_client_create_fn)?
+        .static_method(tls_client_create_with_credentials_fn)?
         // enable/disable
         .method(enable_fn)?
         .method(disable_fn)?
         // setting methods
         .method(set_decode_level_fn)?
+        // statistics
+        .method(get_statistics_fn)?
+        .method(get_unit_statistics_fn)?
         // read methods
         .async_method(read_coils_method)?
         .async_method(read_discrete_inputs_method)?
@@ -275,6 +366,146 @@ pub(crate) fn build(lib: &mut LibraryBuilder, common: &CommonDefinitions) -> Bac
     Ok(())
 }
 
+// inclusive upper bounds of the latency buckets of the client statistics
+const LATENCY_BUCKET_BOUNDS_MS: [u64; 10] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000];
+
+fn define_exception_counts(lib: &mut LibraryBuilder) -> BackTraced<FunctionReturnStructHandle> {
+    let exception_counts = lib.declare_function_return_struct("exception_counts")?;
+    let mut builder = lib.define_function_return_struct(exception_counts)?;
+    for (name, _value, _desc) in MODBUS_EXCEPTION {
+        builder = builder.add(
+            *name,
+            Primitive::U64,
+            format!("Number of {{enum:modbus_exception.{name}}} responses received"),
+        )?;
+    }
+    let exception_counts = builder
+        .doc("Number of exception responses received for each exception code")?
+        .end_fields()?
+        .build()?;
+
+    Ok(exception_counts)
+}
+
+fn define_latency_histogram(lib: &mut LibraryBuilder) -> BackTraced<FunctionReturnStructHandle> {
+    let latency_histogram = lib.declare_function_return_struct("latency_histogram")?;
+    let mut builder = lib.define_function_return_struct(latency_histogram)?;
+    let mut lower = 0;
+    for bound in LATENCY_BUCKET_BOUNDS_MS {
+        builder = builder.add(
+            format!("up_to_{bound}_ms"),
+            Primitive::U64,
+            format!("Number of responses received in more than {lower} ms and at most {bound} ms"),
+        )?;
+        lower = bound;
+    }
+    let latency_histogram = builder
+        .add(
+            format!("over_{lower}_ms"),
+            Primitive::U64,
+            format!("Number of responses received in more than {lower} ms"),
+        )?
+        .doc("Histogram of the round-trip latency of the responses received")?
+        .end_fields()?
+        .build()?;
+
+    Ok(latency_histogram)
+}
+
+fn define_request_statistics(lib: &mut LibraryBuilder) -> BackTraced<FunctionReturnStructHandle> {
+    let exception_counts = define_exception_counts(lib)?;
+    let latency_histogram = define_latency_histogram(lib)?;
+
+    let request_statistics = lib.declare_function_return_struct("request_statistics")?;
+    let request_statistics = lib
+        .define_function_return_struct(request_statistics)?
+        .add(
+            "requests_sent",
+            Primitive::U64,
+            "Number of requests written, including retries",
+        )?
+        .add(
+            "responses_received",
+            Primitive::U64,
+            "Number of responses received, including exception responses",
+        )?
+        .add(
+            "timeouts",
+            Primitive::U64,
+            "Number of requests that timed out waiting for a response",
+        )?
+        .add(
+            "exceptions",
+            Primitive::U64,
+            "Number of exception responses received",
+        )?
+        .add(
+            "exception_counts",
+            exception_counts,
+            "Number of exception responses received for each exception code",
+        )?
+        .add(
+            "mean_latency",
+            BasicType::Duration(DurationType::Milliseconds),
+            "Mean round-trip latency of the responses received",
+        )?
+        .add(
+            "max_latency",
+            BasicType::Duration(DurationType::Milliseconds),
+            "Largest round-trip latency of the responses received",
+        )?
+        .add(
+            "latency_histogram",
+            latency_histogram,
+            "Histogram of the round-trip latency of the responses received",
+        )?
+        .doc("Counters related to the requests sent to a device or a whole channel")?
+        .end_fields()?
+        .build()?;
+
+    Ok(request_statistics)
+}
+
+fn define_client_statistics(
+    lib: &mut LibraryBuilder,
+    request_statistics: FunctionReturnStructHandle,
+) -> BackTraced<FunctionReturnStructHandle> {
+    let client_statistics = lib.declare_function_return_struct("client_statistics")?;
+    let client_statistics = lib
+        .define_function_return_struct(client_statistics)?
+        .add(
+            "connects",
+            Primitive::U64,
+            "Number of times a connection was established or the serial port was opened",
+        )?
+        .add(
+            "connect_failures",
+            Primitive::U64,
+            "Number of failed attempts to connect or to open the serial port",
+        )?
+        .add(
+            "bytes_sent",
+            Primitive::U64,
+            "Number of bytes written to the physical layer",
+        )?
+        .add(
+            "bytes_received",
+            Primitive::U64,
+            "Number of bytes read from the physical layer",
+        )?
+        .add(
+            "bad_frames",
+            Primitive::U64,
+            "Number of frames that could not be parsed",
+        )?
+        .add("total", request_statistics, "Counters for all units")?
+        .doc("Communication statistics of a client channel")?
+        .end_fields()?
+        .build()?;
+
+    Ok(client_statistics)
+}
+
 fn define_port_state_listener(lib: &mut LibraryBuilder) -> BackTraced<AsynchronousInterface> {
     let port_state = lib
         .define_enum("port_state")?
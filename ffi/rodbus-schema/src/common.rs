@@ -16,6 +16,7 @@ pub(crate) struct CommonDefinitions {
     pub(crate) serial_port_settings: FunctionArgStructHandle,
     pub(crate) min_tls_version: EnumHandle,
     pub(crate) certificate_mode: EnumHandle,
+    pub(crate) tls_credentials: ClassHandle,
     pub(crate) retry_strategy: UniversalStructHandle,
 }
 
@@ -31,7 +32,7 @@ impl CommonDefinitions {
             error_type: error_type.clone(),
             nothing,
             decode_level,
-            runtime_handle: sfio_tokio_ffi::define(lib, error_type)?,
+            runtime_handle: sfio_tokio_ffi::define(lib, error_type.clone())?,
             error_info: build_request_error(lib)?,
             address_range: build_address_range(lib)?,
             request_param: build_request_param(lib)?,
@@ -43,6 +44,7 @@ impl CommonDefinitions {
             serial_port_settings: build_serial_params(lib)?,
             min_tls_version: build_min_tls_version(lib)?,
             certificate_mode: build_certificate_mode(lib)?,
+            tls_credentials: build_tls_credentials(lib, &error_type)?,
             retry_strategy: build_retry_strategy(lib)?,
         })
     }
@@ -306,6 +308,68 @@ fn build_certificate_mode(lib: &mut LibraryBuilder) -> BackTraced<EnumHandle> {
     Ok(definition)
 }
 
+fn build_tls_credentials(
+    lib: &mut LibraryBuilder,
+    error_type: &ErrorTypeHandle,
+) -> BackTraced<ClassHandle> {
+    let byte_list = lib.define_collection("byte_list", Primitive::U8, true)?;
+    let tls_credentials = lib.declare_class("tls_credentials")?;
+
+    let from_pem_fn = lib
+        .define_function("tls_credentials_from_pem")?
+        .param(
+            "peer_certs",
+            StringType,
+            "PEM-encoded certificate(s) of the peer",
+        )?
+        .param(
+            "local_certs",
+            StringType,
+            "PEM-encoded local certificate(s)",
+        )?
+        .param("private_key", StringType, "PEM-encoded private key")?
+        .returns(tls_credentials.clone(), "TLS credentials")?
+        .doc("Create TLS credentials from PEM-encoded certificates and private key")?
+        .build_static("from_pem")?;
+
+    let from_der_fn = lib
+        .define_function("tls_credentials_from_der")?
+        .param(
+            "peer_cert",
+            byte_list.clone(),
+            "DER-encoded certificate of the peer",
+        )?
+        .param("local_cert", byte_list.clone(), "DER-encoded local certificate")?
+        .param(
+            "private_key",
+            byte_list,
+            "DER-encoded PKCS#8, PKCS#1 or SEC1 private key",
+        )?
+        .returns(tls_credentials.clone(), "TLS credentials")?
+        .fails_with(error_type.clone())?
+        .doc(
+            doc("Create TLS credentials from DER-encoded certificates and private key")
+                .details("Each certificate list contains a single certificate. Use {class:tls_credentials.from_pem()} for certificate chains or several trust anchors."),
+        )?
+        .build_static("from_der")?;
+
+    let destructor = lib.define_destructor(tls_credentials.clone(), "Destroy TLS credentials")?;
+
+    let tls_credentials = lib
+        .define_class(&tls_credentials)?
+        .destructor(destructor)?
+        .static_method(from_pem_fn)?
+        .static_method(from_der_fn)?
+        .doc(
+            doc("Certificates and private key of a TLS configuration held in memory")
+                .details("Use these credentials instead of the file paths of the TLS configuration to keep the private key off the disk, e.g. if it is loaded from a secrets manager.")
+                .details("An encrypted private key is decrypted with the password of the TLS configuration."),
+        )?
+        .build()?;
+
+    Ok(tls_credentials)
+}
+
 fn build_serial_params(lib: &mut LibraryBuilder) -> BackTraced<FunctionArgStructHandle> {
     let data_bits = lib
         .define_enum("data_bits")?
@@ -391,7 +455,7 @@ fn build_serial_params(lib: &mut LibraryBuilder) -> BackTraced<FunctionArgStruct
     Ok(serial_params)
 }
 
-const MODBUS_EXCEPTION: &[(&str, u8, &str)] = &[
+pub(crate) const MODBUS_EXCEPTION: &[(&str, u8, &str)] = &[
     ("illegal_function", 0x01, "The data address received in the query is not an allowable address for the server"),
     ("illegal_data_address", 0x02, "The data address received in the query is not an allowable address for the server"),
     ("illegal_data_value", 0x03, "A value contained in the request is not an allowable value for server"),
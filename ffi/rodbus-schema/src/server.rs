@@ -127,7 +127,7 @@ pub(crate) fn build_server(
         )?
         .param(
             "authorization_handler",
-        authorization_handler,
+        authorization_handler.clone(),
             "Authorization handler"
         )?
         .param("decode_level", common.decode_level.clone(), "Decode levels for this server")?
@@ -139,6 +139,47 @@ pub(crate) fn build_server(
             .details("When the maximum number of concurrent sessions is reached, the oldest session is closed."))?
         .build_static("create_tls_with_authz")?;
 
+    let tls_constructor_with_authz_and_credentials = lib
+        .define_function("server_create_tls_with_authz_and_credentials")?
+        .param(
+            "runtime",
+            common.runtime_handle.clone(),
+            "runtime on which to spawn the server",
+        )?
+        .param("address", StringType, address_doc)?
+        .param("port", Primitive::U16, port_doc)?
+        .param("filter", address_filter.declaration(), "Filter used to limit which IP address(es) can connect")?
+        .param("max_sessions", Primitive::U16, "Maximum number of concurrent sessions")?
+        .param(
+            "endpoints",
+            handler_map.declaration(),
+            "map of endpoints which is emptied upon passing to this function",
+        )?
+        .param(
+            "tls_config",
+            tls_server_config.clone(),
+            "TLS server configuration",
+        )?
+        .param(
+            "credentials",
+            common.tls_credentials.declaration(),
+            "Certificates and private key used instead of the files of the TLS configuration",
+        )?
+        .param(
+            "authorization_handler",
+        authorization_handler,
+            "Authorization handler"
+        )?
+        .param("decode_level", common.decode_level.clone(), "Decode levels for this server")?
+        .returns(server.clone(), "Modbus Security (TLS) server instance")?
+        .fails_with(common.error_type.clone())?
+        .doc(doc("Create a Modbus Security (TLS) server from credentials held in memory")
+            .details("The certificate and private key paths of the TLS configuration are ignored.")
+            .details("This server requires that the client certificate contains the role extension and authorizes each request against the supplied handler.")
+            .details("Recommended port for Modbus Security is 802.")
+            .details("When the maximum number of concurrent sessions is reached, the oldest session is closed."))?
+        .build_static("create_tls_with_authz_and_credentials")?;
+
     let tls_constructor_raw = lib
         .define_function("server_create_tls")?
         .param(
@@ -157,7 +198,7 @@ pub(crate) fn build_server(
         )?
         .param(
             "tls_config",
-            tls_server_config,
+            tls_server_config.clone(),
             "TLS server configuration",
         )?
         .param("decode_level", common.decode_level.clone(), "Decode levels for this server")?
@@ -168,9 +209,45 @@ pub(crate) fn build_server(
             .details("When the maximum number of concurrent sessions is reached, the oldest session is closed."))?
         .build_static("create_tls")?;
 
+    let tls_constructor_with_credentials = lib
+        .define_function("server_create_tls_with_credentials")?
+        .param(
+            "runtime",
+            common.runtime_handle.clone(),
+            "runtime on which to spawn the server",
+        )?
+        .param("address", StringType, address_doc)?
+        .param("port", Primitive::U16, port_doc)?
+        .param("filter", address_filter.declaration(), "Filter used to limit which IP address(es) can connect")?
+        .param("max_sessions", Primitive::U16, "Maximum number of concurrent sessions")?
+        .param(
+            "endpoints",
+            handler_map.declaration(),
+            "map of endpoints which is emptied upon passing to this function",
+        )?
+        .param(
+            "tls_config",
+            tls_server_config.clone(),
+            "TLS server configuration",
+        )?
+        .param(
+            "credentials",
+            common.tls_credentials.declaration(),
+            "Certificates and private key used instead of the files of the TLS configuration",
+        )?
+        .param("decode_level", common.decode_level.clone(), "Decode levels for this server")?
+        .returns(server.clone(), "Modbus Security (TLS) server instance")?
+        .fails_with(common.error_type.clone())?
+        .doc(doc("Create a TLS server that does NOT require the client role extension from credentials held in memory")
+            .details("The certificate and private key paths of the TLS configuration are ignored.")
+            .details("This functionality is not standardized by Modbus.org, but nevertheless is commonly implemented")
+            .details("When the maximum number of concurrent sessions is reached, the oldest session is closed."))?
+        .build_static("create_tls_with_credentials")?;
+
     let destructor = lib.define_destructor(
         server.clone(),
-        "Shutdown and release all resources of a running server",
+        doc("Shutdown and release all resources of a running server")
+            .details("Requests being processed are aborted. The call blocks until the server task and all of the session tasks have exited."),
     )?;
 
     let update_fn = lib
@@ -193,6 +270,8 @@ pub(crate) fn build_server(
         .static_method(rtu_constructor)?
         .static_method(tls_constructor_with_authz)?
         .static_method(tls_constructor_raw)?
+        .static_method(tls_constructor_with_authz_and_credentials)?
+        .static_method(tls_constructor_with_credentials)?
         .method(update_fn)?
         .method(set_decode_level_fn)?
         .destructor(destructor)?
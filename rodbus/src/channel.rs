@@ -14,4 +14,13 @@ impl<T> Receiver<T> {
     pub(crate) async fn recv(&mut self) -> Result<T, Shutdown> {
         self.0.recv().await.ok_or(Shutdown)
     }
+
+    /// returns `None` if no value is immediately available
+    pub(crate) fn try_recv(&mut self) -> Result<Option<T>, Shutdown> {
+        match self.0.try_recv() {
+            Ok(x) => Ok(Some(x)),
+            Err(tokio::sync::mpsc::error::TryRecvError::Empty) => Ok(None),
+            Err(tokio::sync::mpsc::error::TryRecvError::Disconnected) => Err(Shutdown),
+        }
+    }
 }
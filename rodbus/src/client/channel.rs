@@ -1,11 +1,15 @@
 use std::time::Duration;
 
-use crate::client::message::{Command, Promise, Request, RequestDetails, Setting};
+use tokio::time::Instant;
+
+use crate::client::message::{Cancellation, Command, Promise, Request, RequestDetails, Setting};
 use crate::client::requests::read_bits::ReadBits;
 use crate::client::requests::read_registers::ReadRegisters;
 use crate::client::requests::write_multiple::{MultipleWriteRequest, WriteMultiple};
 use crate::client::requests::write_single::SingleWrite;
+use crate::client::statistics::{ClientStatistics, SharedStatistics};
 use crate::error::*;
+use crate::retry::RequestRetryPolicy;
 use crate::types::{AddressRange, BitIterator, Indexed, RegisterIterator, UnitId};
 use crate::DecodeLevel;
 
@@ -13,15 +17,39 @@ use crate::DecodeLevel;
 #[derive(Debug, Clone)]
 pub struct Channel {
     pub(crate) tx: tokio::sync::mpsc::Sender<Command>,
+    pub(crate) stats: SharedStatistics,
+}
+
+/// Order in which queued requests are sent by the channel
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum RequestPriority {
+    /// Requests are sent in the order they are submitted
+    #[default]
+    Normal,
+    /// Requests are sent ahead of any queued `Normal` requests. To avoid starving them, a
+    /// `Normal` request is still sent after every few consecutive `High` requests.
+    High,
 }
 
 /// Request parameters to dispatch the request to the proper device
+///
+/// Prefer creating it with [`RequestParam::new`] and customizing it with the `with_*` methods,
+/// struct literals have to list every field.
 #[derive(Debug, Clone, Copy)]
 pub struct RequestParam {
     /// Unit ID of the target device
     pub id: UnitId,
     /// Response timeout
     pub response_timeout: Duration,
+    /// Optional policy used to retry the request when it fails
+    pub retry: Option<RequestRetryPolicy>,
+    /// Optional limit on the total time the request may take, measured from the moment it is
+    /// submitted to the channel. Unlike `response_timeout`, this includes the time spent waiting in
+    /// the queue and any retries. The request fails with [`RequestError::ResponseTimeout`] when it
+    /// elapses.
+    pub deadline: Option<Duration>,
+    /// Priority of the request relative to other queued requests
+    pub priority: RequestPriority,
 }
 
 impl RequestParam {
@@ -30,8 +58,41 @@ impl RequestParam {
         Self {
             id,
             response_timeout,
+            retry: None,
+            deadline: None,
+            priority: RequestPriority::Normal,
         }
     }
+
+    /// Retry the request according to the supplied policy
+    pub fn with_retry(mut self, policy: RequestRetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Limit the total time the request may take, including time spent in the queue
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Set the priority of the request relative to other queued requests
+    pub fn with_priority(mut self, priority: RequestPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    fn build(self, details: RequestDetails, cancellation: Option<Cancellation>) -> Request {
+        Request::new(
+            self.id,
+            self.response_timeout,
+            self.deadline.map(|x| Instant::now() + x),
+            self.retry,
+            self.priority,
+            cancellation,
+            details,
+        )
+    }
 }
 
 impl Channel {
@@ -69,20 +130,24 @@ impl Channel {
 
         let path = path.to_string();
         let (tx, rx) = tokio::sync::mpsc::channel(max_queued_requests);
+        let stats = SharedStatistics::default();
+        let task_stats = stats.clone();
         let task = async move {
             let _ = crate::serial::client::SerialChannelTask::new(
                 &path,
                 serial_settings,
                 rx.into(),
+                max_queued_requests,
                 retry,
                 decode,
                 listener.unwrap_or_else(|| crate::client::NullListener::create()),
+                task_stats,
             )
             .run()
             .instrument(tracing::info_span!("Modbus-Client-RTU", "port" = ?path))
             .await;
         };
-        (Channel { tx }, task)
+        (Channel { tx, stats }, task)
     }
 
     /// Enable communications
@@ -97,6 +162,11 @@ impl Channel {
         Ok(())
     }
 
+    /// Retrieve the communication statistics of the channel, optionally resetting them
+    pub async fn get_statistics(&self, reset: bool) -> ClientStatistics {
+        self.stats.get(reset)
+    }
+
     /// Read coils from the server
     pub async fn read_coils(
         &mut self,
@@ -104,12 +174,12 @@ impl Channel {
         range: AddressRange,
     ) -> Result<Vec<Indexed<bool>>, RequestError> {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<Vec<Indexed<bool>>, RequestError>>();
-        let request = wrap(
+        self.send_and_wait(
             param,
             RequestDetails::ReadCoils(ReadBits::channel(range.of_read_bits()?, tx)),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            rx,
+        )
+        .await
     }
 
     /// Read discrete inputs from the server
@@ -119,12 +189,12 @@ impl Channel {
         range: AddressRange,
     ) -> Result<Vec<Indexed<bool>>, RequestError> {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<Vec<Indexed<bool>>, RequestError>>();
-        let request = wrap(
+        self.send_and_wait(
             param,
             RequestDetails::ReadDiscreteInputs(ReadBits::channel(range.of_read_bits()?, tx)),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            rx,
+        )
+        .await
     }
 
     /// Read holding registers from the server
@@ -134,15 +204,15 @@ impl Channel {
         range: AddressRange,
     ) -> Result<Vec<Indexed<u16>>, RequestError> {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<Vec<Indexed<u16>>, RequestError>>();
-        let request = wrap(
+        self.send_and_wait(
             param,
             RequestDetails::ReadHoldingRegisters(ReadRegisters::channel(
                 range.of_read_registers()?,
                 tx,
             )),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            rx,
+        )
+        .await
     }
 
     /// Read input registers from the server
@@ -152,15 +222,15 @@ impl Channel {
         range: AddressRange,
     ) -> Result<Vec<Indexed<u16>>, RequestError> {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<Vec<Indexed<u16>>, RequestError>>();
-        let request = wrap(
+        self.send_and_wait(
             param,
             RequestDetails::ReadInputRegisters(ReadRegisters::channel(
                 range.of_read_registers()?,
                 tx,
             )),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            rx,
+        )
+        .await
     }
 
     /// Write a single coil on the server
@@ -170,12 +240,12 @@ impl Channel {
         request: Indexed<bool>,
     ) -> Result<Indexed<bool>, RequestError> {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<Indexed<bool>, RequestError>>();
-        let request = wrap(
+        self.send_and_wait(
             param,
             RequestDetails::WriteSingleCoil(SingleWrite::new(request, Promise::channel(tx))),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            rx,
+        )
+        .await
     }
 
     /// Write a single register on the server
@@ -185,12 +255,12 @@ impl Channel {
         request: Indexed<u16>,
     ) -> Result<Indexed<u16>, RequestError> {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<Indexed<u16>, RequestError>>();
-        let request = wrap(
+        self.send_and_wait(
             param,
             RequestDetails::WriteSingleRegister(SingleWrite::new(request, Promise::channel(tx))),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            rx,
+        )
+        .await
     }
 
     /// Write multiple contiguous coils on the server
@@ -200,15 +270,15 @@ impl Channel {
         request: WriteMultiple<bool>,
     ) -> Result<AddressRange, RequestError> {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<AddressRange, RequestError>>();
-        let request = wrap(
+        self.send_and_wait(
             param,
             RequestDetails::WriteMultipleCoils(MultipleWriteRequest::new(
                 request,
                 Promise::channel(tx),
             )),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            rx,
+        )
+        .await
     }
 
     /// Write multiple contiguous registers on the server
@@ -218,15 +288,36 @@ impl Channel {
         request: WriteMultiple<u16>,
     ) -> Result<AddressRange, RequestError> {
         let (tx, rx) = tokio::sync::oneshot::channel::<Result<AddressRange, RequestError>>();
-        let request = wrap(
+        self.send_and_wait(
             param,
             RequestDetails::WriteMultipleRegisters(MultipleWriteRequest::new(
                 request,
                 Promise::channel(tx),
             )),
-        );
-        self.tx.send(request).await?;
-        rx.await?
+            rx,
+        )
+        .await
+    }
+
+    /// Replace the TLS configuration used when the channel connects to the server
+    ///
+    /// The policy determines whether the current connection is closed and re-established
+    /// immediately or keeps running until it is closed for another reason.
+    ///
+    /// Only applies to TLS channels
+    #[cfg(feature = "tls")]
+    pub async fn set_tls_config(
+        &mut self,
+        config: crate::client::TlsClientConfig,
+        policy: crate::client::TlsReloadPolicy,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(Command::Setting(Setting::TlsConfig(
+                Box::new(config),
+                policy,
+            )))
+            .await?;
+        Ok(())
     }
 
     /// Dynamically change the protocol decoding level of the channel
@@ -236,6 +327,30 @@ impl Channel {
             .await?;
         Ok(())
     }
+
+    async fn send_and_wait<T>(
+        &mut self,
+        param: RequestParam,
+        details: RequestDetails,
+        rx: tokio::sync::oneshot::Receiver<Result<T, RequestError>>,
+    ) -> Result<T, RequestError> {
+        // if this future is dropped, the guard notifies the task that the request was cancelled
+        let (_guard, cancellation) = Cancellation::create();
+        let request = param.build(details, Some(cancellation));
+        let deadline = request.deadline;
+        let result = async move {
+            self.tx.send(Command::Request(request)).await?;
+            rx.await?
+        };
+
+        match deadline {
+            None => result.await,
+            // the guard is dropped when the deadline elapses, the task abandons the request
+            Some(deadline) => tokio::time::timeout_at(deadline, result)
+                .await
+                .unwrap_or(Err(RequestError::ResponseTimeout)),
+        }
+    }
 }
 
 /// Callback-based session
@@ -258,6 +373,12 @@ impl CallbackSession {
         }
     }
 
+    /// Set the priority of all requests made through this session
+    pub fn with_priority(mut self, priority: RequestPriority) -> Self {
+        self.param.priority = priority;
+        self
+    }
+
     /// Read coils from the server
     pub async fn read_coils<C>(&mut self, range: AddressRange, callback: C)
     where
@@ -386,5 +507,5 @@ impl CallbackSession {
 }
 
 fn wrap(param: RequestParam, details: RequestDetails) -> Command {
-    Command::Request(Request::new(param.id, param.response_timeout, details))
+    Command::Request(param.build(details, None))
 }
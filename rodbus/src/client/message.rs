@@ -4,22 +4,30 @@ use crate::decode::AppDecodeLevel;
 use crate::error::AduParseError;
 use crate::error::*;
 use crate::exception::ExceptionCode;
+use crate::retry::RequestRetryPolicy;
 use crate::DecodeLevel;
 
 use crate::client::requests::read_bits::ReadBits;
 use crate::client::requests::read_registers::ReadRegisters;
 use crate::client::requests::write_multiple::MultipleWriteRequest;
 use crate::client::requests::write_single::SingleWrite;
+use crate::client::RequestPriority;
 use crate::common::traits::Serialize;
 use crate::types::{Indexed, UnitId};
 
 use scursor::{ReadCursor, WriteCursor};
 use std::time::Duration;
+use tokio::time::Instant;
 
 pub(crate) enum Setting {
     DecodeLevel(DecodeLevel),
     Enable,
     Disable,
+    #[cfg(feature = "tls")]
+    TlsConfig(
+        Box<crate::client::TlsClientConfig>,
+        crate::client::TlsReloadPolicy,
+    ),
 }
 
 pub(crate) enum Command {
@@ -32,9 +40,42 @@ pub(crate) enum Command {
 pub(crate) struct Request {
     pub(crate) id: UnitId,
     pub(crate) timeout: Duration,
+    pub(crate) deadline: Option<Instant>,
+    pub(crate) retry: Option<RequestRetryPolicy>,
+    pub(crate) priority: RequestPriority,
+    pub(crate) cancellation: Option<Cancellation>,
     pub(crate) details: RequestDetails,
 }
 
+/// Held by the caller for as long as it is waiting on the result of a request
+pub(crate) struct CancelGuard {
+    _tx: tokio::sync::oneshot::Sender<()>,
+}
+
+/// Allows the channel task to detect that the caller is no longer waiting on a request
+pub(crate) struct Cancellation {
+    rx: tokio::sync::oneshot::Receiver<()>,
+}
+
+impl Cancellation {
+    pub(crate) fn create() -> (CancelGuard, Self) {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        (CancelGuard { _tx: tx }, Self { rx })
+    }
+
+    fn is_cancelled(&mut self) -> bool {
+        matches!(
+            self.rx.try_recv(),
+            Err(tokio::sync::oneshot::error::TryRecvError::Closed)
+        )
+    }
+
+    async fn cancelled(&mut self) {
+        // the guard never sends anything, so this only completes when it is dropped
+        let _ = (&mut self.rx).await;
+    }
+}
+
 // possible requests that can be sent through the channel
 pub(crate) enum RequestDetails {
     ReadCoils(ReadBits),
@@ -48,14 +89,50 @@ pub(crate) enum RequestDetails {
 }
 
 impl Request {
-    pub(crate) fn new(id: UnitId, timeout: Duration, details: RequestDetails) -> Self {
+    pub(crate) fn new(
+        id: UnitId,
+        timeout: Duration,
+        deadline: Option<Instant>,
+        retry: Option<RequestRetryPolicy>,
+        priority: RequestPriority,
+        cancellation: Option<Cancellation>,
+        details: RequestDetails,
+    ) -> Self {
         Self {
             id,
             timeout,
+            deadline,
+            retry,
+            priority,
+            cancellation,
             details,
         }
     }
 
+    /// True if the caller is no longer waiting on the result of the request
+    pub(crate) fn is_cancelled(&mut self) -> bool {
+        match &mut self.cancellation {
+            Some(x) => x.is_cancelled(),
+            None => false,
+        }
+    }
+
+    /// Completes when the caller stops waiting on the result of the request
+    pub(crate) async fn cancelled(&mut self) {
+        match &mut self.cancellation {
+            Some(x) => x.cancelled().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// True if the deadline of the request has already elapsed
+    pub(crate) fn is_expired(&self) -> bool {
+        match self.deadline {
+            Some(x) => Instant::now() >= x,
+            None => false,
+        }
+    }
+
     pub(crate) fn handle_response(
         &mut self,
         payload: &[u8],
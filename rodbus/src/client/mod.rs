@@ -6,12 +6,15 @@ use crate::decode::DecodeLevel;
 pub(crate) mod channel;
 pub(crate) mod listener;
 pub(crate) mod message;
+pub(crate) mod queue;
 pub(crate) mod requests;
+pub(crate) mod statistics;
 pub(crate) mod task;
 
 pub use crate::client::channel::*;
 pub use crate::client::listener::*;
 pub use crate::client::requests::write_multiple::WriteMultiple;
+pub use crate::client::statistics::*;
 pub use crate::retry::*;
 
 #[cfg(feature = "tls")]
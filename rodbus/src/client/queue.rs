@@ -0,0 +1,308 @@
+use std::collections::VecDeque;
+
+use crate::channel::Receiver;
+use crate::client::message::{Command, Request, Setting};
+use crate::client::RequestPriority;
+use crate::error::Shutdown;
+
+/// Number of high priority requests that may be serviced in a row while normal
+/// priority requests are waiting before one normal priority request is serviced
+const MAX_CONSECUTIVE_HIGH_PRIORITY: usize = 4;
+
+/// Wraps the command receiver and hands out requests in priority order.
+///
+/// Requests waiting in the mpsc are moved into the queues so that a high priority
+/// request is not stuck behind a backlog of normal priority requests. At most `limit`
+/// requests are held in the queues, the rest wait in the mpsc until a slot is free,
+/// so that memory stays bounded by twice the configured number of queued requests.
+///
+/// Requests whose caller stopped waiting are dropped from the queues so that they do
+/// not take up slots.
+///
+/// Settings act as a barrier: requests submitted after a setting are not reordered
+/// ahead of it, and it is only applied once every request submitted before it has
+/// been handed out.
+pub(crate) struct CommandQueue {
+    rx: Receiver<Command>,
+    limit: usize,
+    high: VecDeque<Request>,
+    normal: VecDeque<Request>,
+    setting: Option<Setting>,
+    consecutive_high: usize,
+}
+
+impl CommandQueue {
+    pub(crate) fn new(rx: Receiver<Command>, limit: usize) -> Self {
+        Self {
+            rx,
+            limit,
+            high: VecDeque::new(),
+            normal: VecDeque::new(),
+            setting: None,
+            consecutive_high: 0,
+        }
+    }
+
+    /// Retrieve the next command. This future is cancel safe.
+    pub(crate) async fn recv(&mut self) -> Result<Command, Shutdown> {
+        self.fill();
+
+        if let Some(request) = self.next_request() {
+            return Ok(Command::Request(request));
+        }
+
+        if let Some(setting) = self.setting.take() {
+            return Ok(Command::Setting(setting));
+        }
+
+        self.rx.recv().await
+    }
+
+    /// Wait until a setting is the next command to hand out, leaving requests in the queue.
+    /// This future is cancel safe.
+    ///
+    /// Fails once the mpsc is closed.
+    pub(crate) async fn next_setting(&mut self) -> Result<Setting, Shutdown> {
+        loop {
+            self.fill();
+
+            if self.high.is_empty() && self.normal.is_empty() {
+                if let Some(setting) = self.setting.take() {
+                    return Ok(setting);
+                }
+            } else if self.setting.is_some() || self.len() >= self.limit {
+                // the setting is only applied after the requests submitted before it,
+                // and the rest of the mpsc is only read once a slot is free
+                return std::future::pending().await;
+            }
+
+            match self.rx.recv().await? {
+                Command::Request(request) => self.push(request),
+                Command::Setting(setting) => self.setting = Some(setting),
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.high.len() + self.normal.len()
+    }
+
+    fn push(&mut self, mut request: Request) {
+        if request.is_cancelled() {
+            tracing::info!("discarding request that was cancelled while queued");
+            return;
+        }
+        match request.priority {
+            RequestPriority::High => self.high.push_back(request),
+            RequestPriority::Normal => self.normal.push_back(request),
+        }
+    }
+
+    /// Drop the requests whose caller is no longer waiting on them
+    fn remove_cancelled(&mut self) {
+        self.high.retain_mut(|x| !x.is_cancelled());
+        self.normal.retain_mut(|x| !x.is_cancelled());
+    }
+
+    fn fill(&mut self) {
+        if self.len() >= self.limit {
+            self.remove_cancelled();
+        }
+        while self.setting.is_none() && self.len() < self.limit {
+            // a closed mpsc is reported once the buffered commands are consumed
+            match self.rx.try_recv() {
+                Ok(Some(Command::Request(request))) => self.push(request),
+                Ok(Some(Command::Setting(setting))) => self.setting = Some(setting),
+                Ok(None) | Err(Shutdown) => return,
+            }
+        }
+    }
+
+    fn next_request(&mut self) -> Option<Request> {
+        let starved = self.consecutive_high >= MAX_CONSECUTIVE_HIGH_PRIORITY;
+        if !starved || self.normal.is_empty() {
+            if let Some(request) = self.high.pop_front() {
+                self.consecutive_high += 1;
+                return Some(request);
+            }
+        }
+
+        self.consecutive_high = 0;
+        self.normal.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::client::message::{CancelGuard, Cancellation, RequestDetails};
+    use crate::client::requests::read_bits::ReadBits;
+    use crate::types::{AddressRange, UnitId};
+
+    fn request(start: u16, priority: RequestPriority) -> Command {
+        build(start, priority, None)
+    }
+
+    fn cancellable(start: u16, priority: RequestPriority) -> (CancelGuard, Command) {
+        let (guard, cancellation) = Cancellation::create();
+        (guard, build(start, priority, Some(cancellation)))
+    }
+
+    fn build(start: u16, priority: RequestPriority, cancellation: Option<Cancellation>) -> Command {
+        let (tx, _) = tokio::sync::oneshot::channel();
+        let range = AddressRange::try_from(start, 1).unwrap();
+        Command::Request(Request::new(
+            UnitId::new(1),
+            Duration::from_secs(1),
+            None,
+            None,
+            priority,
+            cancellation,
+            RequestDetails::ReadCoils(ReadBits::channel(range.of_read_bits().unwrap(), tx)),
+        ))
+    }
+
+    async fn next_start(queue: &mut CommandQueue) -> Option<u16> {
+        match queue.recv().await.unwrap() {
+            Command::Request(request) => match &request.details {
+                RequestDetails::ReadCoils(x) => Some(x.request.inner.start),
+                _ => unreachable!(),
+            },
+            Command::Setting(_) => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn services_high_priority_requests_first() {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let mut queue = CommandQueue::new(rx.into(), 16);
+
+        tx.send(request(0, RequestPriority::Normal)).await.unwrap();
+        tx.send(request(1, RequestPriority::Normal)).await.unwrap();
+        tx.send(request(2, RequestPriority::High)).await.unwrap();
+
+        assert_eq!(next_start(&mut queue).await, Some(2));
+        assert_eq!(next_start(&mut queue).await, Some(0));
+        assert_eq!(next_start(&mut queue).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn services_high_priority_requests_behind_a_backlog() {
+        let (tx, rx) = tokio::sync::mpsc::channel(256);
+        let mut queue = CommandQueue::new(rx.into(), 256);
+
+        for i in 0..200 {
+            tx.send(request(i, RequestPriority::Normal)).await.unwrap();
+        }
+        assert_eq!(next_start(&mut queue).await, Some(0));
+
+        tx.send(request(1000, RequestPriority::High)).await.unwrap();
+        assert_eq!(next_start(&mut queue).await, Some(1000));
+        assert_eq!(next_start(&mut queue).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn leaves_requests_in_the_mpsc_once_the_queues_are_full() {
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        let mut queue = CommandQueue::new(rx.into(), 2);
+
+        tx.send(request(0, RequestPriority::Normal)).await.unwrap();
+        tx.send(request(1, RequestPriority::Normal)).await.unwrap();
+        tx.send(request(2, RequestPriority::High)).await.unwrap();
+
+        // the high priority request is only read once a slot is free
+        assert_eq!(next_start(&mut queue).await, Some(0));
+        assert_eq!(next_start(&mut queue).await, Some(2));
+        assert_eq!(next_start(&mut queue).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn cancelled_request_frees_its_slot() {
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        let mut queue = CommandQueue::new(rx.into(), 2);
+
+        let (guard, cmd) = cancellable(0, RequestPriority::Normal);
+        tx.send(cmd).await.unwrap();
+        tx.send(request(1, RequestPriority::Normal)).await.unwrap();
+        queue.fill();
+        tx.send(request(2, RequestPriority::High)).await.unwrap();
+        drop(guard);
+
+        // the high priority request takes the slot of the cancelled one
+        assert_eq!(next_start(&mut queue).await, Some(2));
+        assert_eq!(next_start(&mut queue).await, Some(1));
+        assert!(queue.high.is_empty() && queue.normal.is_empty());
+    }
+
+    #[tokio::test]
+    async fn does_not_starve_normal_priority_requests() {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let mut queue = CommandQueue::new(rx.into(), 16);
+
+        tx.send(request(100, RequestPriority::Normal))
+            .await
+            .unwrap();
+        for i in 0..(MAX_CONSECUTIVE_HIGH_PRIORITY + 1) {
+            tx.send(request(i as u16, RequestPriority::High))
+                .await
+                .unwrap();
+        }
+
+        for i in 0..MAX_CONSECUTIVE_HIGH_PRIORITY {
+            assert_eq!(next_start(&mut queue).await, Some(i as u16));
+        }
+        assert_eq!(next_start(&mut queue).await, Some(100));
+        assert_eq!(
+            next_start(&mut queue).await,
+            Some(MAX_CONSECUTIVE_HIGH_PRIORITY as u16)
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_are_not_reordered_across_settings() {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let mut queue = CommandQueue::new(rx.into(), 16);
+
+        tx.send(request(0, RequestPriority::Normal)).await.unwrap();
+        tx.send(Command::Setting(Setting::Disable)).await.unwrap();
+        tx.send(request(1, RequestPriority::High)).await.unwrap();
+
+        assert_eq!(next_start(&mut queue).await, Some(0));
+        assert_eq!(next_start(&mut queue).await, None);
+        assert_eq!(next_start(&mut queue).await, Some(1));
+    }
+
+    #[tokio::test]
+    async fn waits_for_settings_without_consuming_requests() {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let mut queue = CommandQueue::new(rx.into(), 16);
+
+        tx.send(Command::Setting(Setting::Disable)).await.unwrap();
+        tx.send(request(0, RequestPriority::Normal)).await.unwrap();
+        assert!(matches!(queue.next_setting().await, Ok(Setting::Disable)));
+
+        // a setting queued behind a request is not handed out ahead of it
+        tx.send(Command::Setting(Setting::Enable)).await.unwrap();
+        let waiting = tokio::time::timeout(Duration::from_millis(10), queue.next_setting());
+        assert!(waiting.await.is_err());
+        assert_eq!(next_start(&mut queue).await, Some(0));
+        assert!(matches!(queue.next_setting().await, Ok(Setting::Enable)));
+
+        drop(tx);
+        assert!(queue.next_setting().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reports_shutdown_after_buffered_requests_are_consumed() {
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let mut queue = CommandQueue::new(rx.into(), 16);
+
+        tx.send(request(0, RequestPriority::Normal)).await.unwrap();
+        drop(tx);
+
+        assert_eq!(next_start(&mut queue).await, Some(0));
+        assert!(queue.recv().await.is_err());
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::exception::ExceptionCode;
+use crate::types::UnitId;
+
+/// Upper bounds (in milliseconds) of the buckets of a [`LatencyHistogram`]
+const LATENCY_BUCKET_BOUNDS_MS: [u64; 10] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000];
+
+/// Histogram of the time between sending a request and receiving the response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LatencyHistogram {
+    // the last bucket has no upper bound
+    counts: [u64; LATENCY_BUCKET_BOUNDS_MS.len() + 1],
+    total: Duration,
+    max: Duration,
+}
+
+/// A single bucket of a [`LatencyHistogram`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyBucket {
+    /// Inclusive upper bound of the bucket, `None` for the last bucket
+    pub upper_bound: Option<Duration>,
+    /// Number of samples that fell into the bucket
+    pub count: u64,
+}
+
+impl LatencyHistogram {
+    /// Total number of samples
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Mean of all samples, `None` if there are no samples
+    pub fn mean(&self) -> Option<Duration> {
+        match self.count() {
+            0 => None,
+            count => Some(Duration::from_nanos(
+                (self.total.as_nanos() / count as u128) as u64,
+            )),
+        }
+    }
+
+    /// Largest sample, `Duration::ZERO` if there are no samples
+    pub fn max(&self) -> Duration {
+        self.max
+    }
+
+    /// Buckets of the histogram in ascending order
+    pub fn buckets(&self) -> impl Iterator<Item = LatencyBucket> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| LatencyBucket {
+                upper_bound: LATENCY_BUCKET_BOUNDS_MS
+                    .get(i)
+                    .map(|x| Duration::from_millis(*x)),
+                count: *count,
+            })
+    }
+
+    fn record(&mut self, latency: Duration) {
+        let index = LATENCY_BUCKET_BOUNDS_MS
+            .iter()
+            .position(|x| latency <= Duration::from_millis(*x))
+            .unwrap_or(LATENCY_BUCKET_BOUNDS_MS.len());
+        self.counts[index] += 1;
+        self.total += latency;
+        self.max = self.max.max(latency);
+    }
+}
+
+/// Counters related to the requests sent to a device or a whole channel
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RequestStatistics {
+    /// Number of requests written, including retries
+    pub requests_sent: u64,
+    /// Number of responses received, including exception responses
+    pub responses_received: u64,
+    /// Number of requests that timed out waiting for a response
+    pub timeouts: u64,
+    /// Number of exception responses received by exception code
+    pub exceptions: BTreeMap<ExceptionCode, u64>,
+    /// Round-trip latency of the responses received
+    pub latency: LatencyHistogram,
+}
+
+/// Communication statistics of a client channel
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ClientStatistics {
+    /// Number of times a connection was established or the serial port was opened
+    pub connects: u64,
+    /// Number of failed attempts to connect or to open the serial port
+    pub connect_failures: u64,
+    /// Number of bytes written to the physical layer
+    pub bytes_sent: u64,
+    /// Number of bytes read from the physical layer
+    pub bytes_received: u64,
+    /// Number of frames that could not be parsed
+    pub bad_frames: u64,
+    /// Counters for all units
+    pub total: RequestStatistics,
+    /// Counters for each unit that requests were sent to
+    pub units: BTreeMap<UnitId, RequestStatistics>,
+}
+
+/// Statistics shared between the channel handle and the channel task
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SharedStatistics {
+    inner: Arc<Mutex<ClientStatistics>>,
+}
+
+impl SharedStatistics {
+    pub(crate) fn get(&self, reset: bool) -> ClientStatistics {
+        let mut guard = self.inner.lock().unwrap();
+        if reset {
+            std::mem::take(&mut *guard)
+        } else {
+            guard.clone()
+        }
+    }
+
+    pub(crate) fn on_connect(&self) {
+        self.inner.lock().unwrap().connects += 1;
+    }
+
+    pub(crate) fn on_connect_failure(&self) {
+        self.inner.lock().unwrap().connect_failures += 1;
+    }
+
+    pub(crate) fn on_bytes(&self, sent: usize, received: usize) {
+        let mut guard = self.inner.lock().unwrap();
+        guard.bytes_sent += sent as u64;
+        guard.bytes_received += received as u64;
+    }
+
+    pub(crate) fn on_bad_frame(&self) {
+        self.inner.lock().unwrap().bad_frames += 1;
+    }
+
+    pub(crate) fn on_request_sent(&self, unit: UnitId) {
+        self.update(unit, |x| x.requests_sent += 1);
+    }
+
+    pub(crate) fn on_response(&self, unit: UnitId, latency: Duration) {
+        self.update(unit, |x| {
+            x.responses_received += 1;
+            x.latency.record(latency);
+        });
+    }
+
+    pub(crate) fn on_timeout(&self, unit: UnitId) {
+        self.update(unit, |x| x.timeouts += 1);
+    }
+
+    pub(crate) fn on_exception(&self, unit: UnitId, ex: ExceptionCode) {
+        self.update(unit, |x| *x.exceptions.entry(ex).or_default() += 1);
+    }
+
+    fn update<F>(&self, unit: UnitId, action: F)
+    where
+        F: Fn(&mut RequestStatistics),
+    {
+        let mut guard = self.inner.lock().unwrap();
+        action(&mut guard.total);
+        action(guard.units.entry(unit).or_default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_places_samples_in_buckets() {
+        let mut histogram = LatencyHistogram::default();
+        histogram.record(Duration::from_millis(5));
+        histogram.record(Duration::from_millis(6));
+        histogram.record(Duration::from_secs(10));
+
+        let buckets: Vec<LatencyBucket> = histogram.buckets().collect();
+        assert_eq!(buckets.len(), LATENCY_BUCKET_BOUNDS_MS.len() + 1);
+        assert_eq!(buckets[0].count, 1);
+        assert_eq!(buckets[1].count, 1);
+        assert_eq!(buckets.last().unwrap().upper_bound, None);
+        assert_eq!(buckets.last().unwrap().count, 1);
+        assert_eq!(histogram.count(), 3);
+        assert_eq!(histogram.max(), Duration::from_secs(10));
+        assert_eq!(histogram.mean(), Some(Duration::from_millis(10011) / 3));
+    }
+
+    #[test]
+    fn counts_per_unit_and_resets() {
+        let stats = SharedStatistics::default();
+        stats.on_request_sent(UnitId::new(1));
+        stats.on_request_sent(UnitId::new(2));
+        stats.on_exception(UnitId::new(2), ExceptionCode::ServerDeviceBusy);
+
+        let snapshot = stats.get(true);
+        assert_eq!(snapshot.total.requests_sent, 2);
+        assert_eq!(snapshot.units[&UnitId::new(1)].requests_sent, 1);
+        assert_eq!(
+            snapshot.units[&UnitId::new(2)].exceptions[&ExceptionCode::ServerDeviceBusy],
+            1
+        );
+
+        assert_eq!(stats.get(false), ClientStatistics::default());
+    }
+}
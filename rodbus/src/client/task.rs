@@ -6,6 +6,8 @@ use crate::common::phys::PhysLayer;
 use tokio::time::Instant;
 
 use crate::client::message::{Command, Request, Setting};
+use crate::client::queue::CommandQueue;
+use crate::client::statistics::SharedStatistics;
 use crate::common::frame::{FrameHeader, FrameWriter, FramedReader, TxId};
 use crate::error::*;
 use crate::DecodeLevel;
@@ -23,6 +25,9 @@ pub(crate) enum SessionError {
     Disabled,
     /// the mpsc is closed (dropped) on the sender side
     Shutdown,
+    /// the TLS configuration was replaced and the connection must be re-established
+    #[cfg(feature = "tls")]
+    TlsReload,
 }
 
 impl From<Shutdown> for SessionError {
@@ -58,6 +63,10 @@ impl std::fmt::Display for SessionError {
             SessionError::Shutdown => {
                 write!(f, "Shutdown was requested")
             }
+            #[cfg(feature = "tls")]
+            SessionError::TlsReload => {
+                write!(f, "TLS configuration was replaced")
+            }
         }
     }
 }
@@ -74,48 +83,100 @@ impl SessionError {
 }
 
 pub(crate) struct ClientLoop {
-    rx: crate::channel::Receiver<Command>,
+    rx: CommandQueue,
     writer: FrameWriter,
     reader: FramedReader,
     tx_id: TxId,
     decode: DecodeLevel,
     enabled: bool,
+    stats: SharedStatistics,
+    // only TLS channels accept a new TLS configuration
+    #[cfg(feature = "tls")]
+    tls_reload: bool,
+    // configuration applied before the next connection attempt
+    #[cfg(feature = "tls")]
+    pending_tls: Option<(
+        Box<crate::client::TlsClientConfig>,
+        crate::client::TlsReloadPolicy,
+    )>,
 }
 
 impl ClientLoop {
     pub(crate) fn new(
         rx: crate::channel::Receiver<Command>,
+        max_queued_requests: usize,
         writer: FrameWriter,
         reader: FramedReader,
         decode: DecodeLevel,
+        stats: SharedStatistics,
     ) -> Self {
         Self {
-            rx,
+            rx: CommandQueue::new(rx, max_queued_requests),
             writer,
             reader,
             tx_id: TxId::default(),
             decode,
             enabled: false,
+            stats,
+            #[cfg(feature = "tls")]
+            tls_reload: false,
+            #[cfg(feature = "tls")]
+            pending_tls: None,
         }
     }
 
+    #[cfg(feature = "tls")]
+    pub(crate) fn enable_tls_reload(&mut self) {
+        self.tls_reload = true;
+    }
+
+    /// Take the TLS configuration that must be used for the next connection, if it was replaced
+    #[cfg(feature = "tls")]
+    pub(crate) fn take_tls_config(&mut self) -> Option<Box<crate::client::TlsClientConfig>> {
+        self.pending_tls.take().map(|(config, _)| config)
+    }
+
     pub(crate) fn is_enabled(&self) -> bool {
         self.enabled
     }
 
+    pub(crate) fn stats(&self) -> &SharedStatistics {
+        &self.stats
+    }
+
     async fn run_cmd(&mut self, cmd: Command, io: &mut PhysLayer) -> Result<(), SessionError> {
         match cmd {
-            Command::Setting(setting) => {
-                self.change_setting(setting);
-                if !self.enabled {
-                    return Err(SessionError::Disabled);
-                }
-                Ok(())
-            }
+            Command::Setting(setting) => self.apply_setting(setting),
             Command::Request(mut request) => self.run_one_request(io, &mut request).await,
         }
     }
 
+    /// Change a setting, returns an error if the session must be closed
+    fn apply_setting(&mut self, setting: Setting) -> Result<(), SessionError> {
+        self.change_setting(setting);
+        if !self.enabled {
+            return Err(SessionError::Disabled);
+        }
+        #[cfg(feature = "tls")]
+        if let Some((_, crate::client::TlsReloadPolicy::CloseSessions)) = self.pending_tls {
+            return Err(SessionError::TlsReload);
+        }
+        Ok(())
+    }
+
+    /// Wait before retrying a request, unless the channel is shut down or a setting
+    /// requires the session to be closed
+    async fn wait_before_retry(&mut self, delay: Duration) -> Result<(), SessionError> {
+        let sleep = tokio::time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => return Ok(()),
+                setting = self.rx.next_setting() => self.apply_setting(setting?)?,
+            }
+        }
+    }
+
     pub(crate) async fn wait_for_enabled(&mut self) -> Result<(), Shutdown> {
         loop {
             if self.enabled {
@@ -130,7 +191,10 @@ impl ClientLoop {
 
     pub(crate) async fn run(&mut self, io: &mut PhysLayer) -> SessionError {
         loop {
-            if let Err(err) = self.poll(io).await {
+            let result = self.poll(io).await;
+            let counts = io.take_counts();
+            self.stats.on_bytes(counts.sent, counts.received);
+            if let Err(err) = result {
                 tracing::warn!("ending session: {}", err);
                 return err;
             }
@@ -145,9 +209,14 @@ impl ClientLoop {
                         tracing::warn!("Received unexpected frame while idle: {:?}", frame.header);
                         Ok(())
                     }
-                    Err(err) => match SessionError::from_request_err(err) {
-                        Some(err) => Err(err),
-                        None => Ok(()),
+                    Err(err) => {
+                        if let RequestError::BadFrame(_) = err {
+                            self.stats.on_bad_frame();
+                        }
+                        match SessionError::from_request_err(err) {
+                            Some(err) => Err(err),
+                            None => Ok(()),
+                        }
                     }
                 }
             }
@@ -163,13 +232,68 @@ impl ClientLoop {
         io: &mut PhysLayer,
         request: &mut Request,
     ) -> Result<(), SessionError> {
-        let tx_id = self.tx_id.next();
-        let result = self
-            .execute_request(io, request, tx_id)
-            .instrument(tracing::info_span!("Transaction", tx_id = %tx_id))
-            .await;
+        if request.is_cancelled() {
+            tracing::info!("discarding request that was cancelled while queued");
+            return Ok(());
+        }
+
+        if request.is_expired() {
+            tracing::warn!("request deadline elapsed while queued");
+            request.details.fail(RequestError::ResponseTimeout);
+            return Ok(());
+        }
+
+        let mut retries = 0;
+        loop {
+            let tx_id = self.tx_id.next();
+            let result = self
+                .execute_request(io, request, tx_id)
+                .instrument(tracing::info_span!("Transaction", tx_id = %tx_id))
+                .await;
+
+            let err = match result {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+
+            if request.is_cancelled() {
+                // nobody is waiting on the result, a late response is ignored by the idle loop
+                tracing::info!("abandoned request that was cancelled while in flight");
+                return Ok(());
+            }
+
+            // retry in place so that the request keeps its position in the queue
+            let delay = request
+                .retry
+                .and_then(|x| x.next_delay(retries, err))
+                .filter(|x| match request.deadline {
+                    Some(deadline) => Instant::now() + *x < deadline,
+                    None => true,
+                });
+
+            if let Some(delay) = delay {
+                retries += 1;
+                tracing::warn!(
+                    "request error: {} - retry {} in {} ms",
+                    err,
+                    retries,
+                    delay.as_millis()
+                );
+                if let Err(session_err) = self.wait_before_retry(delay).await {
+                    tracing::warn!(
+                        "request error: {} - abandoned retries: {}",
+                        err,
+                        session_err
+                    );
+                    request.details.fail(match session_err {
+                        SessionError::Shutdown => RequestError::Shutdown,
+                        _ => err,
+                    });
+                    return Err(session_err);
+                }
+                continue;
+            }
 
-        if let Err(err) = result {
             // Fail the request in ONE place. If the whole future
             // gets dropped, then the request gets failed with Shutdown
             tracing::warn!("request error: {}", err);
@@ -180,9 +304,9 @@ impl ClientLoop {
             if let Some(err) = SessionError::from_request_err(err) {
                 return Err(err);
             }
-        }
 
-        Ok(())
+            return Ok(());
+        }
     }
 
     async fn execute_request(
@@ -199,16 +323,32 @@ impl ClientLoop {
         )?;
 
         io.write(bytes, self.decode.physical).await?;
+        self.stats.on_request_sent(request.id);
+        let sent = Instant::now();
 
-        let deadline = Instant::now() + request.timeout;
+        let response_timeout = sent + request.timeout;
+        let deadline = match request.deadline {
+            Some(x) => std::cmp::min(x, response_timeout),
+            None => response_timeout,
+        };
 
         // loop until we get a response with the correct tx id or we timeout
         let response = loop {
             let frame = tokio::select! {
                 _ = tokio::time::sleep_until(deadline) => {
+                    self.stats.on_timeout(request.id);
+                    self.wait_for_late_response(io, response_timeout).await?;
                     return Err(RequestError::ResponseTimeout);
                 }
+                _ = request.cancelled() => {
+                    self.wait_for_late_response(io, response_timeout).await?;
+                    // the error is never reported since the caller is gone
+                    return Err(RequestError::Shutdown);
+                }
                 frame = self.reader.next_frame(io, self.decode) => {
+                    if let Err(RequestError::BadFrame(_)) = frame {
+                        self.stats.on_bad_frame();
+                    }
                     frame?
                 }
             };
@@ -224,9 +364,40 @@ impl ClientLoop {
             break frame;
         };
 
+        self.stats.on_response(request.id, sent.elapsed());
+
         // once we have a response, handle it. This may complete a promise
         // successfully or bubble up an error
-        request.handle_response(response.payload(), self.decode.app)
+        let result = request.handle_response(response.payload(), self.decode.app);
+        if let Err(RequestError::Exception(ex)) = result {
+            self.stats.on_exception(request.id, ex);
+        }
+        result
+    }
+
+    /// Responses on a serial line carry no transaction id, so the bus stays reserved until the
+    /// response timeout of an abandoned request elapses. Otherwise, a late response would be
+    /// matched to the next request.
+    async fn wait_for_late_response(
+        &mut self,
+        io: &mut PhysLayer,
+        response_timeout: Instant,
+    ) -> Result<(), RequestError> {
+        if self.writer.has_tx_id() {
+            return Ok(());
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep_until(response_timeout) => Ok(()),
+            frame = self.reader.next_frame(io, self.decode) => {
+                if let Err(RequestError::BadFrame(_)) = frame {
+                    self.stats.on_bad_frame();
+                }
+                let frame = frame?;
+                tracing::warn!("ignored late response from unit {}", frame.header.destination);
+                Ok(())
+            }
+        }
     }
 
     pub(crate) fn change_setting(&mut self, setting: Setting) {
@@ -247,6 +418,15 @@ impl ClientLoop {
                     tracing::info!("channel disabled");
                 }
             }
+            #[cfg(feature = "tls")]
+            Setting::TlsConfig(config, policy) => {
+                if self.tls_reload {
+                    tracing::info!("TLS configuration changed ({:?})", policy);
+                    self.pending_tls = Some((config, policy));
+                } else {
+                    tracing::warn!("ignoring TLS configuration, the channel does not use TLS");
+                }
+            }
         }
     }
 
@@ -292,13 +472,14 @@ mod tests {
     use std::io::ErrorKind;
 
     use super::*;
-    use crate::client::{Channel, RequestParam};
+    use crate::client::{Channel, RequestParam, RequestPriority};
+    use crate::common::frame::FunctionField;
     use crate::common::function::FunctionCode;
     use crate::common::traits::{Loggable, Serialize};
     use crate::decode::*;
     use crate::server::response::BitWriter;
     use crate::types::{AddressRange, UnitId};
-    use crate::{ExceptionCode, Indexed, ReadBitsRange};
+    use crate::{ExceptionCode, Indexed, ReadBitsRange, RequestRetryPolicy};
 
     use sfio_tokio_mock_io::Event;
 
@@ -306,35 +487,85 @@ mod tests {
         Channel,
         tokio::task::JoinHandle<SessionError>,
         sfio_tokio_mock_io::Handle,
+    ) {
+        spawn_client_loop_with(FrameWriter::tcp(), FramedReader::tcp())
+    }
+
+    fn spawn_client_loop_with(
+        writer: FrameWriter,
+        reader: FramedReader,
+    ) -> (
+        Channel,
+        tokio::task::JoinHandle<SessionError>,
+        sfio_tokio_mock_io::Handle,
     ) {
         let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let stats = SharedStatistics::default();
         let (mock, io_handle) = sfio_tokio_mock_io::mock();
         let mut client_loop = ClientLoop::new(
             rx.into(),
-            FrameWriter::tcp(),
-            FramedReader::tcp(),
+            16,
+            writer,
+            reader,
             DecodeLevel::default().application(AppDecodeLevel::DataValues),
+            stats.clone(),
         );
         let join_handle = tokio::spawn(async move {
             let mut phys = PhysLayer::new_mock(mock);
             client_loop.run(&mut phys).await
         });
-        let channel = Channel { tx };
+        let channel = Channel { tx, stats };
         (channel, join_handle, io_handle)
     }
 
     fn get_framed_adu<T>(function: FunctionCode, payload: &T) -> Vec<u8>
+    where
+        T: Serialize + Loggable + Sized,
+    {
+        get_framed_adu_with_tx_id(TxId::new(0), function, payload)
+    }
+
+    fn get_framed_adu_with_tx_id<T>(tx_id: TxId, function: FunctionCode, payload: &T) -> Vec<u8>
     where
         T: Serialize + Loggable + Sized,
     {
         let mut fmt = FrameWriter::tcp();
-        let header = FrameHeader::new_tcp_header(UnitId::new(1), TxId::new(0));
+        let header = FrameHeader::new_tcp_header(UnitId::new(1), tx_id);
+        let bytes = fmt
+            .format_request(header, function, payload, DecodeLevel::nothing())
+            .unwrap();
+        Vec::from(bytes)
+    }
+
+    #[cfg(feature = "serial")]
+    fn get_framed_rtu<T>(function: FunctionCode, payload: &T) -> Vec<u8>
+    where
+        T: Serialize + Loggable + Sized,
+    {
+        use crate::common::frame::FrameDestination;
+
+        let mut fmt = FrameWriter::rtu();
+        let header = FrameHeader::new_rtu_header(FrameDestination::UnitId(UnitId::new(1)));
         let bytes = fmt
             .format_request(header, function, payload, DecodeLevel::nothing())
             .unwrap();
         Vec::from(bytes)
     }
 
+    fn get_framed_exception(tx_id: TxId, function: FunctionCode, ex: ExceptionCode) -> Vec<u8> {
+        let mut fmt = FrameWriter::tcp();
+        let header = FrameHeader::new_tcp_header(UnitId::new(1), tx_id);
+        let bytes = fmt
+            .format_ex(
+                header,
+                FunctionField::Exception(function),
+                ex,
+                DecodeLevel::nothing(),
+            )
+            .unwrap();
+        Vec::from(bytes)
+    }
+
     #[tokio::test]
     async fn task_completes_with_shutdown_error_when_all_channels_dropped() {
         let (channel, task, _io) = spawn_client_loop();
@@ -459,4 +690,409 @@ mod tests {
             vec![Indexed::new(7, true), Indexed::new(8, false)]
         );
     }
+
+    #[tokio::test]
+    async fn retries_request_when_server_is_busy() {
+        let (mut channel, _task, mut io) = spawn_client_loop();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let response = get_framed_adu_with_tx_id(
+            TxId::new(1),
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: range }, |idx| match idx {
+                7 => Ok(true),
+                8 => Ok(false),
+                _ => Err(ExceptionCode::IllegalDataAddress),
+            }),
+        );
+
+        let coils = tokio::spawn(async move {
+            let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1)).with_retry(
+                RequestRetryPolicy::new(1, Duration::from_millis(1), Duration::from_millis(1)),
+            );
+            channel.read_coils(param, range).await
+        });
+
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu(FunctionCode::ReadCoils, &range))
+        );
+        io.read(&get_framed_exception(
+            TxId::new(0),
+            FunctionCode::ReadCoils,
+            ExceptionCode::ServerDeviceBusy,
+        ));
+        assert_eq!(io.next_event().await, Event::Read);
+
+        // the request is sent again with the next transaction id
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu_with_tx_id(
+                TxId::new(1),
+                FunctionCode::ReadCoils,
+                &range
+            ))
+        );
+        io.read(&response);
+
+        assert_eq!(
+            coils.await.unwrap().unwrap(),
+            vec![Indexed::new(7, true), Indexed::new(8, false)]
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_request_when_retries_are_exhausted() {
+        let (mut channel, _task, mut io) = spawn_client_loop();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+
+        let coils = tokio::spawn(async move {
+            let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1)).with_retry(
+                RequestRetryPolicy::new(1, Duration::from_millis(1), Duration::from_millis(1)),
+            );
+            channel.read_coils(param, range).await
+        });
+
+        for tx_id in 0..2 {
+            assert_eq!(
+                io.next_event().await,
+                Event::Write(get_framed_adu_with_tx_id(
+                    TxId::new(tx_id),
+                    FunctionCode::ReadCoils,
+                    &range
+                ))
+            );
+            io.read(&get_framed_exception(
+                TxId::new(tx_id),
+                FunctionCode::ReadCoils,
+                ExceptionCode::ServerDeviceBusy,
+            ));
+            assert_eq!(io.next_event().await, Event::Read);
+        }
+
+        assert_eq!(
+            coils.await.unwrap(),
+            Err(RequestError::Exception(ExceptionCode::ServerDeviceBusy))
+        );
+    }
+
+    #[tokio::test]
+    async fn disabling_the_channel_interrupts_retry_delay() {
+        let (channel, task, mut io) = spawn_client_loop();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+
+        let mut requester = channel.clone();
+        let coils = tokio::spawn(async move {
+            let param = RequestParam::new(UnitId::new(1), Duration::from_secs(1)).with_retry(
+                RequestRetryPolicy::new(1, Duration::from_secs(3600), Duration::from_secs(3600)),
+            );
+            requester.read_coils(param, range).await
+        });
+
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu(FunctionCode::ReadCoils, &range))
+        );
+        io.read(&get_framed_exception(
+            TxId::new(0),
+            FunctionCode::ReadCoils,
+            ExceptionCode::ServerDeviceBusy,
+        ));
+        assert_eq!(io.next_event().await, Event::Read);
+
+        channel.disable().await.unwrap();
+
+        assert_eq!(
+            coils.await.unwrap(),
+            Err(RequestError::Exception(ExceptionCode::ServerDeviceBusy))
+        );
+        assert_eq!(task.await.unwrap(), SessionError::Disabled);
+    }
+
+    #[cfg(feature = "serial")]
+    #[tokio::test]
+    async fn reserves_serial_line_until_response_timeout_of_abandoned_request() {
+        let (channel, _task, mut io) =
+            spawn_client_loop_with(FrameWriter::rtu(), FramedReader::rtu_response());
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let response = |values: [bool; 2]| {
+            get_framed_rtu(
+                FunctionCode::ReadCoils,
+                &BitWriter::new(ReadBitsRange { inner: range }, move |idx| {
+                    Ok(values[(idx - 7) as usize])
+                }),
+            )
+        };
+
+        let mut first = channel.clone();
+        let first = tokio::spawn(async move {
+            first
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(5))
+                        .with_deadline(Duration::from_secs(1)),
+                    range,
+                )
+                .await
+        });
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_rtu(FunctionCode::ReadCoils, &range))
+        );
+
+        tokio::time::pause();
+        assert_eq!(first.await.unwrap(), Err(RequestError::ResponseTimeout));
+
+        let mut second = channel.clone();
+        let second = tokio::spawn(async move {
+            second
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(5)),
+                    range,
+                )
+                .await
+        });
+
+        // the late response to the first request is not matched to the second one
+        io.read(&response([true, true]));
+        assert_eq!(io.next_event().await, Event::Read);
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_rtu(FunctionCode::ReadCoils, &range))
+        );
+        io.read(&response([false, true]));
+
+        assert_eq!(
+            second.await.unwrap().unwrap(),
+            vec![Indexed::new(7, false), Indexed::new(8, true)]
+        );
+    }
+
+    #[tokio::test]
+    async fn fails_request_whose_deadline_elapses_while_queued() {
+        let (mut channel, _task, mut io) = spawn_client_loop();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+
+        let mut first = channel.clone();
+        let first = tokio::spawn(async move {
+            first
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(5)),
+                    range,
+                )
+                .await
+        });
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu(FunctionCode::ReadCoils, &range))
+        );
+
+        // queued behind the first request which will time out
+        let mut second = channel.clone();
+        let second = tokio::spawn(async move {
+            second
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(5))
+                        .with_deadline(Duration::from_secs(1)),
+                    range,
+                )
+                .await
+        });
+
+        tokio::time::pause();
+
+        assert_eq!(first.await.unwrap(), Err(RequestError::ResponseTimeout));
+        assert_eq!(second.await.unwrap(), Err(RequestError::ResponseTimeout));
+
+        // the second request was never written, so the next one uses the next transaction id
+        let third = tokio::spawn(async move {
+            channel
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(5)),
+                    range,
+                )
+                .await
+        });
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu_with_tx_id(
+                TxId::new(1),
+                FunctionCode::ReadCoils,
+                &range
+            ))
+        );
+        drop(third);
+    }
+
+    #[tokio::test]
+    async fn discards_queued_request_when_future_is_dropped() {
+        let (mut channel, _task, mut io) = spawn_client_loop();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let param = RequestParam::new(UnitId::new(1), Duration::from_secs(5));
+
+        let mut first = channel.clone();
+        let first = tokio::spawn(async move { first.read_coils(param, range).await });
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu(FunctionCode::ReadCoils, &range))
+        );
+
+        // enqueue a request and then drop the future
+        {
+            let mut second = channel.clone();
+            let mut task = tokio_test::task::spawn(second.read_coils(param, range));
+            tokio_test::assert_pending!(task.poll());
+        }
+
+        io.read(&get_framed_adu(
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: range }, |_| Ok(true)),
+        ));
+        assert_eq!(io.next_event().await, Event::Read);
+        assert!(first.await.unwrap().is_ok());
+
+        // the cancelled request is skipped
+        let _third = tokio::spawn(async move { channel.read_coils(param, range).await });
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu_with_tx_id(
+                TxId::new(1),
+                FunctionCode::ReadCoils,
+                &range
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn abandons_in_flight_request_when_future_is_dropped() {
+        let (mut channel, _task, mut io) = spawn_client_loop();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+
+        {
+            let mut first = channel.clone();
+            let mut task = tokio_test::task::spawn(first.read_coils(
+                RequestParam::new(UnitId::new(1), Duration::from_secs(3600)),
+                range,
+            ));
+            tokio_test::assert_pending!(task.poll());
+            assert_eq!(
+                io.next_event().await,
+                Event::Write(get_framed_adu(FunctionCode::ReadCoils, &range))
+            );
+        }
+
+        // the next request is written without waiting for the response timeout
+        let _second = tokio::spawn(async move {
+            channel
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(5)),
+                    range,
+                )
+                .await
+        });
+        let event = tokio::time::timeout(Duration::from_secs(5), io.next_event())
+            .await
+            .unwrap();
+        assert_eq!(
+            event,
+            Event::Write(get_framed_adu_with_tx_id(
+                TxId::new(1),
+                FunctionCode::ReadCoils,
+                &range
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_high_priority_request_ahead_of_queued_requests() {
+        let (channel, _task, mut io) = spawn_client_loop();
+
+        let param = RequestParam::new(UnitId::new(1), Duration::from_secs(5));
+        let first_range = AddressRange::try_from(7, 2).unwrap();
+        let second_range = AddressRange::try_from(9, 2).unwrap();
+        let urgent_range = AddressRange::try_from(11, 2).unwrap();
+
+        let mut first = channel.clone();
+        let first = tokio::spawn(async move { first.read_coils(param, first_range).await });
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu(FunctionCode::ReadCoils, &first_range))
+        );
+
+        // queue a normal request followed by a high priority request
+        let mut second = channel.clone();
+        let mut second =
+            tokio_test::task::spawn(async move { second.read_coils(param, second_range).await });
+        tokio_test::assert_pending!(second.poll());
+        let mut urgent = channel.clone();
+        let mut urgent = tokio_test::task::spawn(async move {
+            urgent
+                .read_coils(param.with_priority(RequestPriority::High), urgent_range)
+                .await
+        });
+        tokio_test::assert_pending!(urgent.poll());
+
+        io.read(&get_framed_adu(
+            FunctionCode::ReadCoils,
+            &BitWriter::new(ReadBitsRange { inner: first_range }, |_| Ok(true)),
+        ));
+        assert_eq!(io.next_event().await, Event::Read);
+        assert!(first.await.unwrap().is_ok());
+
+        assert_eq!(
+            io.next_event().await,
+            Event::Write(get_framed_adu_with_tx_id(
+                TxId::new(1),
+                FunctionCode::ReadCoils,
+                &urgent_range
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn collects_statistics_for_each_unit() {
+        let (channel, _task, mut io) = spawn_client_loop();
+
+        let range = AddressRange::try_from(7, 2).unwrap();
+        let request = get_framed_adu(FunctionCode::ReadCoils, &range);
+        let response = get_framed_exception(
+            TxId::new(0),
+            FunctionCode::ReadCoils,
+            ExceptionCode::IllegalDataAddress,
+        );
+
+        let mut requester = channel.clone();
+        let result = tokio::spawn(async move {
+            requester
+                .read_coils(
+                    RequestParam::new(UnitId::new(1), Duration::from_secs(5)),
+                    range,
+                )
+                .await
+        });
+        assert_eq!(io.next_event().await, Event::Write(request.clone()));
+        io.read(&response);
+        assert_eq!(io.next_event().await, Event::Read);
+        assert_eq!(
+            result.await.unwrap(),
+            Err(RequestError::Exception(ExceptionCode::IllegalDataAddress))
+        );
+
+        let stats = channel.get_statistics(true).await;
+        assert_eq!(stats.bytes_sent, request.len() as u64);
+        assert_eq!(stats.bytes_received, response.len() as u64);
+        assert_eq!(stats.total.requests_sent, 1);
+        assert_eq!(stats.total.responses_received, 1);
+        assert_eq!(stats.total.latency.count(), 1);
+        let unit = &stats.units[&UnitId::new(1)];
+        assert_eq!(unit.requests_sent, 1);
+        assert_eq!(unit.exceptions[&ExceptionCode::IllegalDataAddress], 1);
+
+        assert_eq!(channel.get_statistics(false).await.total.requests_sent, 0);
+    }
 }
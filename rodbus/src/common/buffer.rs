@@ -135,8 +135,7 @@ mod tests {
         let mut phys = PhysLayer::new_mock(io);
 
         {
-            let mut task =
-                tokio_test::task::spawn(buffer.read_some(&mut phys, PhysDecodeLevel::Nothing));
+            let mut task = task::spawn(buffer.read_some(&mut phys, PhysDecodeLevel::Nothing));
             tokio_test::assert_pending!(task.poll());
         }
 
@@ -254,6 +254,7 @@ impl FormatType {
 pub(crate) struct FrameWriter {
     format_type: FormatType,
     buffer: [u8; constants::MAX_FRAME_LENGTH],
+    last_exception: Option<ExceptionCode>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -299,9 +300,15 @@ impl FrameWriter {
         Self {
             format_type,
             buffer: [0; constants::MAX_FRAME_LENGTH],
+            last_exception: None,
         }
     }
 
+    /// Exception code of the last formatted frame, if it was an exception response
+    pub(crate) fn last_exception(&self) -> Option<ExceptionCode> {
+        self.last_exception
+    }
+
     pub(crate) fn format_reply<T>(
         &mut self,
         header: FrameHeader,
@@ -350,6 +357,7 @@ impl FrameWriter {
         };
 
         let range = self.format_generic(header, function, &ex, decode_level)?;
+        self.last_exception = Some(ex);
 
         Ok(&self.buffer[range])
     }
@@ -364,6 +372,8 @@ impl FrameWriter {
     where
         T: Serialize + Loggable,
     {
+        self.last_exception = None;
+
         let (frame_type, frame_bytes, pdu_body) = {
             let mut cursor = WriteCursor::new(self.buffer.as_mut());
             let info = self
@@ -416,6 +426,15 @@ impl FrameWriter {
     pub(crate) fn rtu() -> Self {
         Self::new(FormatType::Rtu)
     }
+
+    /// True if responses can be matched to requests using a transaction id
+    pub(crate) fn has_tx_id(&self) -> bool {
+        match self.format_type {
+            FormatType::Tcp => true,
+            #[cfg(feature = "serial")]
+            FormatType::Rtu => false,
+        }
+    }
 }
 
 pub(crate) struct FramedReader {
@@ -4,6 +4,14 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 pub(crate) struct PhysLayer {
     layer: PhysLayerImpl,
+    counts: ByteCounts,
+}
+
+/// Number of bytes transferred since the counts were last taken
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ByteCounts {
+    pub(crate) sent: usize,
+    pub(crate) received: usize,
 }
 
 // encapsulates all possible physical layers as an enum
@@ -40,6 +48,7 @@ impl PhysLayer {
     pub(crate) fn new_tcp(socket: tokio::net::TcpStream) -> Self {
         Self {
             layer: PhysLayerImpl::Tcp(socket),
+            counts: ByteCounts::default(),
         }
     }
 
@@ -48,6 +57,7 @@ impl PhysLayer {
         let calculate_inter_character_delay = calculate_inter_character_delay(&stream);
         Self {
             layer: PhysLayerImpl::Serial(stream, calculate_inter_character_delay, None),
+            counts: ByteCounts::default(),
         }
     }
 
@@ -55,6 +65,7 @@ impl PhysLayer {
     pub(crate) fn new_tls(socket: tokio_rustls::TlsStream<tokio::net::TcpStream>) -> Self {
         Self {
             layer: PhysLayerImpl::Tls(Box::new(socket)),
+            counts: ByteCounts::default(),
         }
     }
 
@@ -62,9 +73,15 @@ impl PhysLayer {
     pub(crate) fn new_mock(mock: sfio_tokio_mock_io::Mock) -> Self {
         Self {
             layer: PhysLayerImpl::Mock(mock),
+            counts: ByteCounts::default(),
         }
     }
 
+    /// Retrieve the byte counts and reset them
+    pub(crate) fn take_counts(&mut self) -> ByteCounts {
+        std::mem::take(&mut self.counts)
+    }
+
     pub(crate) async fn read(
         &mut self,
         buffer: &mut [u8],
@@ -80,6 +97,8 @@ impl PhysLayer {
             PhysLayerImpl::Mock(x) => x.read(buffer).await?,
         };
 
+        self.counts.received += length;
+
         if decode_level.enabled() {
             if let Some(x) = buffer.get(0..length) {
                 tracing::info!("PHYS RX - {}", PhysDisplay::new(decode_level, x))
@@ -99,7 +118,7 @@ impl PhysLayer {
         }
 
         match &mut self.layer {
-            PhysLayerImpl::Tcp(x) => x.write_all(data).await,
+            PhysLayerImpl::Tcp(x) => x.write_all(data).await?,
             #[cfg(feature = "serial")]
             PhysLayerImpl::Serial(x, inter_char_delay, last_activity) => {
                 // Respect inter-character delay
@@ -108,13 +127,16 @@ impl PhysLayer {
                 }
                 *last_activity = Some(tokio::time::Instant::now());
 
-                x.write_all(data).await
+                x.write_all(data).await?
             }
             #[cfg(feature = "tls")]
-            PhysLayerImpl::Tls(x) => x.write_all(data).await,
+            PhysLayerImpl::Tls(x) => x.write_all(data).await?,
             #[cfg(test)]
-            PhysLayerImpl::Mock(x) => x.write_all(data).await,
+            PhysLayerImpl::Mock(x) => x.write_all(data).await?,
         }
+
+        self.counts.sent += data.len();
+        Ok(())
     }
 }
 
@@ -12,6 +12,34 @@ impl std::fmt::Display for Shutdown {
     }
 }
 
+/// Errors that can occur when adding or removing the handlers of a running server
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandlerUpdateError {
+    /// The type of the handler differs from the type of the handlers the server was spawned with
+    WrongHandlerType,
+    /// The server task has terminated
+    Shutdown,
+}
+
+impl std::error::Error for HandlerUpdateError {}
+
+impl std::fmt::Display for HandlerUpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HandlerUpdateError::WrongHandlerType => {
+                f.write_str("handler type does not match the handlers of the server")
+            }
+            HandlerUpdateError::Shutdown => Shutdown.fmt(f),
+        }
+    }
+}
+
+impl From<Shutdown> for HandlerUpdateError {
+    fn from(_: Shutdown) -> Self {
+        HandlerUpdateError::Shutdown
+    }
+}
+
 /// Top level error type for the client API
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum RequestError {
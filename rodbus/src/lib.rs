@@ -113,15 +113,12 @@
     dead_code,
     arithmetic_overflow,
     invalid_type_param_default,
-    missing_fragment_specifier,
     mutable_transmutes,
     no_mangle_const_items,
     overflowing_literals,
     patterns_in_fns_without_body,
     pub_use_of_private_extern_crate,
     unknown_crate_types,
-    order_dependent_trait_objects,
-    illegal_floating_point_literal_pattern,
     improper_ctypes,
     late_bound_lifetime_arguments,
     non_camel_case_types,
@@ -129,7 +126,6 @@
     non_snake_case,
     non_upper_case_globals,
     no_mangle_generic_items,
-    private_in_public,
     stable_features,
     type_alias_bounds,
     tyvar_behind_raw_pointer,
@@ -1,5 +1,8 @@
 use std::time::Duration;
 
+use crate::error::RequestError;
+use crate::exception::ExceptionCode;
+
 /// Trait that controls how the channel retries failed connect (TCP/TLS) or open (serial) attempts
 pub trait RetryStrategy: Send {
     /// Reset internal state. Called when a connection is successful or a port is opened
@@ -51,3 +54,151 @@ impl RetryStrategy for Doubling {
         self.min
     }
 }
+
+/// Policy that controls how the channel task retries a single request that failed
+///
+/// Retries are performed inside the channel task before the next queued request is started, so
+/// a retried request never loses its place in the queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestRetryPolicy {
+    /// Maximum number of retries performed after the initial attempt fails
+    pub max_retries: usize,
+    /// Delay before the first retry
+    pub min_delay: Duration,
+    /// Maximum delay between retries. The delay doubles after every retry up to this value.
+    pub max_delay: Duration,
+    /// Retry when no response is received before the response timeout
+    pub on_timeout: bool,
+    /// Retry when a malformed response is received
+    pub on_bad_response: bool,
+    /// Exception codes which cause the request to be retried
+    pub exceptions: ExceptionSet,
+}
+
+impl RequestRetryPolicy {
+    /// Create a policy that retries on timeouts, [`ExceptionCode::ServerDeviceBusy`] and
+    /// [`ExceptionCode::Acknowledge`]
+    pub fn new(max_retries: usize, min_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            min_delay,
+            max_delay,
+            on_timeout: true,
+            on_bad_response: false,
+            exceptions: ExceptionSet::empty()
+                .with(ExceptionCode::ServerDeviceBusy)
+                .with(ExceptionCode::Acknowledge),
+        }
+    }
+
+    /// Change whether response timeouts are retried
+    pub fn on_timeout(mut self, value: bool) -> Self {
+        self.on_timeout = value;
+        self
+    }
+
+    /// Change whether malformed responses are retried
+    pub fn on_bad_response(mut self, value: bool) -> Self {
+        self.on_bad_response = value;
+        self
+    }
+
+    /// Change the set of exception codes that are retried
+    pub fn exceptions(mut self, exceptions: ExceptionSet) -> Self {
+        self.exceptions = exceptions;
+        self
+    }
+
+    pub(crate) fn is_retryable(&self, err: RequestError) -> bool {
+        match err {
+            RequestError::ResponseTimeout => self.on_timeout,
+            RequestError::BadResponse(_) => self.on_bad_response,
+            RequestError::Exception(ex) => self.exceptions.contains(ex),
+            // everything else either kills the session or will never succeed
+            _ => false,
+        }
+    }
+
+    /// Return the delay before the next retry or None if the request shouldn't be retried
+    pub(crate) fn next_delay(&self, retries: usize, err: RequestError) -> Option<Duration> {
+        if retries >= self.max_retries || !self.is_retryable(err) {
+            return None;
+        }
+        let factor = 1u32.checked_shl(retries as u32).unwrap_or(u32::MAX);
+        let delay = self.min_delay.checked_mul(factor).unwrap_or(self.max_delay);
+        Some(std::cmp::min(delay, self.max_delay))
+    }
+}
+
+/// Set of exception codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExceptionSet {
+    bits: [u64; 4],
+}
+
+impl ExceptionSet {
+    /// Create an empty set
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Add an exception code to the set
+    pub fn with(mut self, ex: ExceptionCode) -> Self {
+        let value = u8::from(ex);
+        self.bits[(value / 64) as usize] |= 1 << (value % 64);
+        self
+    }
+
+    /// Check if the set contains an exception code
+    pub fn contains(&self, ex: ExceptionCode) -> bool {
+        let value = u8::from(ex);
+        self.bits[(value / 64) as usize] & (1 << (value % 64)) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AduParseError;
+
+    #[test]
+    fn exception_set_contains_only_added_codes() {
+        let set = ExceptionSet::empty()
+            .with(ExceptionCode::ServerDeviceBusy)
+            .with(ExceptionCode::Unknown(0xFF));
+        assert!(set.contains(ExceptionCode::ServerDeviceBusy));
+        assert!(set.contains(ExceptionCode::Unknown(0xFF)));
+        assert!(!set.contains(ExceptionCode::IllegalFunction));
+        assert!(!set.contains(ExceptionCode::Unknown(0xFE)));
+    }
+
+    #[test]
+    fn request_retry_delay_doubles_up_to_max() {
+        let policy =
+            RequestRetryPolicy::new(4, Duration::from_millis(100), Duration::from_millis(300));
+        let delays: Vec<Option<Duration>> = (0..5)
+            .map(|x| policy.next_delay(x, RequestError::ResponseTimeout))
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                Some(Duration::from_millis(100)),
+                Some(Duration::from_millis(200)),
+                Some(Duration::from_millis(300)),
+                Some(Duration::from_millis(300)),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn request_retry_only_retries_configured_errors() {
+        let policy = RequestRetryPolicy::new(1, Duration::from_millis(1), Duration::from_millis(1))
+            .on_timeout(false);
+        assert!(!policy.is_retryable(RequestError::ResponseTimeout));
+        assert!(policy.is_retryable(RequestError::Exception(ExceptionCode::ServerDeviceBusy)));
+        assert!(!policy.is_retryable(RequestError::Exception(ExceptionCode::IllegalDataAddress)));
+        assert!(!policy.is_retryable(RequestError::BadResponse(AduParseError::InsufficientBytes)));
+        assert!(!policy.is_retryable(RequestError::Io(std::io::ErrorKind::ConnectionReset)));
+    }
+}
@@ -3,6 +3,7 @@ use crate::decode::DecodeLevel;
 use crate::serial::SerialSettings;
 
 use crate::client::message::Command;
+use crate::client::statistics::SharedStatistics;
 use crate::client::task::{ClientLoop, SessionError, StateChange};
 use crate::client::{Listener, PortState, RetryStrategy};
 use crate::common::frame::{FrameWriter, FramedReader};
@@ -17,13 +18,16 @@ pub(crate) struct SerialChannelTask {
 }
 
 impl SerialChannelTask {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         path: &str,
         serial_settings: SerialSettings,
         rx: crate::channel::Receiver<Command>,
+        max_queued_requests: usize,
         retry: Box<dyn RetryStrategy>,
         decode: DecodeLevel,
         listener: Box<dyn Listener<PortState>>,
+        stats: SharedStatistics,
     ) -> Self {
         Self {
             path: path.to_string(),
@@ -31,9 +35,11 @@ impl SerialChannelTask {
             retry,
             client_loop: ClientLoop::new(
                 rx,
+                max_queued_requests,
                 FrameWriter::rtu(),
                 FramedReader::rtu_response(),
                 decode,
+                stats,
             ),
             listener,
         }
@@ -66,12 +72,14 @@ impl SerialChannelTask {
     pub(crate) async fn try_open_and_run(&mut self) -> Result<(), StateChange> {
         match crate::serial::open(self.path.as_str(), self.serial_settings) {
             Err(err) => {
+                self.client_loop.stats().on_connect_failure();
                 let delay = self.retry.after_failed_connect();
                 self.listener.update(PortState::Wait(delay)).get().await;
                 tracing::warn!("{} - waiting {} ms to re-open port", err, delay.as_millis());
                 self.client_loop.fail_requests_for(delay).await
             }
             Ok(serial) => {
+                self.client_loop.stats().on_connect();
                 self.retry.reset();
                 self.listener.update(PortState::Open).get().await;
                 let mut phys = PhysLayer::new_serial(serial);
@@ -81,6 +89,9 @@ impl SerialChannelTask {
                     SessionError::Shutdown => Err(StateChange::Shutdown),
                     // don't wait, we're disabled
                     SessionError::Disabled => Ok(()),
+                    // never requested by serial channels
+                    #[cfg(feature = "tls")]
+                    SessionError::TlsReload => Ok(()),
                     // wait before retrying
                     SessionError::IoError(_) | SessionError::BadFrame => {
                         let delay = self.retry.after_disconnect();
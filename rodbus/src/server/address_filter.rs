@@ -1,5 +1,17 @@
+use std::net::IpAddr;
 use std::str::FromStr;
 
+/// IPv4-mapped IPv6 addresses are reported for IPv4 clients of dual-stack sockets
+pub(crate) fn canonical(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(x) => match x.to_ipv4_mapped() {
+            Some(x) => IpAddr::V4(x),
+            None => addr,
+        },
+        IpAddr::V4(_) => addr,
+    }
+}
+
 /// Represents IPv4 addresses which may contain "*" wildcards
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct WildcardIPv4 {
@@ -42,7 +54,7 @@ impl FromStr for WildcardIPv4 {
 }
 
 impl WildcardIPv4 {
-    pub(crate) fn matches(&self, addr: std::net::IpAddr) -> bool {
+    pub(crate) fn matches(&self, addr: IpAddr) -> bool {
         fn bm(b: u8, other: Option<u8>) -> bool {
             match other {
                 Some(x) => b == x,
@@ -50,16 +62,157 @@ impl WildcardIPv4 {
             }
         }
 
-        match addr {
-            std::net::IpAddr::V4(x) => {
+        match canonical(addr) {
+            IpAddr::V4(x) => {
                 let [b3, b2, b1, b0] = x.octets();
                 bm(b3, self.b3) && bm(b2, self.b2) && bm(b1, self.b1) && bm(b0, self.b0)
             }
-            std::net::IpAddr::V6(_) => false,
+            IpAddr::V6(_) => false,
+        }
+    }
+}
+
+/// IPv4 or IPv6 network in CIDR notation, e.g. `10.20.0.0/16` or `fd00::/8`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct IpCidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+/// Error returned when a CIDR network is not in the correct format or the prefix length is too long
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BadIpCidr;
+
+impl IpCidr {
+    /// Create a network from an address and a prefix length. Bits of the address beyond the
+    /// prefix are ignored.
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Self, BadIpCidr> {
+        let max = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix > max {
+            return Err(BadIpCidr);
+        }
+        Ok(Self { addr, prefix })
+    }
+
+    /// Address of the network
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Length of the prefix in bits
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    pub(crate) fn matches(&self, addr: IpAddr) -> bool {
+        match (self.addr, canonical(addr)) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(addr) & mask
+            }
+            _ => false,
         }
     }
 }
 
+impl FromStr for IpCidr {
+    type Err = BadIpCidr;
+
+    /// Parse a network such as `10.20.0.0/16`. A single address without a prefix length is also accepted.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| BadIpCidr)?;
+        let prefix = match prefix {
+            Some(x) => x.parse::<u8>().map_err(|_| BadIpCidr)?,
+            None if addr.is_ipv4() => 32,
+            None => 128,
+        };
+        Self::new(addr, prefix)
+    }
+}
+
+/// Action taken when an [`AddressRule`] matches the address of a client
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RuleAction {
+    /// Accept the connection
+    Allow,
+    /// Close the connection
+    Deny,
+}
+
+/// A single rule of an [`AddressRules`] list
+#[derive(Clone, Debug)]
+pub struct AddressRule {
+    /// Action taken when the filter matches
+    pub action: RuleAction,
+    /// Addresses the rule applies to
+    pub filter: AddressFilter,
+}
+
+/// Ordered list of allow and deny rules. The first rule that matches an address decides
+/// whether the connection is accepted, and the default action applies if none match.
+#[derive(Clone, Debug)]
+pub struct AddressRules {
+    rules: Vec<AddressRule>,
+    default: RuleAction,
+}
+
+impl AddressRules {
+    /// Create an empty list of rules with the action taken when no rule matches
+    pub fn new(default: RuleAction) -> Self {
+        Self {
+            rules: Vec::new(),
+            default,
+        }
+    }
+
+    /// Append a rule that allows the matching addresses
+    pub fn allow(mut self, filter: AddressFilter) -> Self {
+        self.rules.push(AddressRule {
+            action: RuleAction::Allow,
+            filter,
+        });
+        self
+    }
+
+    /// Append a rule that denies the matching addresses
+    pub fn deny(mut self, filter: AddressFilter) -> Self {
+        self.rules.push(AddressRule {
+            action: RuleAction::Deny,
+            filter,
+        });
+        self
+    }
+
+    /// Rules in evaluation order
+    pub fn rules(&self) -> &[AddressRule] {
+        &self.rules
+    }
+
+    /// Action taken when no rule matches
+    pub fn default_action(&self) -> RuleAction {
+        self.default
+    }
+
+    fn evaluate(&self, addr: IpAddr) -> RuleAction {
+        self.rules
+            .iter()
+            .find(|x| x.filter.matches(addr))
+            .map(|x| x.action)
+            .unwrap_or(self.default)
+    }
+}
+
 /// Address filter used to control which master address(es) may connect to an outstation.
 ///
 /// Note: User code cannot exhaustively match against this enum as new variants may be added in the future.
@@ -69,28 +222,36 @@ pub enum AddressFilter {
     /// Allow any address
     Any,
     /// Allow a specific address
-    Exact(std::net::IpAddr),
+    Exact(IpAddr),
     /// Allow any of set of addresses
-    AnyOf(std::collections::HashSet<std::net::IpAddr>),
+    AnyOf(std::collections::HashSet<IpAddr>),
     /// Matches against an IPv4 address with wildcards
     WildcardIpv4(WildcardIPv4),
+    /// Allow any address within an IPv4 or IPv6 network
+    Cidr(IpCidr),
+    /// Evaluate an ordered list of allow and deny rules
+    Rules(AddressRules),
 }
 
 impl AddressFilter {
-    pub(crate) fn matches(&self, addr: std::net::IpAddr) -> bool {
+    pub(crate) fn matches(&self, addr: IpAddr) -> bool {
         match self {
             AddressFilter::Any => true,
-            AddressFilter::Exact(x) => *x == addr,
-            AddressFilter::AnyOf(set) => set.contains(&addr),
+            AddressFilter::Exact(x) => canonical(*x) == canonical(addr),
+            AddressFilter::AnyOf(set) => {
+                let addr = canonical(addr);
+                set.iter().any(|x| canonical(*x) == addr)
+            }
             AddressFilter::WildcardIpv4(wc) => wc.matches(addr),
+            AddressFilter::Cidr(cidr) => cidr.matches(addr),
+            AddressFilter::Rules(rules) => rules.evaluate(addr) == RuleAction::Allow,
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{BadIpv4Wildcard, WildcardIPv4};
-    use std::net::IpAddr;
+    use super::*;
 
     #[test]
     fn parses_address_with_subnet_wildcard() {
@@ -148,4 +309,82 @@ mod test {
         assert!(wc.matches(ip1));
         assert!(!wc.matches(ip2));
     }
+
+    #[test]
+    fn wildcard_matches_ipv4_mapped_addresses() {
+        let wc: WildcardIPv4 = "192.168.0.*".parse().unwrap();
+        assert!(wc.matches("::ffff:192.168.0.1".parse().unwrap()));
+        assert!(!wc.matches("fd00::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn parses_cidr_networks() {
+        let cidr: IpCidr = "10.20.0.0/16".parse().unwrap();
+        assert_eq!(cidr.addr(), "10.20.0.0".parse::<IpAddr>().unwrap());
+        assert_eq!(cidr.prefix(), 16);
+
+        let cidr: IpCidr = "fd00::1".parse().unwrap();
+        assert_eq!(cidr.prefix(), 128);
+
+        for x in [
+            "10.20.0.0/33",
+            "fd00::/129",
+            "10.20.0.0/",
+            "10.20.0/16",
+            "/8",
+        ] {
+            assert_eq!(x.parse::<IpCidr>(), Err(BadIpCidr));
+        }
+    }
+
+    #[test]
+    fn cidr_matching_works() {
+        let v4: IpCidr = "10.20.0.0/16".parse().unwrap();
+        assert!(v4.matches("10.20.255.1".parse().unwrap()));
+        assert!(v4.matches("::ffff:10.20.0.1".parse().unwrap()));
+        assert!(!v4.matches("10.21.0.1".parse().unwrap()));
+        assert!(!v4.matches("fd00::1".parse().unwrap()));
+
+        let v6: IpCidr = "fd00::/8".parse().unwrap();
+        assert!(v6.matches("fd12:3456::1".parse().unwrap()));
+        assert!(!v6.matches("fe80::1".parse().unwrap()));
+        assert!(!v6.matches("10.20.0.1".parse().unwrap()));
+
+        let all: IpCidr = "0.0.0.0/0".parse().unwrap();
+        assert!(all.matches("192.168.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn rules_are_evaluated_in_order() {
+        let rules = AddressRules::new(RuleAction::Deny)
+            .deny(AddressFilter::Exact("10.20.0.5".parse().unwrap()))
+            .allow(AddressFilter::Cidr("10.20.0.0/16".parse().unwrap()))
+            .allow(AddressFilter::Cidr("fd00::/8".parse().unwrap()));
+        let filter = AddressFilter::Rules(rules);
+
+        assert!(!filter.matches("10.20.0.5".parse().unwrap()));
+        assert!(filter.matches("10.20.0.6".parse().unwrap()));
+        assert!(filter.matches("fd00::5".parse().unwrap()));
+        assert!(!filter.matches("192.168.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn exact_addresses_match_ipv4_mapped_addresses() {
+        let exact = AddressFilter::Exact("10.20.0.5".parse().unwrap());
+        assert!(exact.matches("::ffff:10.20.0.5".parse().unwrap()));
+        assert!(!exact.matches("::ffff:10.20.0.6".parse().unwrap()));
+
+        let mapped = AddressFilter::Exact("::ffff:10.20.0.5".parse().unwrap());
+        assert!(mapped.matches("10.20.0.5".parse().unwrap()));
+
+        let set = AddressFilter::AnyOf(
+            ["10.20.0.5", "::ffff:10.20.0.6"]
+                .iter()
+                .map(|x| x.parse().unwrap())
+                .collect(),
+        );
+        assert!(set.matches("::ffff:10.20.0.5".parse().unwrap()));
+        assert!(set.matches("10.20.0.6".parse().unwrap()));
+        assert!(!set.matches("10.20.0.7".parse().unwrap()));
+    }
 }
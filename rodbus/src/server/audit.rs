@@ -0,0 +1,331 @@
+#[cfg(feature = "audit")]
+use std::fs::File;
+#[cfg(feature = "audit")]
+use std::io::Write;
+use std::net::SocketAddr;
+#[cfg(feature = "audit")]
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+#[cfg(feature = "audit")]
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc,
+};
+use std::time::SystemTime;
+
+use crate::server::{Authorization, AuthorizationRequest};
+use crate::types::UnitId;
+
+/// Authorization decision reported to an [`AuditSink`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorizationRecord {
+    /// Time at which the decision was made
+    pub timestamp: SystemTime,
+    /// Identifier of the session that received the request
+    pub session: Option<u128>,
+    /// Address of the remote client
+    pub peer: Option<SocketAddr>,
+    /// Roles of the client
+    pub roles: Vec<String>,
+    /// Unit id the request was addressed to
+    pub unit: UnitId,
+    /// Request that was authorized or denied
+    pub request: AuthorizationRequest,
+    /// Outcome of the decision
+    pub outcome: Authorization,
+}
+
+#[cfg(feature = "audit")]
+impl AuthorizationRecord {
+    /// Format the record as a single line of JSON, without the line terminator
+    pub fn to_json(&self) -> String {
+        let range = self.request.range();
+        let record = JsonRecord {
+            timestamp_ms: self
+                .timestamp
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis(),
+            session: self.session,
+            peer: self.peer.map(|x| x.to_string()),
+            roles: &self.roles,
+            unit: self.unit.value,
+            function: self.request.function_code(),
+            start: range.start,
+            count: range.count,
+            outcome: match self.outcome {
+                Authorization::Allow => "allow",
+                Authorization::Deny => "deny",
+            },
+        };
+        // serializing a struct of plain values cannot fail
+        serde_json::to_string(&record).unwrap_or_default()
+    }
+}
+
+/// Layout of the JSON representation of an [`AuthorizationRecord`]
+#[cfg(feature = "audit")]
+#[derive(serde::Serialize)]
+struct JsonRecord<'a> {
+    timestamp_ms: u128,
+    session: Option<u128>,
+    peer: Option<String>,
+    roles: &'a [String],
+    unit: u8,
+    function: u8,
+    start: u16,
+    count: u16,
+    outcome: &'static str,
+}
+
+/// Receives every authorization decision made by a server with an [`crate::server::AuthorizationHandler`]
+///
+/// The sink is invoked from the session tasks, implementations should not block for long.
+pub trait AuditSink: Send + Sync + 'static {
+    /// Moves a sink implementation into an `Arc` suitable for passing to the server
+    fn wrap(self) -> Arc<dyn AuditSink>
+    where
+        Self: Sized,
+    {
+        Arc::new(self)
+    }
+
+    /// Record an authorization decision
+    fn record(&self, record: &AuthorizationRecord);
+}
+
+/// Number of records queued by a [`JsonLinesAuditSink`] before new records are dropped
+#[cfg(feature = "audit")]
+pub const JSON_LINES_AUDIT_CAPACITY: usize = 1024;
+
+/// [`AuditSink`] that appends each record as a line of JSON to a file
+///
+/// When the file would exceed the maximum size, it is renamed with a `.1` suffix, previously
+/// rotated files are shifted by one and the oldest one is deleted.
+///
+/// Records are formatted by the session tasks and written by a dedicated thread, so that file
+/// I/O never blocks the sessions. At most [`JSON_LINES_AUDIT_CAPACITY`] records are queued,
+/// records received while the queue is full are dropped and counted by [`Self::dropped`].
+///
+/// Dropping the sink does not wait for the queued records to be written, use [`Self::flush`]
+/// or [`Self::close`] to do so. Both block the calling thread.
+#[cfg(feature = "audit")]
+pub struct JsonLinesAuditSink {
+    tx: mpsc::SyncSender<Message>,
+    writer: std::thread::JoinHandle<()>,
+    dropped: AtomicU64,
+}
+
+#[cfg(feature = "audit")]
+enum Message {
+    Record(String),
+    // completed once the records queued before it are written
+    Flush(mpsc::SyncSender<()>),
+}
+
+#[cfg(feature = "audit")]
+impl JsonLinesAuditSink {
+    /// Create a sink that appends to `path`, keeping at most `max_rotated_files` files of
+    /// `max_file_size` bytes besides the current one
+    pub fn new(path: &Path, max_file_size: u64, max_rotated_files: usize) -> std::io::Result<Self> {
+        let mut file = RotatingFile {
+            path: path.to_owned(),
+            max_file_size,
+            max_rotated_files,
+            file: Some(RotatingFile::open(path)?),
+        };
+
+        let (tx, rx) = mpsc::sync_channel(JSON_LINES_AUDIT_CAPACITY);
+        let writer = std::thread::Builder::new()
+            .name("rodbus-audit".to_string())
+            .spawn(move || {
+                // runs until the sink is dropped or closed
+                for message in rx {
+                    match message {
+                        Message::Record(line) => {
+                            if let Err(err) = file.write(&line) {
+                                tracing::error!(
+                                    "unable to write audit record to {}: {}",
+                                    file.path.display(),
+                                    err
+                                );
+                            }
+                        }
+                        Message::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            tx,
+            writer,
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// Number of records dropped because the queue was full or the writer thread exited
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Block until the records received before this call are written
+    pub fn flush(&self) {
+        let (tx, rx) = mpsc::sync_channel(1);
+        if self.tx.send(Message::Flush(tx)).is_ok() {
+            // fails if the writer thread exited
+            let _ = rx.recv();
+        }
+    }
+
+    /// Write the queued records and stop the writer thread, blocking until it exits
+    pub fn close(self) {
+        let Self { tx, writer, .. } = self;
+        // closing the channel ends the writer thread once the queued records are written
+        drop(tx);
+        let _ = writer.join();
+    }
+}
+
+#[cfg(feature = "audit")]
+impl AuditSink for JsonLinesAuditSink {
+    fn record(&self, record: &AuthorizationRecord) {
+        if let Err(err) = self.tx.try_send(Message::Record(record.to_json())) {
+            let count = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+            match err {
+                mpsc::TrySendError::Full(_) => {
+                    tracing::warn!("audit queue is full, dropped {} record(s)", count)
+                }
+                mpsc::TrySendError::Disconnected(_) => {
+                    tracing::error!(
+                        "audit writer thread has exited, dropped {} record(s)",
+                        count
+                    )
+                }
+            }
+        }
+    }
+}
+
+/// File written by the thread of a [`JsonLinesAuditSink`]
+#[cfg(feature = "audit")]
+struct RotatingFile {
+    path: PathBuf,
+    max_file_size: u64,
+    max_rotated_files: usize,
+    file: Option<(File, u64)>,
+}
+
+#[cfg(feature = "audit")]
+impl RotatingFile {
+    fn open(path: &Path) -> std::io::Result<(File, u64)> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let size = file.metadata()?.len();
+        Ok((file, size))
+    }
+
+    fn rotate(&self) -> std::io::Result<()> {
+        if self.max_rotated_files == 0 {
+            return std::fs::remove_file(&self.path);
+        }
+        for index in (1..self.max_rotated_files).rev() {
+            let from = rotated(&self.path, index);
+            if from.exists() {
+                std::fs::rename(from, rotated(&self.path, index + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, rotated(&self.path, 1))
+    }
+
+    fn write(&mut self, line: &str) -> std::io::Result<()> {
+        if self.file.is_none() {
+            // a previous rotation failed, try to reopen the file
+            self.file = Some(Self::open(&self.path)?);
+        }
+
+        let len = line.len() as u64 + 1;
+        let size = self
+            .file
+            .as_ref()
+            .map(|(_, size)| *size)
+            .unwrap_or_default();
+        if size > 0 && size + len > self.max_file_size {
+            // close the current file before renaming it
+            self.file.take();
+            self.rotate()?;
+            self.file = Some(Self::open(&self.path)?);
+        }
+
+        if let Some((file, size)) = self.file.as_mut() {
+            file.write_all(format!("{line}\n").as_bytes())?;
+            file.flush()?;
+            *size += len;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "audit")]
+fn rotated(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.to_owned().into_os_string();
+    name.push(format!(".{index}"));
+    name.into()
+}
+
+#[cfg(all(test, feature = "audit"))]
+mod tests {
+    use super::*;
+    use crate::types::AddressRange;
+
+    fn record(outcome: Authorization) -> AuthorizationRecord {
+        AuthorizationRecord {
+            timestamp: SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(1500),
+            session: Some(3),
+            peer: Some("127.0.0.1:502".parse().unwrap()),
+            roles: vec!["operator".to_string(), "a \"quoted\" role".to_string()],
+            unit: UnitId::new(1),
+            request: AuthorizationRequest::WriteMultipleRegisters(AddressRange {
+                start: 10,
+                count: 4,
+            }),
+            outcome,
+        }
+    }
+
+    #[test]
+    fn formats_records_as_json() {
+        assert_eq!(
+            record(Authorization::Deny).to_json(),
+            r#"{"timestamp_ms":1500,"session":3,"peer":"127.0.0.1:502","roles":["operator","a \"quoted\" role"],"unit":1,"function":16,"start":10,"count":4,"outcome":"deny"}"#
+        );
+    }
+
+    #[test]
+    fn rotates_files() {
+        let dir = std::env::temp_dir().join(format!("rodbus-audit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.jsonl");
+
+        let line_len = record(Authorization::Allow).to_json().len() as u64 + 1;
+        // two records per file, two rotated files
+        let sink = JsonLinesAuditSink::new(&path, 2 * line_len, 2).unwrap();
+        for _ in 0..7 {
+            sink.record(&record(Authorization::Allow));
+        }
+        sink.flush();
+        assert_eq!(sink.dropped(), 0);
+        sink.close();
+
+        let lines = |path: &Path| std::fs::read_to_string(path).unwrap().lines().count();
+        assert_eq!(lines(&path), 1);
+        assert_eq!(lines(&rotated(&path, 1)), 2);
+        assert_eq!(lines(&rotated(&path, 2)), 2);
+        assert!(!rotated(&path, 3).exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
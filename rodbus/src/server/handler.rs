@@ -1,6 +1,8 @@
 use std::collections::BTreeMap;
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 
+use crate::common::function::FunctionCode;
 use crate::exception::ExceptionCode;
 use crate::server::{WriteCoils, WriteRegisters};
 use crate::types::*;
@@ -23,6 +25,13 @@ pub trait RequestHandler: Send + 'static {
         Arc::new(Mutex::new(Box::new(self)))
     }
 
+    /// Informs the handler of the session that submitted the request processed next
+    ///
+    /// Called before each request, including broadcasts, while the lock on the handler is held.
+    /// A handler may be shared by several sessions, so the context only applies to the request
+    /// that follows.
+    fn set_context(&mut self, _context: RequestContext) {}
+
     /// Read single coil or return an ExceptionCode
     fn read_coil(&self, _address: u16) -> Result<bool, ExceptionCode> {
         Err(ExceptionCode::IllegalFunction)
@@ -64,6 +73,20 @@ pub trait RequestHandler: Send + 'static {
     }
 }
 
+/// Session that submitted a request, see [`RequestHandler::set_context`]
+#[derive(Debug, Clone, Copy)]
+pub struct RequestContext<'a> {
+    /// Identifier of the session, `None` for RTU servers
+    pub session: Option<u128>,
+    /// Address of the client, `None` for RTU servers
+    pub peer: Option<SocketAddr>,
+    /// Roles of the client, empty if the server does not authorize requests
+    pub roles: &'a [String],
+    /// Parameters of the TLS session, `None` unless the session uses TLS
+    #[cfg(feature = "tls")]
+    pub tls: Option<&'a crate::tcp::tls::TlsSessionInfo>,
+}
+
 /// Trait useful for converting None into IllegalDataAddress
 pub trait IllegalAddressConversion<T> {
     /// convert into a Result of the value
@@ -85,11 +108,25 @@ where
 /// Server handler boxed inside a `Arc<Mutex>`.
 pub type ServerHandlerType<T> = Arc<Mutex<Box<T>>>;
 
+/// Determines how the server responds to requests for a unit id without a handler
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum UnmappedUnitResponse {
+    /// Do not respond, the client eventually times out
+    #[default]
+    NoResponse,
+    /// Respond with [`ExceptionCode::GatewayTargetDeviceFailedToRespond`] like a gateway would
+    GatewayTargetDeviceFailedToRespond,
+    /// Respond with [`ExceptionCode::IllegalFunction`]
+    IllegalFunction,
+}
+
 /// Type that hides the underlying map implementation
 /// and allows lookups of a [`RequestHandler`] from a [`UnitId`]
 #[derive(Debug, Default)]
 pub struct ServerHandlerMap<T: RequestHandler> {
     handlers: BTreeMap<UnitId, ServerHandlerType<T>>,
+    default: Option<ServerHandlerType<T>>,
+    unmapped: UnmappedUnitResponse,
 }
 
 // this couldn't be derived automatically
@@ -101,6 +138,8 @@ where
     fn clone(&self) -> Self {
         ServerHandlerMap {
             handlers: self.handlers.clone(),
+            default: self.default.clone(),
+            unmapped: self.unmapped,
         }
     }
 }
@@ -113,6 +152,8 @@ where
     pub fn new() -> Self {
         Self {
             handlers: BTreeMap::new(),
+            default: None,
+            unmapped: UnmappedUnitResponse::default(),
         }
     }
 
@@ -120,7 +161,11 @@ where
     pub fn single(id: UnitId, handler: ServerHandlerType<T>) -> Self {
         let mut map: BTreeMap<UnitId, ServerHandlerType<T>> = BTreeMap::new();
         map.insert(id, handler);
-        Self { handlers: map }
+        Self {
+            handlers: map,
+            default: None,
+            unmapped: UnmappedUnitResponse::default(),
+        }
     }
 
     /// Retrieve a mutable reference to a [`RequestHandler`]
@@ -137,8 +182,95 @@ where
         self.handlers.insert(id, server)
     }
 
+    /// Remove the handler of a unit id from the map
+    pub fn remove(&mut self, id: UnitId) -> Option<ServerHandlerType<T>> {
+        self.handlers.remove(&id)
+    }
+
+    /// Set the handler used for requests to any unit id without a handler of its own,
+    /// e.g. for single-device TCP endpoints addressed with unit id 0xFF
+    ///
+    /// The default handler also receives broadcast requests.
+    pub fn set_default(&mut self, handler: ServerHandlerType<T>) -> Option<ServerHandlerType<T>> {
+        self.default.replace(handler)
+    }
+
+    /// Change how the server responds to requests for a unit id without a handler when no
+    /// default handler is set. By default, the server does not respond.
+    pub fn set_unmapped_unit_response(&mut self, response: UnmappedUnitResponse) {
+        self.unmapped = response;
+    }
+
+    /// Retrieve the handler of a unit id, falling back on the default handler
+    pub(crate) fn resolve(&mut self, id: UnitId) -> Option<&mut ServerHandlerType<T>> {
+        match self.handlers.get_mut(&id) {
+            Some(handler) => Some(handler),
+            None => self.default.as_mut(),
+        }
+    }
+
+    pub(crate) fn unmapped_unit_response(&self) -> UnmappedUnitResponse {
+        self.unmapped
+    }
+
+    /// Iterate over the handlers that receive broadcast requests
+    ///
+    /// The default handler is skipped if it is also mapped to a unit id.
     pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut ServerHandlerType<T>> {
-        self.handlers.values_mut()
+        let default = match &mut self.default {
+            Some(x) if !self.handlers.values().any(|h| Arc::ptr_eq(h, x)) => Some(x),
+            _ => None,
+        };
+        self.handlers.values_mut().chain(default)
+    }
+}
+
+/// Handler map shared by a server and all of its sessions, so that handler changes
+/// apply to every session without forwarding them
+pub(crate) struct SharedHandlerMap<T: RequestHandler> {
+    inner: Arc<Mutex<ServerHandlerMap<T>>>,
+}
+
+impl<T: RequestHandler> Clone for SharedHandlerMap<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: RequestHandler> SharedHandlerMap<T> {
+    pub(crate) fn new(map: ServerHandlerMap<T>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(map)),
+        }
+    }
+
+    pub(crate) fn add(&self, id: UnitId, handler: ServerHandlerType<T>) {
+        self.inner.lock().unwrap().add(id, handler);
+    }
+
+    pub(crate) fn remove(&self, id: UnitId) {
+        self.inner.lock().unwrap().remove(id);
+    }
+
+    /// Retrieve the handler of a unit id, or how to respond if there is none
+    pub(crate) fn resolve(&self, id: UnitId) -> Result<ServerHandlerType<T>, UnmappedUnitResponse> {
+        let mut map = self.inner.lock().unwrap();
+        match map.resolve(id) {
+            Some(handler) => Ok(handler.clone()),
+            None => Err(map.unmapped_unit_response()),
+        }
+    }
+
+    /// Handlers that receive broadcast requests
+    pub(crate) fn broadcast_handlers(&self) -> Vec<ServerHandlerType<T>> {
+        self.inner
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .map(|x| x.clone())
+            .collect()
     }
 }
 
@@ -151,7 +283,63 @@ pub enum Authorization {
     Deny,
 }
 
+/// Request submitted to an [`AuthorizationHandler`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthorizationRequest {
+    /// Read Coils request
+    ReadCoils(AddressRange),
+    /// Read Discrete Inputs request
+    ReadDiscreteInputs(AddressRange),
+    /// Read Holding Registers request
+    ReadHoldingRegisters(AddressRange),
+    /// Read Input Registers request
+    ReadInputRegisters(AddressRange),
+    /// Write Single Coil request
+    WriteSingleCoil(u16),
+    /// Write Single Register request
+    WriteSingleRegister(u16),
+    /// Write Multiple Coils request
+    WriteMultipleCoils(AddressRange),
+    /// Write Multiple Registers request
+    WriteMultipleRegisters(AddressRange),
+}
+
+impl AuthorizationRequest {
+    /// Function code of the request
+    pub fn function_code(&self) -> u8 {
+        let function = match self {
+            Self::ReadCoils(_) => FunctionCode::ReadCoils,
+            Self::ReadDiscreteInputs(_) => FunctionCode::ReadDiscreteInputs,
+            Self::ReadHoldingRegisters(_) => FunctionCode::ReadHoldingRegisters,
+            Self::ReadInputRegisters(_) => FunctionCode::ReadInputRegisters,
+            Self::WriteSingleCoil(_) => FunctionCode::WriteSingleCoil,
+            Self::WriteSingleRegister(_) => FunctionCode::WriteSingleRegister,
+            Self::WriteMultipleCoils(_) => FunctionCode::WriteMultipleCoils,
+            Self::WriteMultipleRegisters(_) => FunctionCode::WriteMultipleRegisters,
+        };
+        function.get_value()
+    }
+
+    /// Range of addresses accessed by the request, a single address for Write Single requests
+    pub fn range(&self) -> AddressRange {
+        match *self {
+            Self::ReadCoils(x)
+            | Self::ReadDiscreteInputs(x)
+            | Self::ReadHoldingRegisters(x)
+            | Self::ReadInputRegisters(x)
+            | Self::WriteMultipleCoils(x)
+            | Self::WriteMultipleRegisters(x) => x,
+            Self::WriteSingleCoil(x) | Self::WriteSingleRegister(x) => {
+                AddressRange { start: x, count: 1 }
+            }
+        }
+    }
+}
+
 /// Authorization handler used in Modbus Security protocol
+///
+/// The handler may also be used by plain TCP and RTU servers. The role passed to the handler is
+/// then the IP address of the client or the path of the serial port respectively.
 pub trait AuthorizationHandler: Send + Sync + 'static {
     /// Moves an authorization handler implementation into a `Arc<Mutex<Box<AuthorizationHandler>>>`
     /// suitable for passing to the server
@@ -162,6 +350,51 @@ pub trait AuthorizationHandler: Send + Sync + 'static {
         Arc::new(self)
     }
 
+    /// Authorize a request with all the roles of the client
+    ///
+    /// The default implementation allows the request if at least one of the roles is allowed by
+    /// the method specific to the request. Override it to make decisions based on the whole set.
+    fn authorize(
+        &self,
+        unit_id: UnitId,
+        request: AuthorizationRequest,
+        roles: &[String],
+    ) -> Authorization {
+        let allowed = roles.iter().any(|role| {
+            let result = match request {
+                AuthorizationRequest::ReadCoils(x) => self.read_coils(unit_id, x, role),
+                AuthorizationRequest::ReadDiscreteInputs(x) => {
+                    self.read_discrete_inputs(unit_id, x, role)
+                }
+                AuthorizationRequest::ReadHoldingRegisters(x) => {
+                    self.read_holding_registers(unit_id, x, role)
+                }
+                AuthorizationRequest::ReadInputRegisters(x) => {
+                    self.read_input_registers(unit_id, x, role)
+                }
+                AuthorizationRequest::WriteSingleCoil(x) => {
+                    self.write_single_coil(unit_id, x, role)
+                }
+                AuthorizationRequest::WriteSingleRegister(x) => {
+                    self.write_single_register(unit_id, x, role)
+                }
+                AuthorizationRequest::WriteMultipleCoils(x) => {
+                    self.write_multiple_coils(unit_id, x, role)
+                }
+                AuthorizationRequest::WriteMultipleRegisters(x) => {
+                    self.write_multiple_registers(unit_id, x, role)
+                }
+            };
+            result == Authorization::Allow
+        });
+
+        if allowed {
+            Authorization::Allow
+        } else {
+            Authorization::Deny
+        }
+    }
+
     /// Authorize a Read Coils request
     fn read_coils(&self, _unit_id: UnitId, _range: AddressRange, _role: &str) -> Authorization {
         Authorization::Deny
@@ -346,4 +579,53 @@ mod tests {
         assert!(map.add(UnitId::new(2), DefaultHandler {}.wrap()).is_none());
         assert!(map.add(UnitId::new(1), DefaultHandler {}.wrap()).is_some());
     }
+
+    #[test]
+    fn resolves_unmapped_unit_ids_to_default_handler() {
+        let mut map = ServerHandlerMap::single(UnitId::new(1), DefaultHandler {}.wrap());
+        assert!(map.resolve(UnitId::new(0xFF)).is_none());
+        assert_eq!(map.iter_mut().count(), 1);
+
+        let default = DefaultHandler {}.wrap();
+        assert!(map.set_default(default.clone()).is_none());
+        let resolved = map.resolve(UnitId::new(0xFF)).unwrap();
+        assert!(Arc::ptr_eq(resolved, &default));
+        let resolved = map.resolve(UnitId::new(1)).unwrap();
+        assert!(!Arc::ptr_eq(resolved, &default));
+        assert_eq!(map.iter_mut().count(), 2);
+
+        // a default handler that is also mapped to a unit id only receives broadcasts once
+        map.add(UnitId::new(2), default);
+        assert_eq!(map.iter_mut().count(), 2);
+    }
+
+    struct OperatorHandler;
+    impl AuthorizationHandler for OperatorHandler {
+        fn write_single_coil(&self, _unit_id: UnitId, _idx: u16, role: &str) -> Authorization {
+            if role == "operator" {
+                Authorization::Allow
+            } else {
+                Authorization::Deny
+            }
+        }
+    }
+
+    #[test]
+    fn default_authorize_allows_request_if_any_role_is_allowed() {
+        let request = AuthorizationRequest::WriteSingleCoil(7);
+        let roles = |x: &[&str]| x.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            OperatorHandler.authorize(UnitId::new(1), request, &roles(&["viewer", "operator"])),
+            Authorization::Allow
+        );
+        assert_eq!(
+            OperatorHandler.authorize(UnitId::new(1), request, &roles(&["viewer"])),
+            Authorization::Deny
+        );
+        assert_eq!(
+            OperatorHandler.authorize(UnitId::new(1), request, &[]),
+            Authorization::Deny
+        );
+    }
 }
@@ -0,0 +1,182 @@
+use crate::server::AddressFilter;
+
+/// Determines which session is closed when accepting a new connection would exceed a session limit
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum MaxSessionPolicy {
+    /// Close the oldest session to make room for the new connection
+    #[default]
+    EvictOldest,
+    /// Keep the existing sessions and close the new connection
+    RejectNewest,
+    /// Close the session that least recently received a request to make room for the new connection
+    EvictLeastRecentlyActive,
+}
+
+/// Limits applied to the sessions of a TCP or TLS server when accepting a new connection
+///
+/// When a limit is reached, the [`MaxSessionPolicy`] only selects among the sessions that count
+/// against that limit, e.g. when a client exceeds the per-IP limit, one of its own sessions is
+/// closed or its new connection is rejected.
+#[derive(Debug, Clone, Default)]
+pub struct SessionLimits {
+    pub(crate) policy: MaxSessionPolicy,
+    pub(crate) max_per_ip: Option<usize>,
+    pub(crate) reserved: Option<(AddressFilter, usize)>,
+}
+
+impl SessionLimits {
+    /// Create limits that only apply the maximum number of sessions of the server with the specified policy
+    pub fn new(policy: MaxSessionPolicy) -> Self {
+        Self {
+            policy,
+            max_per_ip: None,
+            reserved: None,
+        }
+    }
+
+    /// Limit the number of concurrent sessions from a single IP address
+    pub fn with_max_per_ip(mut self, max: usize) -> Self {
+        self.max_per_ip = Some(max);
+        self
+    }
+
+    /// Reserve some of the sessions for addresses matching a priority filter. Other addresses
+    /// may only use the remaining sessions, and when the server is full, a connection from a priority
+    /// address closes a non-priority session first.
+    pub fn with_reserved_sessions(mut self, filter: AddressFilter, count: usize) -> Self {
+        self.reserved = Some((filter, count));
+        self
+    }
+
+    pub(crate) fn is_priority(&self, addr: std::net::IpAddr) -> bool {
+        match &self.reserved {
+            Some((filter, _)) => filter.matches(addr),
+            None => false,
+        }
+    }
+
+    pub(crate) fn reserved_count(&self) -> usize {
+        match &self.reserved {
+            Some((_, count)) => *count,
+            None => 0,
+        }
+    }
+}
+
+/// Action taken when a session exceeds its request rate
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum RateLimitAction {
+    /// Reply to the request with [`crate::ExceptionCode::ServerDeviceBusy`] without invoking the handler
+    #[default]
+    ReplyBusy,
+    /// Close the session
+    Disconnect,
+}
+
+/// Token bucket limiting the rate at which a session may submit requests
+///
+/// The bucket starts full and holds up to `burst` tokens. It is refilled at `requests_per_second`
+/// and each request consumes one token.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RateLimit {
+    pub(crate) requests_per_second: u32,
+    pub(crate) burst: u32,
+    pub(crate) action: RateLimitAction,
+}
+
+impl RateLimit {
+    /// Create a rate limit. A `burst` of zero is treated as one.
+    pub fn new(requests_per_second: u32, burst: u32, action: RateLimitAction) -> Self {
+        Self {
+            requests_per_second,
+            burst: burst.max(1),
+            action,
+        }
+    }
+}
+
+/// Limits applied to the traffic of each session of a TCP or TLS server
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ActivityLimits {
+    pub(crate) idle_timeout: Option<std::time::Duration>,
+    pub(crate) rate_limit: Option<RateLimit>,
+}
+
+impl ActivityLimits {
+    /// Close sessions that do not receive a frame within the specified duration
+    ///
+    /// TLS sessions must also complete their handshake within this duration.
+    pub fn with_idle_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Limit the rate at which each session may submit requests
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+}
+
+pub(crate) struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_refill: tokio::time::Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(limit: RateLimit, now: tokio::time::Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.burst as f64,
+            last_refill: now,
+        }
+    }
+
+    pub(crate) fn action(&self) -> RateLimitAction {
+        self.limit.action
+    }
+
+    /// Consume a token, returns false if the bucket is empty
+    pub(crate) fn try_acquire(&mut self, now: tokio::time::Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.last_refill = now;
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.limit.requests_per_second as f64)
+            .min(self.limit.burst as f64);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn token_bucket_allows_bursts_and_refills_over_time() {
+        let start = tokio::time::Instant::now();
+        let mut bucket = TokenBucket::new(RateLimit::new(10, 2, RateLimitAction::ReplyBusy), start);
+
+        assert!(bucket.try_acquire(start));
+        assert!(bucket.try_acquire(start));
+        assert!(!bucket.try_acquire(start));
+
+        // a token is added every 100ms
+        assert!(!bucket.try_acquire(start + Duration::from_millis(50)));
+        assert!(bucket.try_acquire(start + Duration::from_millis(100)));
+        assert!(!bucket.try_acquire(start + Duration::from_millis(100)));
+
+        // the bucket never holds more than the burst size
+        let later = start + Duration::from_secs(10);
+        assert!(bucket.try_acquire(later));
+        assert!(bucket.try_acquire(later));
+        assert!(!bucket.try_acquire(later));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::server::AuthorizationHandler;
+use crate::tcp::server::TcpServerConnectionHandler;
+
+#[cfg(feature = "tls")]
+use crate::server::TlsServerConfig;
+
+/// Listening socket bound before spawning a TCP or TLS server, e.g. to bind port 0 or
+/// to use a socket inherited from the parent process
+#[derive(Debug)]
+pub enum BoundListener {
+    /// Standard library listener, switched to non-blocking mode when the server is spawned
+    Std(std::net::TcpListener),
+    /// Tokio listener
+    Tokio(tokio::net::TcpListener),
+}
+
+impl BoundListener {
+    pub(crate) fn into_tokio(self) -> Result<tokio::net::TcpListener, std::io::Error> {
+        match self {
+            Self::Std(listener) => {
+                listener.set_nonblocking(true)?;
+                tokio::net::TcpListener::from_std(listener)
+            }
+            Self::Tokio(listener) => Ok(listener),
+        }
+    }
+}
+
+impl From<std::net::TcpListener> for BoundListener {
+    fn from(value: std::net::TcpListener) -> Self {
+        Self::Std(value)
+    }
+}
+
+impl From<tokio::net::TcpListener> for BoundListener {
+    fn from(value: tokio::net::TcpListener) -> Self {
+        Self::Tokio(value)
+    }
+}
+
+/// Listening socket of a server along with the protocol used by the connections it accepts
+///
+/// A server spawned with several endpoints shares the handlers, the session limits and the
+/// settings of its [`crate::server::ServerHandle`] across all of them.
+pub struct ServerEndpoint {
+    pub(crate) listener: BoundListener,
+    pub(crate) handler: TcpServerConnectionHandler,
+}
+
+impl ServerEndpoint {
+    /// Accept plain TCP connections on the listener
+    pub fn tcp(listener: impl Into<BoundListener>) -> Self {
+        Self {
+            listener: listener.into(),
+            handler: TcpServerConnectionHandler::Tcp(None),
+        }
+    }
+
+    /// Accept plain TCP connections on the listener and authorize requests using the IP address
+    /// of the client as its identity
+    ///
+    /// The handler receives the IP address in place of the role, e.g. `192.168.0.10` or `::1`.
+    pub fn tcp_with_authz(
+        listener: impl Into<BoundListener>,
+        auth_handler: std::sync::Arc<dyn AuthorizationHandler>,
+    ) -> Self {
+        Self {
+            listener: listener.into(),
+            handler: TcpServerConnectionHandler::Tcp(Some(auth_handler)),
+        }
+    }
+
+    /// Accept TLS connections on the listener without requiring the client role extension
+    #[cfg(feature = "tls")]
+    pub fn tls(listener: impl Into<BoundListener>, tls_config: TlsServerConfig) -> Self {
+        Self {
+            listener: listener.into(),
+            handler: TcpServerConnectionHandler::Tls(Box::new(tls_config), None),
+        }
+    }
+
+    /// Accept "Secure Modbus" TLS connections on the listener and authorize requests using
+    /// the role extension of the client certificate
+    #[cfg(feature = "tls")]
+    pub fn tls_with_authz(
+        listener: impl Into<BoundListener>,
+        auth_handler: std::sync::Arc<dyn AuthorizationHandler>,
+        tls_config: TlsServerConfig,
+    ) -> Self {
+        Self {
+            listener: listener.into(),
+            handler: TcpServerConnectionHandler::Tls(Box::new(tls_config), Some(auth_handler)),
+        }
+    }
+}
@@ -2,26 +2,48 @@ use std::net::SocketAddr;
 
 use tracing::Instrument;
 
+use crate::client::Listener;
 use crate::decode::DecodeLevel;
-use crate::server::task::ServerSetting;
-use crate::tcp::server::{ServerTask, TcpServerConnectionHandler};
+use crate::server::statistics::SharedServerState;
+use crate::server::task::{ServerCommand, ServerSetting};
+use crate::tcp::server::{Endpoint, ServerTask, TcpServerConnectionHandler};
 
 /// server handling
 mod address_filter;
+mod audit;
 pub(crate) mod handler;
+mod limits;
+mod listener;
+mod options;
+#[cfg(feature = "policy")]
+mod policy;
 pub(crate) mod request;
 pub(crate) mod response;
+pub(crate) mod statistics;
 pub(crate) mod task;
 pub(crate) mod types;
+mod writes;
 
 /// Fine for this to be a constant since the corresponding channel is only used to change settings
 pub(crate) const SERVER_SETTING_CHANNEL_CAPACITY: usize = 8;
 
-use crate::error::Shutdown;
+/// Number of write events buffered for each subscriber of [`ServerHandle::subscribe_writes`]
+pub const WRITE_EVENT_CAPACITY: usize = 256;
+
+use crate::error::{HandlerUpdateError, Shutdown};
+use crate::types::UnitId;
 
 pub use address_filter::*;
+pub use audit::*;
 pub use handler::*;
+pub use limits::*;
+pub use listener::*;
+pub use options::*;
+#[cfg(feature = "policy")]
+pub use policy::*;
+pub use statistics::*;
 pub use types::*;
+pub use writes::*;
 
 // re-export to the public API
 #[cfg(feature = "tls")]
@@ -29,23 +51,258 @@ pub use crate::tcp::tls::server::TlsServerConfig;
 #[cfg(feature = "tls")]
 pub use crate::tcp::tls::*;
 
+/// Determines what happens to requests that are being processed when a server is shut down
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ShutdownMode {
+    /// Sessions finish the request they are processing, including writing the response
+    FinishRequests,
+    /// Session tasks are aborted immediately
+    ///
+    /// RTU servers always finish the request they are processing
+    Abort,
+}
+
 /// Handle to the server async task. The task is shutdown when the handle is dropped.
 #[derive(Debug)]
 pub struct ServerHandle {
-    tx: tokio::sync::mpsc::Sender<ServerSetting>,
+    tx: tokio::sync::mpsc::Sender<ServerCommand>,
+    state: SharedServerState,
+    // every task spawned by the server holds a sender, so this completes once they have all exited
+    completion: tokio::sync::mpsc::Receiver<()>,
+    local_addrs: Vec<SocketAddr>,
+    // type of the handlers the server was spawned with
+    handler_type: std::any::TypeId,
 }
 
 impl ServerHandle {
-    /// Construct a [ServerHandle] from its fields
+    pub(crate) fn new<T: RequestHandler>(
+        tx: tokio::sync::mpsc::Sender<ServerCommand>,
+        state: SharedServerState,
+        completion: tokio::sync::mpsc::Receiver<()>,
+        local_addrs: Vec<SocketAddr>,
+    ) -> Self {
+        ServerHandle {
+            tx,
+            state,
+            completion,
+            local_addrs,
+            handler_type: std::any::TypeId::of::<T>(),
+        }
+    }
+
+    /// Add or replace the handler of a unit id. The change applies to active sessions and future sessions,
+    /// including requests that are received after this future completes.
+    ///
+    /// The handler must be of the same type as the handlers the server was spawned with.
+    pub async fn add_handler<T: RequestHandler>(
+        &mut self,
+        unit_id: UnitId,
+        handler: ServerHandlerType<T>,
+    ) -> Result<(), HandlerUpdateError> {
+        if std::any::TypeId::of::<T>() != self.handler_type {
+            return Err(HandlerUpdateError::WrongHandlerType);
+        }
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.tx
+            .send(ServerCommand::AddHandler(
+                unit_id,
+                Box::new(handler),
+                Some(tx),
+            ))
+            .await
+            .map_err(|_| HandlerUpdateError::Shutdown)?;
+        rx.await.map_err(|_| HandlerUpdateError::Shutdown)?;
+        Ok(())
+    }
+
+    /// Remove the handler of a unit id. The change applies to active sessions and future sessions.
+    ///
+    /// Requests for the unit id are then processed by the default handler or answered
+    /// according to the [`UnmappedUnitResponse`] of the [`ServerHandlerMap`].
+    pub async fn remove_handler(&mut self, unit_id: UnitId) -> Result<(), HandlerUpdateError> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.tx
+            .send(ServerCommand::RemoveHandler(unit_id, Some(tx)))
+            .await
+            .map_err(|_| HandlerUpdateError::Shutdown)?;
+        rx.await.map_err(|_| HandlerUpdateError::Shutdown)?;
+        Ok(())
+    }
+
+    /// Local address of the listening socket, e.g. to retrieve the port chosen by the OS when binding port 0
+    ///
+    /// For servers with several endpoints, this is the address of the first endpoint.
+    /// Always `None` for RTU servers.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addrs.first().copied()
+    }
+
+    /// Local addresses of all the listening sockets in the order the endpoints were supplied
+    ///
+    /// Always empty for RTU servers
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+
+    /// Shutdown the server and wait until the server task and every session task have exited
+    ///
+    /// The server stops accepting connections immediately. Dropping the handle shuts the server
+    /// down in the same way as [`ShutdownMode::FinishRequests`], without waiting for the tasks to exit.
+    pub async fn shutdown(self, mode: ShutdownMode) {
+        let Self {
+            tx,
+            state: _,
+            mut completion,
+            local_addrs: _,
+            handler_type: _,
+        } = self;
+
+        // fails if the server task already exited
+        let _ = tx.send(ServerCommand::Shutdown(mode)).await;
+        drop(tx);
+
+        // the senders are never used, so this only returns once they are all dropped
+        while completion.recv().await.is_some() {}
+    }
+
+    /// Retrieve information about the active sessions
     ///
-    /// This function is only required for the C bindings
-    pub fn new(tx: tokio::sync::mpsc::Sender<ServerSetting>) -> Self {
-        ServerHandle { tx }
+    /// The list is always empty for RTU servers
+    pub fn get_sessions(&self) -> Vec<SessionInfo> {
+        self.state.sessions()
+    }
+
+    /// Retrieve the aggregate statistics of the server, optionally resetting them
+    ///
+    /// Resetting also clears the counters of each active session
+    pub fn get_statistics(&self, reset: bool) -> ServerStatistics {
+        self.state.statistics(reset)
+    }
+
+    /// Subscribe to the write requests successfully processed by the handlers of the server
+    ///
+    /// Events are published after the handler has committed the write. A receiver that falls more than
+    /// [`WRITE_EVENT_CAPACITY`] events behind loses the oldest events and is notified that it lagged.
+    pub fn subscribe_writes(&self) -> tokio::sync::broadcast::Receiver<WriteEvent> {
+        self.state.subscribe_writes()
+    }
+
+    /// Set or clear the hook invoked to validate write requests before they are passed to the handlers.
+    /// The change applies immediately to all sessions.
+    pub fn set_write_validator(&self, validator: Option<std::sync::Arc<dyn WriteValidator>>) {
+        self.state.set_write_validator(validator);
+    }
+
+    /// Set or clear the sink receiving every authorization decision made by the [`AuthorizationHandler`].
+    /// The change applies immediately to all sessions. Use [`ServerOptions::with_audit_sink`] to
+    /// record the decisions from the first connection.
+    pub fn set_audit_sink(&self, sink: Option<std::sync::Arc<dyn AuditSink>>) {
+        self.state.set_audit_sink(sink);
     }
 
     /// Change the decoding level for future sessions and all active sessions
     pub async fn set_decode_level(&mut self, level: DecodeLevel) -> Result<(), Shutdown> {
-        self.tx.send(ServerSetting::ChangeDecoding(level)).await?;
+        self.tx
+            .send(ServerCommand::Setting(ServerSetting::ChangeDecoding(level)))
+            .await?;
+        Ok(())
+    }
+
+    /// Close the session with the specified id
+    ///
+    /// Only applies to TCP and TLS servers
+    pub async fn disconnect_session(&mut self, id: u128) -> Result<(), Shutdown> {
+        self.tx.send(ServerCommand::DisconnectSession(id)).await?;
+        Ok(())
+    }
+
+    /// Close all active sessions. The server keeps accepting new connections.
+    ///
+    /// Only applies to TCP and TLS servers
+    pub async fn disconnect_all(&mut self) -> Result<(), Shutdown> {
+        self.tx.send(ServerCommand::DisconnectAll).await?;
+        Ok(())
+    }
+
+    /// Close the listening socket so that no new connections are accepted. Active sessions
+    /// keep running until they are closed by the remote client or disconnected.
+    ///
+    /// Only applies to TCP and TLS servers
+    pub async fn stop_listening(&mut self) -> Result<(), Shutdown> {
+        self.tx.send(ServerCommand::StopListening).await?;
+        Ok(())
+    }
+
+    /// Change the limits applied to the sessions when accepting new connections. Active sessions
+    /// are only closed to make room for a new connection.
+    ///
+    /// By default, only the maximum number of sessions passed when spawning the server is enforced
+    /// by closing the oldest session. Use [`ServerOptions::with_session_limits`] to apply limits
+    /// from the first connection.
+    ///
+    /// Only applies to TCP and TLS servers
+    pub async fn set_session_limits(&mut self, limits: SessionLimits) -> Result<(), Shutdown> {
+        self.tx
+            .send(ServerCommand::SetSessionLimits(limits))
+            .await?;
+        Ok(())
+    }
+
+    /// Replace the filter applied to the address of new connections. Active sessions whose address
+    /// is not allowed by the new filter are closed.
+    ///
+    /// Only applies to TCP and TLS servers
+    pub async fn set_address_filter(&mut self, filter: AddressFilter) -> Result<(), Shutdown> {
+        self.tx
+            .send(ServerCommand::SetAddressFilter(filter))
+            .await?;
+        Ok(())
+    }
+
+    /// Change the idle timeout and the request rate limit of future sessions and all active sessions
+    ///
+    /// Sessions are not limited by default, see [`ServerOptions::with_activity_limits`] to limit
+    /// them from the first connection. Changing the limits refills the rate limit of each session.
+    ///
+    /// Only applies to TCP and TLS servers
+    pub async fn set_activity_limits(&mut self, limits: ActivityLimits) -> Result<(), Shutdown> {
+        self.tx
+            .send(ServerCommand::Setting(ServerSetting::ChangeActivityLimits(
+                limits,
+            )))
+            .await?;
+        Ok(())
+    }
+
+    /// Replace the TLS configuration used for new handshakes on all of the TLS endpoints of the server.
+    /// The authorization handler of each endpoint is retained.
+    ///
+    /// The policy determines whether the sessions established on the TLS endpoints are closed.
+    ///
+    /// Only applies to TLS servers
+    #[cfg(feature = "tls")]
+    pub async fn set_tls_config(
+        &mut self,
+        config: TlsServerConfig,
+        policy: TlsReloadPolicy,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(ServerCommand::SetTlsConfig(config, policy))
+            .await?;
+        Ok(())
+    }
+
+    /// Set the listener notified when sessions are opened and closed
+    ///
+    /// Events are delivered in order from a separate task, a listener that falls too far
+    /// behind loses the newest events. Only applies to TCP and TLS servers
+    pub async fn set_session_listener(
+        &mut self,
+        listener: Box<dyn Listener<SessionEvent>>,
+    ) -> Result<(), Shutdown> {
+        self.tx
+            .send(ServerCommand::SetSessionListener(listener))
+            .await?;
         Ok(())
     }
 }
@@ -70,26 +327,174 @@ pub async fn spawn_tcp_server_task<T: RequestHandler>(
     decode: DecodeLevel,
 ) -> Result<ServerHandle, std::io::Error> {
     let listener = tokio::net::TcpListener::bind(addr).await?;
+    spawn_tcp_server_task_with_listener(max_sessions, listener, handlers, filter, decode)
+}
+
+/// Spawns a TCP server task onto the runtime using a listening socket that is already bound
+///
+/// * `max_sessions` - Maximum number of concurrent sessions
+/// * `listener` - A bound `std::net::TcpListener` or `tokio::net::TcpListener`
+/// * `handlers` - A map of handlers keyed by a unit id
+/// * `filter` - Address filter which may be used to restrict the connecting IP address
+/// * `decode` - Decode log level
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+pub fn spawn_tcp_server_task_with_listener<T: RequestHandler>(
+    max_sessions: usize,
+    listener: impl Into<BoundListener>,
+    handlers: ServerHandlerMap<T>,
+    filter: AddressFilter,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    spawn_tcp_listener_task(
+        max_sessions,
+        listener.into().into_tokio()?,
+        handlers,
+        None,
+        filter,
+        decode,
+    )
+}
+
+/// Spawns a TCP server task onto the runtime that checks the authorization of requests against
+/// the supplied handler. Without client certificates, the IP address of the client is used as its
+/// identity and passed to the handler in place of the role, e.g. `192.168.0.10` or `::1`.
+///
+/// Each incoming connection will spawn a new task to handle it.
+///
+/// * `max_sessions` - Maximum number of concurrent sessions
+/// * `addr` - A socket address to bound to
+/// * `handlers` - A map of handlers keyed by a unit id
+/// * `auth_handler` - Handler used to authorize requests
+/// * `filter` - Address filter which may be used to restrict the connecting IP address
+/// * `decode` - Decode log level
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+pub async fn spawn_tcp_server_task_with_authz<T: RequestHandler>(
+    max_sessions: usize,
+    addr: SocketAddr,
+    handlers: ServerHandlerMap<T>,
+    auth_handler: std::sync::Arc<dyn AuthorizationHandler>,
+    filter: AddressFilter,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    spawn_tcp_listener_task(
+        max_sessions,
+        listener,
+        handlers,
+        Some(auth_handler),
+        filter,
+        decode,
+    )
+}
+
+fn spawn_tcp_listener_task<T: RequestHandler>(
+    max_sessions: usize,
+    listener: tokio::net::TcpListener,
+    handlers: ServerHandlerMap<T>,
+    auth_handler: Option<std::sync::Arc<dyn AuthorizationHandler>>,
+    filter: AddressFilter,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    let local_addr = listener.local_addr()?;
+    spawn_endpoints_task(
+        max_sessions,
+        vec![Endpoint::new(
+            listener,
+            TcpServerConnectionHandler::Tcp(auth_handler),
+        )],
+        handlers,
+        filter,
+        decode,
+        ServerOptions::default(),
+        tracing::info_span!("Modbus-Server-TCP", "listen" = ?local_addr),
+    )
+}
+
+/// Spawns a server task onto the runtime that accepts connections on several endpoints, e.g.
+/// plain TCP on one interface and TLS on another. All the endpoints share the handlers, the
+/// session limits and the settings of the returned handle.
+///
+/// * `max_sessions` - Maximum number of concurrent sessions across all endpoints
+/// * `endpoints` - Listening sockets and the protocol of the connections they accept
+/// * `handlers` - A map of handlers keyed by a unit id
+/// * `filter` - Address filter which may be used to restrict the connecting IP address
+/// * `decode` - Decode log level
+/// * `options` - Settings applied from the first accepted connection, e.g. the session limits
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+pub fn spawn_server_task<T: RequestHandler>(
+    max_sessions: usize,
+    endpoints: Vec<ServerEndpoint>,
+    handlers: ServerHandlerMap<T>,
+    filter: AddressFilter,
+    decode: DecodeLevel,
+    options: ServerOptions,
+) -> Result<ServerHandle, std::io::Error> {
+    let endpoints = endpoints
+        .into_iter()
+        .map(|x| Ok(Endpoint::new(x.listener.into_tokio()?, x.handler)))
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    let local_addrs = endpoints
+        .iter()
+        .map(|x| x.local_addr())
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    spawn_endpoints_task(
+        max_sessions,
+        endpoints,
+        handlers,
+        filter,
+        decode,
+        options,
+        tracing::info_span!("Modbus-Server", "listen" = ?local_addrs),
+    )
+}
+
+fn spawn_endpoints_task<T: RequestHandler>(
+    max_sessions: usize,
+    endpoints: Vec<Endpoint>,
+    handlers: ServerHandlerMap<T>,
+    filter: AddressFilter,
+    decode: DecodeLevel,
+    options: ServerOptions,
+    span: tracing::Span,
+) -> Result<ServerHandle, std::io::Error> {
+    let local_addrs = endpoints
+        .iter()
+        .map(|x| x.local_addr())
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
 
     let (tx, rx) = tokio::sync::mpsc::channel(SERVER_SETTING_CHANNEL_CAPACITY);
+    let (completion_tx, completion_rx) = tokio::sync::mpsc::channel(1);
+    let state = SharedServerState::default();
+    state.set_audit_sink(options.audit_sink.clone());
+    let task_state = state.clone();
 
     let task = async move {
         ServerTask::new(
             max_sessions,
-            listener,
+            endpoints,
             handlers,
-            TcpServerConnectionHandler::Tcp,
             filter,
             decode,
+            options,
+            task_state,
+            completion_tx,
         )
         .run(rx)
-        .instrument(tracing::info_span!("Modbus-Server-TCP", "listen" = ?addr))
+        .instrument(span)
         .await;
     };
 
     tokio::spawn(task);
 
-    Ok(ServerHandle::new(tx))
+    Ok(ServerHandle::new::<T>(
+        tx,
+        state,
+        completion_rx,
+        local_addrs,
+    ))
 }
 
 /// Spawns a RTU server task onto the runtime.
@@ -109,14 +514,68 @@ pub fn spawn_rtu_server_task<T: RequestHandler>(
     handlers: ServerHandlerMap<T>,
     decode: DecodeLevel,
 ) -> Result<ServerHandle, std::io::Error> {
-    let (tx, rx) = tokio::sync::mpsc::channel(SERVER_SETTING_CHANNEL_CAPACITY);
-    let session = crate::server::task::SessionTask::new(
+    spawn_rtu_server_task_impl(
+        path,
+        settings,
+        retry,
         handlers,
-        crate::server::task::AuthorizationType::None,
+        task::AuthorizationType::None,
+        decode,
+    )
+}
+
+/// Spawns a RTU server task onto the runtime that checks the authorization of requests against
+/// the supplied handler. The path of the serial port is used as the identity of the client and passed
+/// to the handler in place of the role.
+///
+/// * `path` - Path to the serial device. Generally `/dev/tty0` on Linux and `COM1` on Windows.
+/// * `settings` - Serial port settings
+/// * `retry` - A boxed trait object that controls when opening the serial port is retried after a failure
+/// * `handlers` - A map of handlers keyed by a unit id
+/// * `auth_handler` - Handler used to authorize requests
+/// * `decode` - Decode log level
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+#[cfg(feature = "serial")]
+pub fn spawn_rtu_server_task_with_authz<T: RequestHandler>(
+    path: &str,
+    settings: crate::serial::SerialSettings,
+    retry: Box<dyn crate::retry::RetryStrategy>,
+    handlers: ServerHandlerMap<T>,
+    auth_handler: std::sync::Arc<dyn AuthorizationHandler>,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    spawn_rtu_server_task_impl(
+        path,
+        settings,
+        retry,
+        handlers,
+        task::AuthorizationType::Handler(auth_handler, vec![path.to_string()]),
+        decode,
+    )
+}
+
+#[cfg(feature = "serial")]
+fn spawn_rtu_server_task_impl<T: RequestHandler>(
+    path: &str,
+    settings: crate::serial::SerialSettings,
+    retry: Box<dyn crate::retry::RetryStrategy>,
+    handlers: ServerHandlerMap<T>,
+    auth: task::AuthorizationType,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    let (tx, rx) = tokio::sync::mpsc::channel(SERVER_SETTING_CHANNEL_CAPACITY);
+    let (completion_tx, completion_rx) = tokio::sync::mpsc::channel::<()>(1);
+    let state = SharedServerState::default();
+    let session = task::SessionTask::new(
+        SharedHandlerMap::new(handlers),
+        auth,
         crate::common::frame::FrameWriter::rtu(),
         crate::common::frame::FramedReader::rtu_request(),
         rx,
         decode,
+        state.session(None),
+        None,
     );
 
     let mut rtu = crate::serial::server::RtuServerTask {
@@ -129,6 +588,7 @@ pub fn spawn_rtu_server_task<T: RequestHandler>(
     let path = path.to_string();
 
     let task = async move {
+        let _completion = completion_tx;
         rtu.run()
             .instrument(tracing::info_span!("Modbus-Server-RTU", "port" = ?path))
             .await
@@ -136,7 +596,7 @@ pub fn spawn_rtu_server_task<T: RequestHandler>(
 
     tokio::spawn(task);
 
-    Ok(ServerHandle::new(tx))
+    Ok(ServerHandle::new::<T>(tx, state, completion_rx, Vec::new()))
 }
 
 /// Spawns a "raw" TLS server task onto the runtime. This TLS server does NOT require that
@@ -212,6 +672,72 @@ pub async fn spawn_tls_server_task_with_authz<T: RequestHandler>(
     .await
 }
 
+/// Spawns a "raw" TLS server task onto the runtime using a listening socket that is already bound.
+/// This TLS server does NOT require that the client certificate contain the Role extension and allows
+/// all operations for any authenticated client.
+///
+/// * `max_sessions` - Maximum number of concurrent sessions
+/// * `listener` - A bound `std::net::TcpListener` or `tokio::net::TcpListener`
+/// * `handlers` - A map of handlers keyed by a unit id
+/// * `tls_config` - TLS configuration
+/// * `filter` - Address filter which may be used to restrict the connecting IP address
+/// * `decode` - Decode log level
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+#[cfg(feature = "tls")]
+pub fn spawn_tls_server_task_with_listener<T: RequestHandler>(
+    max_sessions: usize,
+    listener: impl Into<BoundListener>,
+    handlers: ServerHandlerMap<T>,
+    tls_config: TlsServerConfig,
+    filter: AddressFilter,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    spawn_tls_listener_task(
+        max_sessions,
+        listener.into().into_tokio()?,
+        handlers,
+        None,
+        tls_config,
+        filter,
+        decode,
+    )
+}
+
+/// Spawns a "Secure Modbus" TLS server task onto the runtime using a listening socket that is already
+/// bound. This TLS server requires that the client certificate contain the Role extension and checks the
+/// authorization of requests against the supplied handler.
+///
+/// * `max_sessions` - Maximum number of concurrent sessions
+/// * `listener` - A bound `std::net::TcpListener` or `tokio::net::TcpListener`
+/// * `handlers` - A map of handlers keyed by a unit id
+/// * `auth_handler` - Handler used to authorize requests
+/// * `tls_config` - TLS configuration
+/// * `filter` - Address filter which may be used to restrict the connecting IP address
+/// * `decode` - Decode log level
+///
+/// `WARNING`: This function must be called from with the context of the Tokio runtime or it will panic.
+#[cfg(feature = "tls")]
+pub fn spawn_tls_server_task_with_authz_and_listener<T: RequestHandler>(
+    max_sessions: usize,
+    listener: impl Into<BoundListener>,
+    handlers: ServerHandlerMap<T>,
+    auth_handler: std::sync::Arc<dyn AuthorizationHandler>,
+    tls_config: TlsServerConfig,
+    filter: AddressFilter,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    spawn_tls_listener_task(
+        max_sessions,
+        listener.into().into_tokio()?,
+        handlers,
+        Some(auth_handler),
+        tls_config,
+        filter,
+        decode,
+    )
+}
+
 #[cfg(feature = "tls")]
 async fn spawn_tls_server_task_impl<T: RequestHandler>(
     max_sessions: usize,
@@ -223,24 +749,38 @@ async fn spawn_tls_server_task_impl<T: RequestHandler>(
     decode: DecodeLevel,
 ) -> Result<ServerHandle, std::io::Error> {
     let listener = tokio::net::TcpListener::bind(addr).await?;
+    spawn_tls_listener_task(
+        max_sessions,
+        listener,
+        handlers,
+        auth_handler,
+        tls_config,
+        filter,
+        decode,
+    )
+}
 
-    let (tx, rx) = tokio::sync::mpsc::channel(SERVER_SETTING_CHANNEL_CAPACITY);
-
-    let task = async move {
-        ServerTask::new(
-            max_sessions,
+#[cfg(feature = "tls")]
+fn spawn_tls_listener_task<T: RequestHandler>(
+    max_sessions: usize,
+    listener: tokio::net::TcpListener,
+    handlers: ServerHandlerMap<T>,
+    auth_handler: Option<std::sync::Arc<dyn AuthorizationHandler>>,
+    tls_config: TlsServerConfig,
+    filter: AddressFilter,
+    decode: DecodeLevel,
+) -> Result<ServerHandle, std::io::Error> {
+    let local_addr = listener.local_addr()?;
+    spawn_endpoints_task(
+        max_sessions,
+        vec![Endpoint::new(
             listener,
-            handlers,
-            TcpServerConnectionHandler::Tls(tls_config, auth_handler),
-            filter,
-            decode,
-        )
-        .run(rx)
-        .instrument(tracing::info_span!("Modbus-Server-TLS", "listen" = ?addr))
-        .await
-    };
-
-    tokio::spawn(task);
-
-    Ok(ServerHandle::new(tx))
+            TcpServerConnectionHandler::Tls(Box::new(tls_config), auth_handler),
+        )],
+        handlers,
+        filter,
+        decode,
+        ServerOptions::default(),
+        tracing::info_span!("Modbus-Server-TLS", "listen" = ?local_addr),
+    )
 }
@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use crate::server::{ActivityLimits, AuditSink, SessionLimits};
+
+/// Settings applied by a server from the moment it is spawned
+///
+/// Each of them may also be changed at runtime using the [`crate::server::ServerHandle`], but
+/// connections accepted before the change is applied use the initial value.
+#[derive(Clone, Default)]
+pub struct ServerOptions {
+    pub(crate) session_limits: SessionLimits,
+    pub(crate) activity_limits: ActivityLimits,
+    pub(crate) audit_sink: Option<Arc<dyn AuditSink>>,
+}
+
+impl ServerOptions {
+    /// Create options with the default value of each setting
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits applied to the sessions when accepting a new connection
+    pub fn with_session_limits(mut self, limits: SessionLimits) -> Self {
+        self.session_limits = limits;
+        self
+    }
+
+    /// Idle timeout and request rate limit of each session, including the TLS handshake
+    pub fn with_activity_limits(mut self, limits: ActivityLimits) -> Self {
+        self.activity_limits = limits;
+        self
+    }
+
+    /// Sink receiving every authorization decision made by the
+    /// [`crate::server::AuthorizationHandler`] of the endpoints
+    pub fn with_audit_sink(mut self, sink: Arc<dyn AuditSink>) -> Self {
+        self.audit_sink = Some(sink);
+        self
+    }
+}
@@ -0,0 +1,531 @@
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use serde::Deserialize;
+
+use crate::server::{Authorization, AuthorizationHandler, AuthorizationRequest};
+use crate::types::{AddressRange, UnitId};
+
+/// Errors that occur while loading an authorization policy
+#[derive(Debug)]
+pub enum PolicyError {
+    /// The policy file could not be read
+    Io(std::io::Error),
+    /// The policy is not valid JSON or does not follow the expected schema
+    Parse(String),
+    /// The policy is well-formed but one of its rules is invalid
+    InvalidRule {
+        /// Index of the rule in the policy
+        index: usize,
+        /// Description of the problem
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "unable to read policy: {err}"),
+            Self::Parse(err) => write!(f, "unable to parse policy: {err}"),
+            Self::InvalidRule { index, reason } => write!(f, "invalid rule #{index}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+impl From<std::io::Error> for PolicyError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Function codes that can be listed in a [`Rule`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyFunction {
+    /// Read Coils (0x01)
+    ReadCoils,
+    /// Read Discrete Inputs (0x02)
+    ReadDiscreteInputs,
+    /// Read Holding Registers (0x03)
+    ReadHoldingRegisters,
+    /// Read Input Registers (0x04)
+    ReadInputRegisters,
+    /// Write Single Coil (0x05)
+    WriteSingleCoil,
+    /// Write Single Register (0x06)
+    WriteSingleRegister,
+    /// Write Multiple Coils (0x0F)
+    WriteMultipleCoils,
+    /// Write Multiple Registers (0x10)
+    WriteMultipleRegisters,
+}
+
+/// Whether a matching [`Rule`] allows or denies the request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Effect {
+    /// The request is allowed unless a deny rule also matches it
+    #[default]
+    Allow,
+    /// The request is denied, even if an allow rule matches it
+    Deny,
+}
+
+/// Range of addresses listed in a [`Rule`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyRange {
+    /// Starting address of the range
+    pub start: u16,
+    /// Count of addresses in the range
+    pub count: u16,
+}
+
+/// Rule of an authorization policy
+///
+/// Omitting `functions`, `units` or `ranges` matches every function code, unit id or address.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Effect of the rule when it matches a request
+    #[serde(default)]
+    pub effect: Effect,
+    /// Roles to which the rule applies, a request matches if the client has any of them
+    pub roles: Vec<String>,
+    /// Function codes to which the rule applies
+    pub functions: Option<Vec<PolicyFunction>>,
+    /// Unit ids to which the rule applies
+    pub units: Option<Vec<u8>>,
+    /// Addresses to which the rule applies
+    ///
+    /// An allow rule matches a request if one of its ranges contains all the requested addresses,
+    /// while a deny rule matches a request if one of its ranges contains any requested address.
+    pub ranges: Option<Vec<PolicyRange>>,
+}
+
+/// Role-based authorization policy
+///
+/// Requests are denied unless an allow rule matches them and no deny rule does. The policy is
+/// loaded from a JSON document:
+///
+/// ```json
+/// {
+///   "rules": [
+///     { "roles": ["viewer", "operator"], "functions": ["read_holding_registers"] },
+///     { "roles": ["operator"], "functions": ["write_single_register"], "units": [1], "ranges": [{ "start": 0, "count": 100 }] },
+///     { "effect": "deny", "roles": ["operator"], "ranges": [{ "start": 90, "count": 10 }] }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Rules of the policy
+    pub rules: Vec<Rule>,
+}
+
+impl Policy {
+    /// Parse and validate a policy from a JSON document
+    pub fn from_json(json: &str) -> Result<Self, PolicyError> {
+        let policy: Self =
+            serde_json::from_str(json).map_err(|err| PolicyError::Parse(err.to_string()))?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    /// Read, parse and validate a policy from a JSON file
+    pub fn from_file(path: &Path) -> Result<Self, PolicyError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Check that the policy can be evaluated unambiguously
+    pub fn validate(&self) -> Result<(), PolicyError> {
+        for (index, rule) in self.rules.iter().enumerate() {
+            let invalid = |reason: &str| PolicyError::InvalidRule {
+                index,
+                reason: reason.to_string(),
+            };
+
+            if rule.roles.is_empty() {
+                return Err(invalid("no role specified"));
+            }
+            if rule.roles.iter().any(|x| x.is_empty()) {
+                return Err(invalid("empty role"));
+            }
+            if matches!(&rule.functions, Some(x) if x.is_empty()) {
+                return Err(invalid("empty list of functions"));
+            }
+            if matches!(&rule.units, Some(x) if x.is_empty()) {
+                return Err(invalid("empty list of units"));
+            }
+            if let Some(ranges) = &rule.ranges {
+                if ranges.is_empty() {
+                    return Err(invalid("empty list of ranges"));
+                }
+                for range in ranges {
+                    AddressRange::try_from(range.start, range.count)
+                        .map_err(|err| invalid(&format!("invalid range: {err}")))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Evaluate a request against the policy
+    pub fn evaluate(
+        &self,
+        unit_id: UnitId,
+        request: AuthorizationRequest,
+        roles: &[String],
+    ) -> Authorization {
+        let function = function(request);
+        let range = request.range();
+        let matches = |rule: &Rule| -> bool {
+            rule.roles.iter().any(|x| roles.contains(x))
+                && unrestricted_or(&rule.functions, |x| x.contains(&function))
+                && unrestricted_or(&rule.units, |x| x.contains(&unit_id.value))
+                && unrestricted_or(&rule.ranges, |ranges| {
+                    ranges.iter().any(|x| match rule.effect {
+                        Effect::Allow => contains(x, range),
+                        Effect::Deny => overlaps(x, range),
+                    })
+                })
+        };
+
+        let mut allowed = false;
+        for rule in self.rules.iter().filter(|x| matches(x)) {
+            match rule.effect {
+                Effect::Allow => allowed = true,
+                Effect::Deny => return Authorization::Deny,
+            }
+        }
+
+        if allowed {
+            Authorization::Allow
+        } else {
+            Authorization::Deny
+        }
+    }
+}
+
+fn function(request: AuthorizationRequest) -> PolicyFunction {
+    match request {
+        AuthorizationRequest::ReadCoils(_) => PolicyFunction::ReadCoils,
+        AuthorizationRequest::ReadDiscreteInputs(_) => PolicyFunction::ReadDiscreteInputs,
+        AuthorizationRequest::ReadHoldingRegisters(_) => PolicyFunction::ReadHoldingRegisters,
+        AuthorizationRequest::ReadInputRegisters(_) => PolicyFunction::ReadInputRegisters,
+        AuthorizationRequest::WriteSingleCoil(_) => PolicyFunction::WriteSingleCoil,
+        AuthorizationRequest::WriteSingleRegister(_) => PolicyFunction::WriteSingleRegister,
+        AuthorizationRequest::WriteMultipleCoils(_) => PolicyFunction::WriteMultipleCoils,
+        AuthorizationRequest::WriteMultipleRegisters(_) => PolicyFunction::WriteMultipleRegisters,
+    }
+}
+
+// ranges are compared as [start, end) in u32 so that they cannot overflow
+fn bounds(start: u16, count: u16) -> (u32, u32) {
+    (start as u32, start as u32 + count as u32)
+}
+
+fn contains(rule: &PolicyRange, request: AddressRange) -> bool {
+    let (rule_start, rule_end) = bounds(rule.start, rule.count);
+    let (start, end) = bounds(request.start, request.count);
+    rule_start <= start && end <= rule_end
+}
+
+/// A missing filter places no restriction on the rule
+fn unrestricted_or<T>(filter: &Option<T>, check: impl FnOnce(&T) -> bool) -> bool {
+    match filter {
+        None => true,
+        Some(x) => check(x),
+    }
+}
+
+fn overlaps(rule: &PolicyRange, request: AddressRange) -> bool {
+    let (rule_start, rule_end) = bounds(rule.start, rule.count);
+    let (start, end) = bounds(request.start, request.count);
+    rule_start < end && start < rule_end
+}
+
+/// [`AuthorizationHandler`] that evaluates requests against a role-based [`Policy`]
+///
+/// The policy can be replaced while the server is running, subsequent requests are evaluated
+/// against the new policy.
+#[derive(Debug)]
+pub struct PolicyAuthorizationHandler {
+    policy: RwLock<Arc<Policy>>,
+}
+
+impl PolicyAuthorizationHandler {
+    /// Create a handler from a validated policy
+    ///
+    /// Use [`AuthorizationHandler::wrap`] to hand it to a server, or wrap it in an `Arc`
+    /// yourself to keep a reference for [`PolicyAuthorizationHandler::set_policy`].
+    pub fn new(policy: Policy) -> Result<Self, PolicyError> {
+        policy.validate()?;
+        Ok(Self {
+            policy: RwLock::new(Arc::new(policy)),
+        })
+    }
+
+    /// Create a handler from a JSON policy file
+    pub fn from_file(path: &Path) -> Result<Self, PolicyError> {
+        Self::new(Policy::from_file(path)?)
+    }
+
+    /// Replace the policy, the current policy is kept if the new one is invalid
+    pub fn set_policy(&self, policy: Policy) -> Result<(), PolicyError> {
+        policy.validate()?;
+        *self.policy.write().unwrap() = Arc::new(policy);
+        Ok(())
+    }
+
+    /// Reload the policy from a JSON file, the current policy is kept if the file is invalid
+    pub fn reload(&self, path: &Path) -> Result<(), PolicyError> {
+        self.set_policy(Policy::from_file(path)?)
+    }
+
+    /// Current policy
+    pub fn policy(&self) -> Arc<Policy> {
+        self.policy.read().unwrap().clone()
+    }
+
+    fn evaluate(
+        &self,
+        unit_id: UnitId,
+        request: AuthorizationRequest,
+        role: &str,
+    ) -> Authorization {
+        self.policy()
+            .evaluate(unit_id, request, std::slice::from_ref(&role.to_string()))
+    }
+}
+
+impl AuthorizationHandler for PolicyAuthorizationHandler {
+    fn authorize(
+        &self,
+        unit_id: UnitId,
+        request: AuthorizationRequest,
+        roles: &[String],
+    ) -> Authorization {
+        self.policy().evaluate(unit_id, request, roles)
+    }
+
+    fn read_coils(&self, unit_id: UnitId, range: AddressRange, role: &str) -> Authorization {
+        self.evaluate(unit_id, AuthorizationRequest::ReadCoils(range), role)
+    }
+
+    fn read_discrete_inputs(
+        &self,
+        unit_id: UnitId,
+        range: AddressRange,
+        role: &str,
+    ) -> Authorization {
+        self.evaluate(
+            unit_id,
+            AuthorizationRequest::ReadDiscreteInputs(range),
+            role,
+        )
+    }
+
+    fn read_holding_registers(
+        &self,
+        unit_id: UnitId,
+        range: AddressRange,
+        role: &str,
+    ) -> Authorization {
+        self.evaluate(
+            unit_id,
+            AuthorizationRequest::ReadHoldingRegisters(range),
+            role,
+        )
+    }
+
+    fn read_input_registers(
+        &self,
+        unit_id: UnitId,
+        range: AddressRange,
+        role: &str,
+    ) -> Authorization {
+        self.evaluate(
+            unit_id,
+            AuthorizationRequest::ReadInputRegisters(range),
+            role,
+        )
+    }
+
+    fn write_single_coil(&self, unit_id: UnitId, idx: u16, role: &str) -> Authorization {
+        self.evaluate(unit_id, AuthorizationRequest::WriteSingleCoil(idx), role)
+    }
+
+    fn write_single_register(&self, unit_id: UnitId, idx: u16, role: &str) -> Authorization {
+        self.evaluate(
+            unit_id,
+            AuthorizationRequest::WriteSingleRegister(idx),
+            role,
+        )
+    }
+
+    fn write_multiple_coils(
+        &self,
+        unit_id: UnitId,
+        range: AddressRange,
+        role: &str,
+    ) -> Authorization {
+        self.evaluate(
+            unit_id,
+            AuthorizationRequest::WriteMultipleCoils(range),
+            role,
+        )
+    }
+
+    fn write_multiple_registers(
+        &self,
+        unit_id: UnitId,
+        range: AddressRange,
+        role: &str,
+    ) -> Authorization {
+        self.evaluate(
+            unit_id,
+            AuthorizationRequest::WriteMultipleRegisters(range),
+            role,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"{
+        "rules": [
+            { "roles": ["viewer", "operator"], "functions": ["read_holding_registers"] },
+            { "roles": ["operator"], "functions": ["write_single_register", "write_multiple_registers"], "units": [1], "ranges": [{ "start": 0, "count": 100 }] },
+            { "roles": ["operator"], "functions": ["write_multiple_registers"], "units": [1], "ranges": [{ "start": 50, "count": 100 }] },
+            { "effect": "deny", "roles": ["operator"], "ranges": [{ "start": 140, "count": 5 }] }
+        ]
+    }"#;
+
+    fn roles(roles: &[&str]) -> Vec<String> {
+        roles.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn evaluate(request: AuthorizationRequest, unit: u8, client: &[&str]) -> Authorization {
+        Policy::from_json(POLICY)
+            .unwrap()
+            .evaluate(UnitId::new(unit), request, &roles(client))
+    }
+
+    fn range(start: u16, count: u16) -> AddressRange {
+        AddressRange::try_from(start, count).unwrap()
+    }
+
+    #[test]
+    fn denies_by_default() {
+        let request = AuthorizationRequest::ReadCoils(range(0, 1));
+        assert_eq!(evaluate(request, 1, &["operator"]), Authorization::Deny);
+        let request = AuthorizationRequest::ReadHoldingRegisters(range(0, 1));
+        assert_eq!(evaluate(request, 1, &["unknown"]), Authorization::Deny);
+        assert_eq!(evaluate(request, 1, &[]), Authorization::Deny);
+    }
+
+    #[test]
+    fn allows_if_any_role_matches() {
+        let request = AuthorizationRequest::ReadHoldingRegisters(range(0, 10));
+        assert_eq!(evaluate(request, 7, &["viewer"]), Authorization::Allow);
+        let request = AuthorizationRequest::WriteSingleRegister(5);
+        assert_eq!(evaluate(request, 1, &["viewer"]), Authorization::Deny);
+        assert_eq!(
+            evaluate(request, 1, &["viewer", "operator"]),
+            Authorization::Allow
+        );
+    }
+
+    #[test]
+    fn checks_units_and_ranges() {
+        let request = AuthorizationRequest::WriteSingleRegister(5);
+        assert_eq!(evaluate(request, 2, &["operator"]), Authorization::Deny);
+        let request = AuthorizationRequest::WriteSingleRegister(100);
+        assert_eq!(evaluate(request, 1, &["operator"]), Authorization::Deny);
+        // only multiple writes are allowed at this address
+        let request = AuthorizationRequest::WriteSingleRegister(120);
+        assert_eq!(evaluate(request, 1, &["operator"]), Authorization::Deny);
+    }
+
+    #[test]
+    fn overlapping_allow_rules_are_evaluated_independently() {
+        // contained in the first range
+        let request = AuthorizationRequest::WriteMultipleRegisters(range(10, 20));
+        assert_eq!(evaluate(request, 1, &["operator"]), Authorization::Allow);
+        // contained in the second range
+        let request = AuthorizationRequest::WriteMultipleRegisters(range(120, 20));
+        assert_eq!(evaluate(request, 1, &["operator"]), Authorization::Allow);
+        // spans both ranges without being contained in either of them
+        let request = AuthorizationRequest::WriteMultipleRegisters(range(40, 70));
+        assert_eq!(evaluate(request, 1, &["operator"]), Authorization::Deny);
+    }
+
+    #[test]
+    fn deny_rules_take_precedence_over_overlapping_allow_rules() {
+        // a single address of the request in the denied range is enough
+        let request = AuthorizationRequest::WriteMultipleRegisters(range(100, 41));
+        assert_eq!(evaluate(request, 1, &["operator"]), Authorization::Deny);
+        let request = AuthorizationRequest::WriteMultipleRegisters(range(100, 40));
+        assert_eq!(evaluate(request, 1, &["operator"]), Authorization::Allow);
+        // the deny rule applies to every function of the role
+        let request = AuthorizationRequest::ReadHoldingRegisters(range(144, 1));
+        assert_eq!(evaluate(request, 1, &["operator"]), Authorization::Deny);
+        assert_eq!(evaluate(request, 1, &["viewer"]), Authorization::Allow);
+    }
+
+    #[test]
+    fn rejects_invalid_policies() {
+        assert!(matches!(
+            Policy::from_json(
+                r#"{ "rules": [{ "roles": ["x"], "functions": ["read_everything"] }] }"#
+            ),
+            Err(PolicyError::Parse(_))
+        ));
+        assert!(matches!(
+            Policy::from_json(r#"{ "rules": [{ "roles": ["x"], "unknown": 1 }] }"#),
+            Err(PolicyError::Parse(_))
+        ));
+        assert!(matches!(
+            Policy::from_json(r#"{ "rules": [{ "roles": ["x"] }, { "roles": [] }] }"#),
+            Err(PolicyError::InvalidRule { index: 1, .. })
+        ));
+        assert!(matches!(
+            Policy::from_json(
+                r#"{ "rules": [{ "roles": ["x"], "ranges": [{ "start": 65535, "count": 2 }] }] }"#
+            ),
+            Err(PolicyError::InvalidRule { index: 0, .. })
+        ));
+        assert!(matches!(
+            Policy::from_json(r#"{ "rules": [{ "roles": ["x"], "units": [] }] }"#),
+            Err(PolicyError::InvalidRule { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn keeps_current_policy_if_the_new_one_is_invalid() {
+        let handler = PolicyAuthorizationHandler::new(Policy::from_json(POLICY).unwrap()).unwrap();
+        let invalid = Policy {
+            rules: vec![Rule {
+                effect: Effect::Allow,
+                roles: Vec::new(),
+                functions: None,
+                units: None,
+                ranges: None,
+            }],
+        };
+        assert!(handler.set_policy(invalid).is_err());
+        assert_eq!(handler.policy().rules.len(), 4);
+
+        handler.set_policy(Policy { rules: Vec::new() }).unwrap();
+        assert_eq!(
+            handler.read_holding_registers(UnitId::new(1), range(0, 1), "viewer"),
+            Authorization::Deny
+        );
+    }
+}
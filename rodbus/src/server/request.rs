@@ -34,20 +34,14 @@ pub(crate) enum BroadcastRequest<'a> {
 }
 
 impl<'a> BroadcastRequest<'a> {
-    // execute a broadcast request against the handler
-    pub(crate) fn execute<T: RequestHandler>(&self, handler: &mut T) {
+    // execute a broadcast request against the handler, returns true if the handler accepted it
+    pub(crate) fn execute<T: RequestHandler>(&self, handler: &mut T) -> bool {
         match self {
-            BroadcastRequest::WriteSingleCoil(x) => {
-                let _ = handler.write_single_coil(*x);
-            }
-            BroadcastRequest::WriteSingleRegister(x) => {
-                let _ = handler.write_single_register(*x);
-            }
-            BroadcastRequest::WriteMultipleCoils(x) => {
-                let _ = handler.write_multiple_coils(*x);
-            }
+            BroadcastRequest::WriteSingleCoil(x) => handler.write_single_coil(*x).is_ok(),
+            BroadcastRequest::WriteSingleRegister(x) => handler.write_single_register(*x).is_ok(),
+            BroadcastRequest::WriteMultipleCoils(x) => handler.write_multiple_coils(*x).is_ok(),
             BroadcastRequest::WriteMultipleRegisters(x) => {
-                let _ = handler.write_multiple_registers(*x);
+                handler.write_multiple_registers(*x).is_ok()
             }
         }
     }
@@ -67,6 +61,32 @@ impl<'a> Request<'a> {
         }
     }
 
+    /// Range and values of a write request
+    pub(crate) fn write_values(&self) -> Option<(AddressRange, WriteValues)> {
+        match self {
+            Request::ReadCoils(_)
+            | Request::ReadDiscreteInputs(_)
+            | Request::ReadHoldingRegisters(_)
+            | Request::ReadInputRegisters(_) => None,
+            Request::WriteSingleCoil(x) => Some((
+                AddressRange::try_from(x.index, 1).ok()?,
+                WriteValues::Coils(vec![x.value]),
+            )),
+            Request::WriteSingleRegister(x) => Some((
+                AddressRange::try_from(x.index, 1).ok()?,
+                WriteValues::Registers(vec![x.value]),
+            )),
+            Request::WriteMultipleCoils(x) => Some((
+                x.range,
+                WriteValues::Coils(x.iterator.map(|x| x.value).collect()),
+            )),
+            Request::WriteMultipleRegisters(x) => Some((
+                x.range,
+                WriteValues::Registers(x.iterator.map(|x| x.value).collect()),
+            )),
+        }
+    }
+
     pub(crate) fn into_broadcast_request(self) -> Option<BroadcastRequest<'a>> {
         match self {
             Request::ReadCoils(_) => None,
@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::exception::ExceptionCode;
+use crate::server::{
+    AuditSink, Authorization, AuthorizationRecord, AuthorizationRequest, WriteEvent,
+    WriteValidator, WRITE_EVENT_CAPACITY,
+};
+use crate::types::UnitId;
+
+/// Counters for the requests processed by a session or by the whole server
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SessionCounters {
+    /// Number of requests received by function code, including unknown function codes
+    pub requests: BTreeMap<u8, u64>,
+    /// Number of exception responses returned by exception code
+    pub exceptions: BTreeMap<ExceptionCode, u64>,
+    /// Number of requests rejected by the authorization handler
+    pub authorization_denials: u64,
+    /// Number of frames that could not be parsed
+    pub bad_frames: u64,
+    /// Number of requests that exceeded the rate limit of the session
+    pub rate_limited: u64,
+    /// Number of sessions closed because they did not receive a frame within the idle timeout
+    pub idle_timeouts: u64,
+}
+
+/// Information about an active session of a TCP or TLS server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    /// Identifier of the session, unique for the lifetime of the server
+    pub id: u128,
+    /// Address of the remote client
+    pub peer: SocketAddr,
+    /// Roles extracted from the client certificate, or the IP address of the client if a TCP server
+    /// authorizes requests. Empty if the server does not authorize requests.
+    pub roles: Vec<String>,
+    /// Parameters of the TLS session, `None` until the handshake completes or if a TCP server
+    #[cfg(feature = "tls")]
+    pub tls: Option<crate::tcp::tls::TlsSessionInfo>,
+    /// Time at which the connection was accepted
+    pub connected_at: SystemTime,
+    /// Time at which the last request was received, or the connection was accepted if no request was received
+    pub last_activity: SystemTime,
+    /// Counters for the requests processed by the session
+    pub counters: SessionCounters,
+}
+
+/// Event reported to the session listener of a TCP or TLS server
+#[derive(Debug, Clone, PartialEq, Eq)]
+// not Copy so that the type is the same with or without the TLS variants
+#[cfg_attr(not(feature = "tls"), allow(missing_copy_implementations))]
+pub enum SessionEvent {
+    /// A connection was accepted and a session was created for it
+    Opened {
+        /// Identifier of the session
+        id: u128,
+        /// Address of the remote client
+        peer: SocketAddr,
+    },
+    /// The TLS handshake of a session completed
+    #[cfg(feature = "tls")]
+    TlsEstablished {
+        /// Identifier of the session
+        id: u128,
+        /// Address of the remote client
+        peer: SocketAddr,
+        /// Parameters negotiated during the handshake
+        session: crate::tcp::tls::TlsSessionInfo,
+    },
+    /// The local certificate or the certificate presented by the client of a session expires
+    /// within the warning period of the TLS configuration
+    #[cfg(feature = "tls")]
+    CertificateExpiring {
+        /// Identifier of the session
+        id: u128,
+        /// Address of the remote client
+        peer: SocketAddr,
+        /// Certificate that is about to expire
+        warning: crate::tcp::tls::ExpiryWarning,
+    },
+    /// A session was closed
+    Closed {
+        /// Identifier of the session
+        id: u128,
+        /// Address of the remote client
+        peer: SocketAddr,
+    },
+}
+
+/// Statistics of a server
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ServerStatistics {
+    /// Number of connections closed immediately because of the address filter or the session limits
+    pub sessions_rejected: u64,
+    /// Counters for all sessions, including the ones that are closed
+    pub total: SessionCounters,
+}
+
+#[derive(Default)]
+struct ServerState {
+    sessions_rejected: u64,
+    total: SessionCounters,
+    sessions: BTreeMap<u128, SessionInfo>,
+    // monotonic time of the last activity of each session, used to order evictions
+    activity: BTreeMap<u128, tokio::time::Instant>,
+    write_validator: Option<Arc<dyn WriteValidator>>,
+    audit_sink: Option<Arc<dyn AuditSink>>,
+}
+
+/// Statistics, session information and write hooks shared between the server handle and its tasks
+#[derive(Clone)]
+pub(crate) struct SharedServerState {
+    inner: Arc<Mutex<ServerState>>,
+    writes: tokio::sync::broadcast::Sender<WriteEvent>,
+}
+
+impl Default for SharedServerState {
+    fn default() -> Self {
+        Self {
+            inner: Default::default(),
+            writes: tokio::sync::broadcast::channel(WRITE_EVENT_CAPACITY).0,
+        }
+    }
+}
+
+impl std::fmt::Debug for SharedServerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("SharedServerState")
+    }
+}
+
+impl SharedServerState {
+    pub(crate) fn sessions(&self) -> Vec<SessionInfo> {
+        self.inner
+            .lock()
+            .unwrap()
+            .sessions
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn statistics(&self, reset: bool) -> ServerStatistics {
+        let mut guard = self.inner.lock().unwrap();
+        let stats = ServerStatistics {
+            sessions_rejected: guard.sessions_rejected,
+            total: guard.total.clone(),
+        };
+        if reset {
+            guard.sessions_rejected = 0;
+            guard.total = SessionCounters::default();
+            for session in guard.sessions.values_mut() {
+                session.counters = SessionCounters::default();
+            }
+        }
+        stats
+    }
+
+    pub(crate) fn add_session(&self, id: u128, peer: SocketAddr) {
+        let now = SystemTime::now();
+        let mut guard = self.inner.lock().unwrap();
+        guard.activity.insert(id, tokio::time::Instant::now());
+        guard.sessions.insert(
+            id,
+            SessionInfo {
+                id,
+                peer,
+                roles: Vec::new(),
+                #[cfg(feature = "tls")]
+                tls: None,
+                connected_at: now,
+                last_activity: now,
+                counters: SessionCounters::default(),
+            },
+        );
+    }
+
+    pub(crate) fn last_activity(&self, id: u128) -> Option<tokio::time::Instant> {
+        self.inner.lock().unwrap().activity.get(&id).copied()
+    }
+
+    pub(crate) fn on_session_rejected(&self) {
+        self.inner.lock().unwrap().sessions_rejected += 1;
+    }
+
+    pub(crate) fn remove_session(&self, id: u128) {
+        let mut guard = self.inner.lock().unwrap();
+        guard.sessions.remove(&id);
+        guard.activity.remove(&id);
+    }
+
+    pub(crate) fn subscribe_writes(&self) -> tokio::sync::broadcast::Receiver<WriteEvent> {
+        self.writes.subscribe()
+    }
+
+    pub(crate) fn set_write_validator(&self, validator: Option<Arc<dyn WriteValidator>>) {
+        self.inner.lock().unwrap().write_validator = validator;
+    }
+
+    pub(crate) fn set_audit_sink(&self, sink: Option<Arc<dyn AuditSink>>) {
+        self.inner.lock().unwrap().audit_sink = sink;
+    }
+
+    pub(crate) fn session(&self, id: Option<u128>) -> SessionStatistics {
+        SessionStatistics {
+            state: self.clone(),
+            id,
+        }
+    }
+}
+
+/// Updates the counters of a single session and the aggregate counters of the server
+pub(crate) struct SessionStatistics {
+    state: SharedServerState,
+    // RTU servers have no sessions
+    id: Option<u128>,
+}
+
+impl SessionStatistics {
+    pub(crate) fn id(&self) -> Option<u128> {
+        self.id
+    }
+
+    /// True if a validator or a subscriber needs the values of each write
+    pub(crate) fn observes_writes(&self) -> bool {
+        self.state.writes.receiver_count() > 0
+            || self.state.inner.lock().unwrap().write_validator.is_some()
+    }
+
+    pub(crate) fn validate_write(&self, write: &WriteEvent) -> Result<(), ExceptionCode> {
+        // release the lock before calling user code
+        let validator = self.state.inner.lock().unwrap().write_validator.clone();
+        match validator {
+            Some(validator) => validator.validate(write),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn audit(
+        &self,
+        unit: UnitId,
+        request: AuthorizationRequest,
+        roles: &[String],
+        outcome: Authorization,
+    ) {
+        // release the lock before calling user code
+        let (sink, peer) = {
+            let guard = self.state.inner.lock().unwrap();
+            let peer = self
+                .id
+                .and_then(|id| guard.sessions.get(&id))
+                .map(|x| x.peer);
+            (guard.audit_sink.clone(), peer)
+        };
+
+        if let Some(sink) = sink {
+            sink.record(&AuthorizationRecord {
+                timestamp: SystemTime::now(),
+                session: self.id,
+                peer,
+                roles: roles.to_vec(),
+                unit,
+                request,
+                outcome,
+            });
+        }
+    }
+
+    pub(crate) fn on_write(&self, write: WriteEvent) {
+        // fails if there are no subscribers
+        let _ = self.state.writes.send(write);
+    }
+
+    pub(crate) fn set_roles(&self, roles: &[String]) {
+        if let Some(id) = self.id {
+            if let Some(session) = self.state.inner.lock().unwrap().sessions.get_mut(&id) {
+                session.roles = roles.to_vec();
+            }
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    pub(crate) fn set_tls_session(&self, info: crate::tcp::tls::TlsSessionInfo) {
+        if let Some(id) = self.id {
+            if let Some(session) = self.state.inner.lock().unwrap().sessions.get_mut(&id) {
+                session.tls = Some(info);
+            }
+        }
+    }
+
+    pub(crate) fn on_request(&self, function: u8) {
+        self.update(|x| *x.requests.entry(function).or_default() += 1);
+        if let Some(id) = self.id {
+            let mut guard = self.state.inner.lock().unwrap();
+            if let Some(session) = guard.sessions.get_mut(&id) {
+                session.last_activity = SystemTime::now();
+                guard.activity.insert(id, tokio::time::Instant::now());
+            }
+        }
+    }
+
+    pub(crate) fn on_exception(&self, ex: ExceptionCode) {
+        self.update(|x| *x.exceptions.entry(ex).or_default() += 1);
+    }
+
+    pub(crate) fn on_authorization_denied(&self) {
+        self.update(|x| x.authorization_denials += 1);
+    }
+
+    pub(crate) fn on_bad_frame(&self) {
+        self.update(|x| x.bad_frames += 1);
+    }
+
+    pub(crate) fn on_rate_limited(&self) {
+        self.update(|x| x.rate_limited += 1);
+    }
+
+    pub(crate) fn on_idle_timeout(&self) {
+        self.update(|x| x.idle_timeouts += 1);
+    }
+
+    fn update<F>(&self, action: F)
+    where
+        F: Fn(&mut SessionCounters),
+    {
+        let mut guard = self.state.inner.lock().unwrap();
+        action(&mut guard.total);
+        if let Some(session) = self.id.and_then(|id| guard.sessions.get_mut(&id)) {
+            action(&mut session.counters);
+        }
+    }
+}
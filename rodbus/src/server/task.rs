@@ -1,5 +1,10 @@
+use crate::client::Listener;
 use crate::common::phys::PhysLayer;
-use crate::server::{Authorization, AuthorizationHandler};
+use crate::server::limits::TokenBucket;
+use crate::server::{
+    ActivityLimits, AddressFilter, Authorization, AuthorizationHandler, AuthorizationRequest,
+    RateLimitAction, RequestContext, SessionEvent, SessionLimits, ShutdownMode, WriteEvent,
+};
 use crate::{DecodeLevel, UnitId};
 
 use crate::common::frame::{
@@ -8,42 +13,117 @@ use crate::common::frame::{
 use crate::common::function::FunctionCode;
 use crate::error::*;
 use crate::exception::ExceptionCode;
-use crate::server::handler::{RequestHandler, ServerHandlerMap};
+use crate::server::handler::{
+    RequestHandler, ServerHandlerType, SharedHandlerMap, UnmappedUnitResponse,
+};
 use crate::server::request::{Request, RequestDisplay};
+use crate::server::statistics::SessionStatistics;
 
 use scursor::ReadCursor;
 use std::sync::Arc;
 
 /// Messages that can be sent to change server settings dynamically
 #[derive(Copy, Clone)]
-pub enum ServerSetting {
+pub(crate) enum ServerSetting {
     ChangeDecoding(DecodeLevel),
+    ChangeActivityLimits(ActivityLimits),
+}
+
+/// Completed once a command has been applied by the server
+pub(crate) type Ack = tokio::sync::oneshot::Sender<()>;
+
+/// Messages sent from the [`crate::server::ServerHandle`] to the server task
+pub(crate) enum ServerCommand {
+    /// Setting that is applied to the server and all of its sessions
+    Setting(ServerSetting),
+    /// Close a particular session
+    DisconnectSession(u128),
+    /// Close all active sessions
+    DisconnectAll,
+    /// Stop accepting new connections, active sessions are unaffected
+    StopListening,
+    /// Replace the listener notified when sessions open and close
+    SetSessionListener(Box<dyn Listener<SessionEvent>>),
+    /// Change the limits applied when accepting new connections
+    SetSessionLimits(SessionLimits),
+    /// Replace the filter applied to new connections and close the sessions it no longer allows
+    SetAddressFilter(AddressFilter),
+    /// Add or replace the handler of a unit id, the handler is a boxed `ServerHandlerType<T>`
+    AddHandler(UnitId, Box<dyn std::any::Any + Send>, Option<Ack>),
+    /// Remove the handler of a unit id
+    RemoveHandler(UnitId, Option<Ack>),
+    /// Replace the TLS configuration of the TLS endpoints
+    #[cfg(feature = "tls")]
+    SetTlsConfig(
+        crate::server::TlsServerConfig,
+        crate::server::TlsReloadPolicy,
+    ),
+    /// Stop the server and all of its sessions
+    Shutdown(ShutdownMode),
 }
 
 pub(crate) struct SessionTask<T>
 where
     T: RequestHandler,
 {
-    handlers: ServerHandlerMap<T>,
+    handlers: SharedHandlerMap<T>,
     auth: AuthorizationType,
-    commands: tokio::sync::mpsc::Receiver<ServerSetting>,
+    commands: tokio::sync::mpsc::Receiver<ServerCommand>,
     writer: FrameWriter,
     reader: FramedReader,
     decode: DecodeLevel,
+    stats: SessionStatistics,
+    // None if the server does not support activity limits (RTU)
+    activity: Option<ActivityLimits>,
+    bucket: Option<TokenBucket>,
+    last_frame: tokio::time::Instant,
+    client: ClientInfo,
+}
+
+/// What the session knows about its client, passed to the handlers with each request
+#[derive(Default)]
+struct ClientInfo {
+    peer: Option<std::net::SocketAddr>,
+    #[cfg(feature = "tls")]
+    tls: Option<crate::tcp::tls::TlsSessionInfo>,
+}
+
+impl ClientInfo {
+    fn context<'a>(
+        &'a self,
+        session: Option<u128>,
+        auth: &'a AuthorizationType,
+    ) -> RequestContext<'a> {
+        RequestContext {
+            session,
+            peer: self.peer,
+            roles: auth.roles(),
+            #[cfg(feature = "tls")]
+            tls: self.tls.as_ref(),
+        }
+    }
 }
 
 impl<T> SessionTask<T>
 where
     T: RequestHandler,
 {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
-        handlers: ServerHandlerMap<T>,
+        handlers: SharedHandlerMap<T>,
         auth: AuthorizationType,
         writer: FrameWriter,
         reader: FramedReader,
-        commands: tokio::sync::mpsc::Receiver<ServerSetting>,
+        commands: tokio::sync::mpsc::Receiver<ServerCommand>,
         decode: DecodeLevel,
+        stats: SessionStatistics,
+        activity: Option<ActivityLimits>,
     ) -> Self {
+        if let AuthorizationType::Handler(_, roles) = &auth {
+            stats.set_roles(roles);
+        }
+
+        let now = tokio::time::Instant::now();
         Self {
             handlers,
             auth,
@@ -51,9 +131,32 @@ where
             writer,
             reader,
             decode,
+            stats,
+            activity,
+            bucket: activity
+                .and_then(|x| x.rate_limit)
+                .map(|x| TokenBucket::new(x, now)),
+            last_frame: now,
+            client: ClientInfo::default(),
         }
     }
 
+    /// Set the address of the client passed to the handlers
+    pub(crate) fn with_peer(mut self, peer: std::net::SocketAddr) -> Self {
+        self.client.peer = Some(peer);
+        self
+    }
+
+    /// Set the parameters of the TLS session passed to the handlers
+    #[cfg(feature = "tls")]
+    pub(crate) fn with_tls_session(
+        mut self,
+        info: Option<crate::tcp::tls::TlsSessionInfo>,
+    ) -> Self {
+        self.client.tls = info;
+        self
+    }
+
     async fn reply_with_error(
         &mut self,
         io: &mut PhysLayer,
@@ -76,6 +179,7 @@ where
         if header.destination != FrameDestination::Broadcast {
             let bytes = self.writer.format_ex(header, func, ex, self.decode)?;
             io.write(bytes, self.decode.physical).await?;
+            self.stats.on_exception(ex);
         }
         Ok(())
     }
@@ -106,25 +210,42 @@ where
     async fn process_settings(&mut self) -> Shutdown {
         loop {
             match self.commands.recv().await {
-                None => return Shutdown,
-                Some(setting) => {
-                    self.apply_setting(setting);
+                None | Some(ServerCommand::Shutdown(_)) => return Shutdown,
+                Some(cmd) => {
+                    self.apply_command(cmd);
                 }
             }
         }
     }
 
     async fn run_one(&mut self, io: &mut PhysLayer) -> Result<(), RequestError> {
+        let idle_deadline = self
+            .activity
+            .and_then(|x| x.idle_timeout)
+            .map(|x| self.last_frame + x);
+
         tokio::select! {
             frame = self.reader.next_frame(io, self.decode) => {
+                if let Err(RequestError::BadFrame(_)) = frame {
+                    self.stats.on_bad_frame();
+                }
                 let frame = frame?;
-                self.handle_frame(io, frame).await
+                self.last_frame = tokio::time::Instant::now();
+                // apply commands sent before the frame was received, e.g. setting changes
+                let shutdown = self.apply_pending_commands();
+                self.handle_frame(io, frame).await?;
+                shutdown
+            }
+            _ = sleep_until(idle_deadline) => {
+                tracing::warn!("closing idle session");
+                self.stats.on_idle_timeout();
+                Err(RequestError::Io(std::io::ErrorKind::TimedOut))
             }
             cmd = self.commands.recv() => {
                match cmd {
-                    None => Err(crate::error::RequestError::Shutdown),
-                    Some(setting) => {
-                        self.apply_setting(setting);
+                    None | Some(ServerCommand::Shutdown(_)) => Err(RequestError::Shutdown),
+                    Some(cmd) => {
+                        self.apply_command(cmd);
                         Ok(())
                     }
                }
@@ -132,36 +253,119 @@ where
         }
     }
 
-    fn apply_setting(&mut self, setting: ServerSetting) {
-        match setting {
-            ServerSetting::ChangeDecoding(level) => {
+    fn apply_pending_commands(&mut self) -> Result<(), RequestError> {
+        loop {
+            match self.commands.try_recv() {
+                Ok(ServerCommand::Shutdown(_)) => return Err(RequestError::Shutdown),
+                Ok(cmd) => self.apply_command(cmd),
+                // a closed channel is detected when waiting for the next frame
+                Err(_) => return Ok(()),
+            }
+        }
+    }
+
+    pub(crate) fn apply_command(&mut self, cmd: ServerCommand) {
+        match cmd {
+            ServerCommand::Setting(ServerSetting::ChangeDecoding(level)) => {
                 self.decode = level;
             }
+            ServerCommand::Setting(ServerSetting::ChangeActivityLimits(limits)) => {
+                if self.activity.is_some() {
+                    self.activity = Some(limits);
+                    self.bucket = limits
+                        .rate_limit
+                        .map(|x| TokenBucket::new(x, tokio::time::Instant::now()));
+                } else {
+                    tracing::warn!("activity limits are not supported by this server");
+                }
+            }
+            ServerCommand::AddHandler(id, handler, ack) => {
+                match handler.downcast::<ServerHandlerType<T>>() {
+                    Ok(handler) => {
+                        self.handlers.add(id, *handler);
+                    }
+                    Err(_) => tracing::error!("ignoring handler of the wrong type for: {}", id),
+                }
+                if let Some(ack) = ack {
+                    let _ = ack.send(());
+                }
+            }
+            ServerCommand::RemoveHandler(id, ack) => {
+                self.handlers.remove(id);
+                if let Some(ack) = ack {
+                    let _ = ack.send(());
+                }
+            }
+            // only received by RTU servers which do not have sessions or a listening socket
+            ServerCommand::DisconnectSession(_)
+            | ServerCommand::DisconnectAll
+            | ServerCommand::StopListening
+            | ServerCommand::SetSessionListener(_)
+            | ServerCommand::SetSessionLimits(_)
+            | ServerCommand::SetAddressFilter(_) => {
+                tracing::warn!("session management is not supported by this server");
+            }
+            #[cfg(feature = "tls")]
+            ServerCommand::SetTlsConfig(_, _) => {
+                tracing::warn!("TLS is not supported by this server");
+            }
+            // handled before commands are applied
+            ServerCommand::Shutdown(_) => {}
         }
     }
 
     async fn handle_frame(&mut self, io: &mut PhysLayer, frame: Frame) -> Result<(), RequestError> {
         let mut cursor = ReadCursor::new(frame.payload());
 
-        let function = match cursor.read_u8() {
+        let value = match cursor.read_u8() {
             Err(_) => {
                 tracing::warn!("received an empty frame");
                 return Ok(());
             }
-            Ok(value) => match FunctionCode::get(value) {
-                Some(x) => x,
-                None => {
-                    tracing::warn!("received unknown function code: {}", value);
-                    return self
-                        .reply_with_error_generic(
+            Ok(value) => value,
+        };
+
+        self.stats.on_request(value);
+
+        if let Some(bucket) = &mut self.bucket {
+            if !bucket.try_acquire(tokio::time::Instant::now()) {
+                self.stats.on_rate_limited();
+                return match bucket.action() {
+                    RateLimitAction::ReplyBusy => {
+                        tracing::warn!("request rate limit exceeded, replying busy");
+                        let function = match FunctionCode::get(value) {
+                            Some(x) => FunctionField::Exception(x),
+                            None => FunctionField::unknown(value),
+                        };
+                        self.reply_with_error_generic(
                             io,
                             frame.header,
-                            FunctionField::unknown(value),
-                            ExceptionCode::IllegalFunction,
+                            function,
+                            ExceptionCode::ServerDeviceBusy,
                         )
-                        .await;
-                }
-            },
+                        .await
+                    }
+                    RateLimitAction::Disconnect => {
+                        tracing::warn!("request rate limit exceeded, closing session");
+                        Err(RequestError::Io(std::io::ErrorKind::ConnectionAborted))
+                    }
+                };
+            }
+        }
+
+        let function = match FunctionCode::get(value) {
+            Some(x) => x,
+            None => {
+                tracing::warn!("received unknown function code: {}", value);
+                return self
+                    .reply_with_error_generic(
+                        io,
+                        frame.header,
+                        FunctionField::unknown(value),
+                        ExceptionCode::IllegalFunction,
+                    )
+                    .await;
+            }
         };
 
         let request = match Request::parse(function, &mut cursor) {
@@ -182,10 +386,12 @@ where
         }
 
         // check authorization
-        if let Authorization::Deny = self
-            .auth
-            .is_authorized(frame.header.destination.into_unit_id(), &request)
-        {
+        if let Authorization::Deny = self.auth.is_authorized(
+            frame.header.destination.into_unit_id(),
+            &request,
+            &self.stats,
+        ) {
+            self.stats.on_authorization_denied();
             if !frame.header.destination.is_broadcast() {
                 self.reply_with_error(
                     io,
@@ -198,32 +404,86 @@ where
             return Ok(());
         }
 
+        // only copy the written values if someone will look at them
+        let write = if self.stats.observes_writes() {
+            request.write_values().map(|(range, values)| WriteEvent {
+                session: self.stats.id(),
+                unit: frame.header.destination.into_unit_id(),
+                range,
+                values,
+            })
+        } else {
+            None
+        };
+
         // if no addresses match, then don't respond
         match frame.header.destination {
             FrameDestination::UnitId(unit_id) => {
-                let handler = match self.handlers.get(unit_id) {
-                    None => {
+                let handler = match self.handlers.resolve(unit_id) {
+                    Err(unmapped) => {
                         tracing::warn!("received frame for unmapped unit id: {}", unit_id);
-                        return Ok(());
+                        let ex = match unmapped {
+                            UnmappedUnitResponse::NoResponse => return Ok(()),
+                            UnmappedUnitResponse::GatewayTargetDeviceFailedToRespond => {
+                                ExceptionCode::GatewayTargetDeviceFailedToRespond
+                            }
+                            UnmappedUnitResponse::IllegalFunction => ExceptionCode::IllegalFunction,
+                        };
+                        return self
+                            .reply_with_error(io, frame.header, request.get_function(), ex)
+                            .await;
                     }
-                    Some(handler) => handler,
+                    Ok(handler) => handler,
                 };
+                if let Some(write) = &write {
+                    if let Err(ex) = self.stats.validate_write(write) {
+                        tracing::warn!("write rejected by validator: {}", ex);
+                        return self
+                            .reply_with_error(io, frame.header, request.get_function(), ex)
+                            .await;
+                    }
+                }
                 // get the reply data (or exception reply)
-                let reply: &[u8] = request.get_reply(
-                    frame.header,
-                    handler.lock().unwrap().as_mut(),
-                    &mut self.writer,
-                    self.decode,
-                )?;
+                let reply: &[u8] = {
+                    let mut handler = handler.lock().unwrap();
+                    handler.set_context(self.client.context(self.stats.id(), &self.auth));
+                    request.get_reply(
+                        frame.header,
+                        handler.as_mut(),
+                        &mut self.writer,
+                        self.decode,
+                    )?
+                };
                 io.write(reply, self.decode.physical).await?;
+                match self.writer.last_exception() {
+                    Some(ex) => self.stats.on_exception(ex),
+                    None => {
+                        if let Some(write) = write {
+                            self.stats.on_write(write);
+                        }
+                    }
+                }
             }
             FrameDestination::Broadcast => match request.into_broadcast_request() {
                 None => {
                     tracing::warn!("broadcast is not supported for {}", function);
                 }
                 Some(request) => {
-                    for handler in self.handlers.iter_mut() {
-                        request.execute(handler.lock().unwrap().as_mut());
+                    if let Some(write) = &write {
+                        if let Err(ex) = self.stats.validate_write(write) {
+                            tracing::warn!("broadcast write rejected by validator: {}", ex);
+                            return Ok(());
+                        }
+                    }
+                    let mut accepted = false;
+                    let context = self.client.context(self.stats.id(), &self.auth);
+                    for handler in self.handlers.broadcast_handlers() {
+                        let mut handler = handler.lock().unwrap();
+                        handler.set_context(context);
+                        accepted |= request.execute(handler.as_mut());
+                    }
+                    if let (true, Some(write)) = (accepted, write) {
+                        self.stats.on_write(write);
                     }
                 }
             },
@@ -233,49 +493,61 @@ where
     }
 }
 
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(x) => tokio::time::sleep_until(x).await,
+        None => std::future::pending().await,
+    }
+}
+
 /// Determines how authorization of user defined requests are handled
 pub(crate) enum AuthorizationType {
-    /// Requests do not require authorization checks (TCP / RTU)
+    /// Requests do not require authorization checks
     None,
-    /// Requests are authorized using a user-supplied handler
-    #[allow(dead_code)] // when tls feature is disabled
-    Handler(Arc<dyn AuthorizationHandler>, String),
+    /// Requests are authorized using a user-supplied handler and the roles of the client,
+    /// i.e. the roles of its certificate, its IP address (TCP) or the serial port (RTU)
+    Handler(Arc<dyn AuthorizationHandler>, Vec<String>),
 }
 
 impl AuthorizationType {
-    fn check_authorization(
-        handler: &dyn AuthorizationHandler,
-        unit_id: UnitId,
-        request: &Request,
-        role: &str,
-    ) -> Authorization {
+    fn roles(&self) -> &[String] {
+        match self {
+            Self::None => &[],
+            Self::Handler(_, roles) => roles,
+        }
+    }
+
+    fn authorization_request(request: &Request) -> AuthorizationRequest {
         match request {
-            Request::ReadCoils(x) => handler.read_coils(unit_id, x.inner, role),
-            Request::ReadDiscreteInputs(x) => handler.read_discrete_inputs(unit_id, x.inner, role),
-            Request::ReadHoldingRegisters(x) => {
-                handler.read_holding_registers(unit_id, x.inner, role)
-            }
-            Request::ReadInputRegisters(x) => handler.read_input_registers(unit_id, x.inner, role),
-            Request::WriteSingleCoil(x) => handler.write_single_coil(unit_id, x.index, role),
-            Request::WriteSingleRegister(x) => {
-                handler.write_single_register(unit_id, x.index, role)
-            }
-            Request::WriteMultipleCoils(x) => handler.write_multiple_coils(unit_id, x.range, role),
+            Request::ReadCoils(x) => AuthorizationRequest::ReadCoils(x.inner),
+            Request::ReadDiscreteInputs(x) => AuthorizationRequest::ReadDiscreteInputs(x.inner),
+            Request::ReadHoldingRegisters(x) => AuthorizationRequest::ReadHoldingRegisters(x.inner),
+            Request::ReadInputRegisters(x) => AuthorizationRequest::ReadInputRegisters(x.inner),
+            Request::WriteSingleCoil(x) => AuthorizationRequest::WriteSingleCoil(x.index),
+            Request::WriteSingleRegister(x) => AuthorizationRequest::WriteSingleRegister(x.index),
+            Request::WriteMultipleCoils(x) => AuthorizationRequest::WriteMultipleCoils(x.range),
             Request::WriteMultipleRegisters(x) => {
-                handler.write_multiple_registers(unit_id, x.range, role)
+                AuthorizationRequest::WriteMultipleRegisters(x.range)
             }
         }
     }
 
-    pub(crate) fn is_authorized(&self, unit_id: UnitId, request: &Request) -> Authorization {
+    pub(crate) fn is_authorized(
+        &self,
+        unit_id: UnitId,
+        request: &Request,
+        stats: &SessionStatistics,
+    ) -> Authorization {
         match self {
             AuthorizationType::None => Authorization::Allow,
-            AuthorizationType::Handler(handler, role) => {
-                let result = Self::check_authorization(handler.as_ref(), unit_id, request, role);
+            AuthorizationType::Handler(handler, roles) => {
+                let request_info = Self::authorization_request(request);
+                let result = handler.authorize(unit_id, request_info, roles);
+                stats.audit(unit_id, request_info, roles, result);
                 if let Authorization::Deny = result {
                     tracing::warn!(
-                        "Role \"{}\" not authorized for request: {:?}",
-                        role,
+                        "Roles {:?} not authorized for request: {:?}",
+                        roles,
                         request.get_function()
                     );
                 }
@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use crate::exception::ExceptionCode;
+use crate::types::{AddressRange, UnitId};
+
+/// Values written by a client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteValues {
+    /// Values written to coils
+    Coils(Vec<bool>),
+    /// Values written to holding registers
+    Registers(Vec<u16>),
+}
+
+/// Write request received by a server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteEvent {
+    /// Identifier of the session that received the request, `None` for RTU servers
+    pub session: Option<u128>,
+    /// Unit id the request was addressed to, [`UnitId::broadcast`] for broadcast requests
+    pub unit: UnitId,
+    /// Range of addresses written
+    pub range: AddressRange,
+    /// Values written, one per address of the range
+    pub values: WriteValues,
+}
+
+/// Hook invoked before a write request is passed to the [`crate::server::RequestHandler`]
+///
+/// The hook is invoked without holding the lock of the handler.
+pub trait WriteValidator: Send + Sync + 'static {
+    /// Moves a validator implementation into an `Arc` suitable for passing to the server
+    fn wrap(self) -> Arc<dyn WriteValidator>
+    where
+        Self: Sized,
+    {
+        Arc::new(self)
+    }
+
+    /// Validate a write. Returning an exception rejects the request without invoking the handler
+    /// and the exception is returned to the client.
+    fn validate(&self, write: &WriteEvent) -> Result<(), ExceptionCode>;
+}
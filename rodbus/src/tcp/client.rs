@@ -5,6 +5,7 @@ use crate::common::phys::PhysLayer;
 use crate::decode::DecodeLevel;
 
 use crate::client::message::Command;
+use crate::client::statistics::SharedStatistics;
 use crate::client::task::{ClientLoop, SessionError, StateChange};
 use crate::common::frame::{FrameWriter, FramedReader};
 use crate::error::Shutdown;
@@ -33,26 +34,30 @@ pub(crate) fn create_tcp_channel(
     listener: Box<dyn Listener<ClientState>>,
 ) -> (Channel, impl std::future::Future<Output = ()>) {
     let (tx, rx) = tokio::sync::mpsc::channel(max_queued_requests);
+    let stats = SharedStatistics::default();
+    let task_stats = stats.clone();
     let task = async move {
         TcpChannelTask::new(
             host.clone(),
             rx.into(),
+            max_queued_requests,
             TcpTaskConnectionHandler::Tcp,
             connect_retry,
             decode,
             listener,
+            task_stats,
         )
         .run()
         .instrument(tracing::info_span!("Modbus-Client-TCP", endpoint = ?host))
         .await;
     };
-    (Channel { tx }, task)
+    (Channel { tx, stats }, task)
 }
 
 pub(crate) enum TcpTaskConnectionHandler {
     Tcp,
     #[cfg(feature = "tls")]
-    Tls(crate::tcp::tls::TlsClientConfig),
+    Tls(Box<crate::tcp::tls::TlsClientConfig>),
 }
 
 impl TcpTaskConnectionHandler {
@@ -67,6 +72,15 @@ impl TcpTaskConnectionHandler {
             Self::Tls(config) => config.handle_connection(socket, _endpoint).await,
         }
     }
+
+    /// Report the certificates of the current session as they enter the warning period, never completes
+    async fn monitor_expiry(&mut self) {
+        match self {
+            Self::Tcp => std::future::pending().await,
+            #[cfg(feature = "tls")]
+            Self::Tls(config) => config.monitor_expiry().await,
+        }
+    }
 }
 
 pub(crate) struct TcpChannelTask {
@@ -78,19 +92,35 @@ pub(crate) struct TcpChannelTask {
 }
 
 impl TcpChannelTask {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         host: HostAddr,
         rx: crate::channel::Receiver<Command>,
+        max_queued_requests: usize,
         connection_handler: TcpTaskConnectionHandler,
         connect_retry: Box<dyn RetryStrategy>,
         decode: DecodeLevel,
         listener: Box<dyn Listener<ClientState>>,
+        stats: SharedStatistics,
     ) -> Self {
+        #[allow(unused_mut)]
+        let mut client_loop = ClientLoop::new(
+            rx,
+            max_queued_requests,
+            FrameWriter::tcp(),
+            FramedReader::tcp(),
+            decode,
+            stats,
+        );
+        #[cfg(feature = "tls")]
+        if let TcpTaskConnectionHandler::Tls(_) = connection_handler {
+            client_loop.enable_tls_reload();
+        }
         Self {
             host,
             connect_retry,
             connection_handler,
-            client_loop: ClientLoop::new(rx, FrameWriter::tcp(), FramedReader::tcp(), decode),
+            client_loop,
             listener,
         }
     }
@@ -120,9 +150,16 @@ impl TcpChannelTask {
     }
 
     async fn try_connect_and_run(&mut self) -> Result<(), StateChange> {
+        #[cfg(feature = "tls")]
+        if let Some(config) = self.client_loop.take_tls_config() {
+            if let TcpTaskConnectionHandler::Tls(current) = &mut self.connection_handler {
+                *current = config;
+            }
+        }
         self.listener.update(ClientState::Connecting).get().await;
         match self.host.connect().await {
             Err(err) => {
+                self.client_loop.stats().on_connect_failure();
                 let delay = self.connect_retry.after_failed_connect();
                 tracing::warn!(
                     "failed to connect to {}: {} - waiting {} ms before next attempt",
@@ -145,6 +182,7 @@ impl TcpChannelTask {
                 }
                 match self.connection_handler.handle(socket, &self.host).await {
                     Err(err) => {
+                        self.client_loop.stats().on_connect_failure();
                         let delay = self.connect_retry.after_failed_connect();
                         tracing::warn!(
                             "{} - waiting {} ms before next attempt",
@@ -158,14 +196,23 @@ impl TcpChannelTask {
                         self.client_loop.fail_requests_for(delay).await
                     }
                     Ok(mut phys) => {
+                        self.client_loop.stats().on_connect();
                         self.listener.update(ClientState::Connected).get().await;
                         // reset the retry strategy now that we have a successful connection
                         // we do this here so that the reset happens after a TLS handshake
                         self.connect_retry.reset();
                         // run the physical layer independent processing loop
-                        match self.client_loop.run(&mut phys).await {
+                        let result = tokio::select! {
+                            result = self.client_loop.run(&mut phys) => result,
+                            // never completes
+                            _ = self.connection_handler.monitor_expiry() => unreachable!(),
+                        };
+                        match result {
                             // the mpsc was closed, end the task
                             SessionError::Shutdown => Err(StateChange::Shutdown),
+                            // reconnect immediately with the new configuration
+                            #[cfg(feature = "tls")]
+                            SessionError::TlsReload => Ok(()),
                             // re-establish the connection
                             SessionError::Disabled
                             | SessionError::IoError(_)
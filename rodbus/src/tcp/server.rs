@@ -2,30 +2,62 @@ use std::collections::BTreeMap;
 
 use tracing::Instrument;
 
+use crate::client::{Listener, NullListener};
 use crate::common::frame::{FrameWriter, FramedReader};
 use crate::common::phys::PhysLayer;
 use crate::decode::DecodeLevel;
-use crate::server::handler::{RequestHandler, ServerHandlerMap};
-use crate::server::task::{AuthorizationType, ServerSetting};
+use crate::server::handler::{
+    RequestHandler, ServerHandlerMap, ServerHandlerType, SharedHandlerMap,
+};
+use crate::server::statistics::{SessionStatistics, SharedServerState};
+use crate::server::task::{AuthorizationType, ServerCommand, ServerSetting};
 
-use crate::server::AddressFilter;
-use std::net::SocketAddr;
+use crate::server::{
+    ActivityLimits, AddressFilter, MaxSessionPolicy, ServerOptions, SessionEvent, SessionLimits,
+    ShutdownMode,
+};
+use std::net::{IpAddr, SocketAddr};
 use tokio::net::TcpListener;
 
-#[cfg(feature = "tls")]
 use crate::server::AuthorizationHandler;
 
-/// event sent back to the server task when a session ends
-struct SessionClose(u128);
+/// Time allowed to complete the TLS handshake of a new session, unless the idle timeout is shorter
+const HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Number of session events buffered for the session listener, events are dropped once it is full
+const SESSION_EVENT_CAPACITY: usize = 256;
+
+/// events sent back to the server task by the sessions
+enum SessionNotification {
+    /// the TLS handshake of the session completed
+    #[cfg(feature = "tls")]
+    TlsEstablished(u128, SocketAddr, crate::tcp::tls::TlsSessionInfo),
+    /// a certificate used by the session is about to expire
+    #[cfg(feature = "tls")]
+    CertificateExpiring(u128, SocketAddr, crate::tcp::tls::ExpiryWarning),
+    /// the session ended
+    Closed(u128, SocketAddr),
+}
+
+struct SessionRecord {
+    sender: tokio::sync::mpsc::Sender<ServerCommand>,
+    ip: IpAddr,
+    priority: bool,
+    // set once the session task is spawned
+    abort: Option<tokio::task::AbortHandle>,
+    // true if the session was accepted on a TLS endpoint
+    tls: bool,
+}
 
 struct SessionTracker {
     max_sessions: usize,
+    limits: SessionLimits,
     id: u128,
-    sessions: BTreeMap<u128, tokio::sync::mpsc::Sender<ServerSetting>>,
+    sessions: BTreeMap<u128, SessionRecord>,
 }
 
 impl SessionTracker {
-    fn new(max_sessions: usize) -> SessionTracker {
+    fn new(max_sessions: usize, limits: SessionLimits) -> Self {
         let max_sessions = if max_sessions == 0 {
             tracing::warn!("Max sessions to 0, defaulting to 1");
             1
@@ -34,6 +66,7 @@ impl SessionTracker {
         };
         Self {
             max_sessions,
+            limits,
             id: 0,
             sessions: BTreeMap::new(),
         }
@@ -45,130 +78,457 @@ impl SessionTracker {
         ret
     }
 
-    pub(crate) fn add(&mut self, sender: tokio::sync::mpsc::Sender<ServerSetting>) -> u128 {
+    /// Returns `None` if the connection is rejected
+    pub(crate) fn add(
+        &mut self,
+        ip: IpAddr,
+        sender: tokio::sync::mpsc::Sender<ServerCommand>,
+        state: &SharedServerState,
+    ) -> Option<u128> {
+        let priority = self.limits.is_priority(ip);
+
+        if let Some(max) = self.limits.max_per_ip {
+            let count = self.sessions.values().filter(|x| x.ip == ip).count();
+            if count >= max && !self.make_room("per-IP", state, |x| x.ip == ip) {
+                return None;
+            }
+        }
+
+        let reserved = self.limits.reserved_count();
+        if !priority && reserved > 0 {
+            let max = self.max_sessions.saturating_sub(reserved);
+            let count = self.sessions.values().filter(|x| !x.priority).count();
+            if count >= max && !self.make_room("non-priority", state, |x| !x.priority) {
+                return None;
+            }
+        }
+
         if self.sessions.len() >= self.max_sessions {
-            if let Some(oldest) = self.sessions.keys().next().copied() {
-                tracing::warn!(
-                    "exceeded max connections, closing oldest session: {}",
-                    oldest
-                );
+            // priority connections close non-priority sessions first
+            let room = (priority && self.make_room("max", state, |x| !x.priority))
+                || self.make_room("max", state, |_| true);
+            if !room {
+                return None;
+            }
+        }
+
+        let id = self.get_next_id();
+        self.sessions.insert(
+            id,
+            SessionRecord {
+                sender,
+                ip,
+                priority,
+                abort: None,
+                tls: false,
+            },
+        );
+        Some(id)
+    }
+
+    /// Close one of the sessions matching the predicate according to the policy
+    fn make_room<F>(&mut self, limit: &str, state: &SharedServerState, predicate: F) -> bool
+    where
+        F: Fn(&SessionRecord) -> bool,
+    {
+        let mut candidates = self
+            .sessions
+            .iter()
+            .filter(|(_, x)| predicate(x))
+            .map(|(id, _)| *id);
+
+        let victim = match self.limits.policy {
+            MaxSessionPolicy::RejectNewest => None,
+            // ids are allocated in ascending order
+            MaxSessionPolicy::EvictOldest => candidates.next(),
+            MaxSessionPolicy::EvictLeastRecentlyActive => {
+                candidates.min_by_key(|id| state.last_activity(*id))
+            }
+        };
+
+        match victim {
+            Some(id) => {
+                tracing::warn!("exceeded {} session limit, closing session: {}", limit, id);
                 // when the record drops, and there are no more senders,
                 // the other end will stop the task
-                self.sessions.remove(&oldest);
+                self.sessions.remove(&id);
+                true
+            }
+            None => {
+                tracing::warn!("exceeded {} session limit, rejecting connection", limit);
+                false
             }
         }
+    }
 
-        let id = self.get_next_id();
-        self.sessions.insert(id, sender);
-        id
+    pub(crate) fn remove(&mut self, id: u128) -> bool {
+        self.sessions.remove(&id).is_some()
     }
 
-    pub(crate) fn remove(&mut self, id: u128) {
-        self.sessions.remove(&id);
+    pub(crate) fn clear(&mut self) {
+        self.sessions.clear();
+    }
+
+    /// Close the sessions whose address does not match the filter
+    pub(crate) fn retain(&mut self, filter: &AddressFilter) {
+        self.sessions.retain(|id, session| {
+            let allowed = filter.matches(session.ip);
+            if !allowed {
+                tracing::info!(
+                    "IP address {:?} no longer matches the filter, closing session: {}",
+                    session.ip,
+                    id
+                );
+            }
+            allowed
+        });
+    }
+}
+
+/// Delivers session events to the session listener from a dedicated task, so that a slow
+/// listener never blocks the server task
+struct SessionEvents {
+    tx: tokio::sync::mpsc::Sender<SessionEvent>,
+    // applied by the task before it delivers the next event
+    replacement: std::sync::Arc<std::sync::Mutex<Option<Box<dyn Listener<SessionEvent>>>>>,
+}
+
+impl SessionEvents {
+    fn spawn(completion: tokio::sync::mpsc::Sender<()>) -> Self {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(SESSION_EVENT_CAPACITY);
+        let replacement = std::sync::Arc::new(std::sync::Mutex::new(None));
+        let pending = replacement.clone();
+        tokio::spawn(async move {
+            // dropped when the server task exits and the remaining events are delivered
+            let _completion = completion;
+            let mut listener = NullListener::create();
+            while let Some(event) = rx.recv().await {
+                if let Some(x) = pending.lock().unwrap().take() {
+                    listener = x;
+                }
+                listener.update(event).get().await;
+            }
+        });
+        Self { tx, replacement }
+    }
+
+    fn set_listener(&self, listener: Box<dyn Listener<SessionEvent>>) {
+        *self.replacement.lock().unwrap() = Some(listener);
+    }
+
+    fn send(&self, event: SessionEvent) {
+        if let Err(tokio::sync::mpsc::error::TrySendError::Full(event)) = self.tx.try_send(event) {
+            tracing::warn!("session listener is not keeping up, dropping: {:?}", event);
+        }
     }
 }
 
 #[derive(Clone)]
 pub(crate) enum TcpServerConnectionHandler {
-    Tcp,
+    Tcp(Option<std::sync::Arc<dyn AuthorizationHandler>>),
     #[cfg(feature = "tls")]
     Tls(
-        crate::tcp::tls::TlsServerConfig,
+        Box<crate::tcp::tls::TlsServerConfig>,
         Option<std::sync::Arc<dyn AuthorizationHandler>>,
     ),
 }
 
 impl TcpServerConnectionHandler {
+    fn is_tls(&self) -> bool {
+        match self {
+            Self::Tcp(_) => false,
+            #[cfg(feature = "tls")]
+            Self::Tls(_, _) => true,
+        }
+    }
+
     async fn handle(
         &mut self,
         socket: tokio::net::TcpStream,
-    ) -> Result<(PhysLayer, AuthorizationType), String> {
+        addr: SocketAddr,
+    ) -> Result<Connection, String> {
         match self {
-            Self::Tcp => Ok((PhysLayer::new_tcp(socket), AuthorizationType::None)),
+            Self::Tcp(auth_handler) => Ok(Connection {
+                phys: PhysLayer::new_tcp(socket),
+                auth: match auth_handler {
+                    None => AuthorizationType::None,
+                    // without certificates, the IP address of the client is its only identity
+                    Some(auth_handler) => AuthorizationType::Handler(
+                        auth_handler.clone(),
+                        vec![crate::server::canonical(addr.ip()).to_string()],
+                    ),
+                },
+                #[cfg(feature = "tls")]
+                tls: None,
+                #[cfg(feature = "tls")]
+                expiring: Vec::new(),
+                #[cfg(feature = "tls")]
+                expiry_timer: Default::default(),
+            }),
             #[cfg(feature = "tls")]
             Self::Tls(config, auth_handler) => {
-                let res = config.handle_connection(socket, auth_handler.clone()).await;
-                if res.is_ok() {
-                    tracing::info!("completed TLS handshake");
-                }
-                res
+                let (phys, auth, info) = config
+                    .handle_connection(socket, auth_handler.clone())
+                    .await?;
+                tracing::info!("completed TLS handshake");
+                let (expiring, expiry_timer) = config.check_expiry(&info);
+                Ok(Connection {
+                    phys,
+                    auth,
+                    tls: Some(info),
+                    expiring,
+                    expiry_timer,
+                })
             }
         }
     }
 }
 
-pub(crate) struct ServerTask<T: RequestHandler> {
+/// Connection of a session, once the TLS handshake is complete if there is one
+struct Connection {
+    phys: PhysLayer,
+    auth: AuthorizationType,
+    #[cfg(feature = "tls")]
+    tls: Option<crate::tcp::tls::TlsSessionInfo>,
+    #[cfg(feature = "tls")]
+    expiring: Vec<crate::tcp::tls::ExpiryWarning>,
+    /// Certificates that enter the warning period later in the session
+    #[cfg(feature = "tls")]
+    expiry_timer: crate::tcp::tls::ExpiryTimer,
+}
+
+/// Listening socket of the server task and how its connections are handled
+pub(crate) struct Endpoint {
     listener: TcpListener,
-    handlers: ServerHandlerMap<T>,
+    handler: TcpServerConnectionHandler,
+}
+
+impl Endpoint {
+    pub(crate) fn new(listener: TcpListener, handler: TcpServerConnectionHandler) -> Self {
+        Self { listener, handler }
+    }
+
+    pub(crate) fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+pub(crate) struct ServerTask<T: RequestHandler> {
+    // empty once the server stops listening
+    endpoints: Vec<Endpoint>,
+    // index of the endpoint polled first, rotated so that a busy endpoint cannot starve the others
+    next_endpoint: usize,
+    session_events: SessionEvents,
+    // shared with the sessions
+    handlers: SharedHandlerMap<T>,
     tracker: SessionTracker,
-    connection_handler: TcpServerConnectionHandler,
     filter: AddressFilter,
     decode: DecodeLevel,
-    tx: tokio::sync::mpsc::Sender<SessionClose>,
-    rx: tokio::sync::mpsc::Receiver<SessionClose>,
+    activity: ActivityLimits,
+    state: SharedServerState,
+    completion: tokio::sync::mpsc::Sender<()>,
+    tx: tokio::sync::mpsc::Sender<SessionNotification>,
+    rx: tokio::sync::mpsc::Receiver<SessionNotification>,
 }
 
 impl<T> ServerTask<T>
 where
     T: RequestHandler,
 {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         max_sessions: usize,
-        listener: TcpListener,
+        endpoints: Vec<Endpoint>,
         handlers: ServerHandlerMap<T>,
-        connection_handler: TcpServerConnectionHandler,
         filter: AddressFilter,
         decode: DecodeLevel,
+        options: ServerOptions,
+        state: SharedServerState,
+        completion: tokio::sync::mpsc::Sender<()>,
     ) -> Self {
         let (tx, rx) = tokio::sync::mpsc::channel(8);
 
         Self {
-            listener,
-            handlers,
-            tracker: SessionTracker::new(max_sessions),
-            connection_handler,
+            endpoints,
+            next_endpoint: 0,
+            session_events: SessionEvents::spawn(completion.clone()),
+            handlers: SharedHandlerMap::new(handlers),
+            tracker: SessionTracker::new(max_sessions, options.session_limits),
             filter,
             decode,
+            activity: options.activity_limits,
+            state,
+            completion,
             tx,
             rx,
         }
     }
 
-    async fn change_setting(&mut self, setting: ServerSetting) {
+    /// Returns false if the server must shut down
+    async fn handle_command(&mut self, cmd: ServerCommand) -> bool {
+        match cmd {
+            ServerCommand::Setting(setting) => self.change_setting(setting),
+            ServerCommand::DisconnectSession(id) => {
+                // dropping the sender stops the session task
+                if self.tracker.remove(id) {
+                    tracing::info!("disconnecting session: {}", id);
+                } else {
+                    tracing::warn!("unable to disconnect unknown session: {}", id);
+                }
+            }
+            ServerCommand::DisconnectAll => {
+                tracing::info!("disconnecting all sessions");
+                self.tracker.clear();
+            }
+            ServerCommand::StopListening => {
+                if !std::mem::take(&mut self.endpoints).is_empty() {
+                    tracing::info!("stopped listening for new connections");
+                }
+            }
+            ServerCommand::SetSessionListener(listener) => {
+                self.session_events.set_listener(listener);
+            }
+            ServerCommand::SetSessionLimits(limits) => {
+                tracing::info!("changed session limits to {:?}", limits);
+                self.tracker.limits = limits;
+            }
+            ServerCommand::SetAddressFilter(filter) => {
+                tracing::info!("changed address filter to {:?}", filter);
+                self.tracker.retain(&filter);
+                self.filter = filter;
+            }
+            ServerCommand::AddHandler(id, handler, ack) => {
+                match handler.downcast::<ServerHandlerType<T>>() {
+                    Ok(handler) => {
+                        tracing::info!("adding handler for: {}", id);
+                        self.handlers.add(id, *handler);
+                    }
+                    Err(_) => tracing::error!("ignoring handler of the wrong type for: {}", id),
+                }
+                if let Some(ack) = ack {
+                    let _ = ack.send(());
+                }
+            }
+            ServerCommand::RemoveHandler(id, ack) => {
+                tracing::info!("removing handler for: {}", id);
+                self.handlers.remove(id);
+                if let Some(ack) = ack {
+                    let _ = ack.send(());
+                }
+            }
+            #[cfg(feature = "tls")]
+            ServerCommand::SetTlsConfig(config, policy) => self.set_tls_config(config, policy),
+            ServerCommand::Shutdown(mode) => {
+                tracing::info!("server shutdown ({:?})", mode);
+                if mode == ShutdownMode::Abort {
+                    for session in self.tracker.sessions.values() {
+                        if let Some(abort) = &session.abort {
+                            abort.abort();
+                        }
+                    }
+                }
+                // the remaining sessions stop once the tracker is dropped
+                return false;
+            }
+        }
+        true
+    }
+
+    #[cfg(feature = "tls")]
+    fn set_tls_config(
+        &mut self,
+        config: crate::tcp::tls::TlsServerConfig,
+        policy: crate::server::TlsReloadPolicy,
+    ) {
+        let mut count = 0;
+        for endpoint in self.endpoints.iter_mut() {
+            if let TcpServerConnectionHandler::Tls(current, _) = &mut endpoint.handler {
+                **current = config.clone();
+                count += 1;
+            }
+        }
+
+        if count == 0 {
+            tracing::warn!("ignoring TLS configuration, the server has no TLS endpoint");
+            return;
+        }
+
+        tracing::info!("changed TLS configuration of {} endpoint(s)", count);
+        if policy == crate::server::TlsReloadPolicy::CloseSessions {
+            self.tracker.sessions.retain(|id, session| {
+                if session.tls {
+                    tracing::info!("closing session after TLS configuration change: {}", id);
+                }
+                !session.tls
+            });
+        }
+    }
+
+    fn change_setting(&mut self, setting: ServerSetting) {
         // first, change it locally so that it is applied to new sessions
         match setting {
             ServerSetting::ChangeDecoding(level) => {
                 tracing::info!("changed decoding level to {:?}", level);
                 self.decode = level;
             }
+            ServerSetting::ChangeActivityLimits(limits) => {
+                tracing::info!("changed activity limits to {:?}", limits);
+                self.activity = limits;
+            }
         }
 
-        for sender in self.tracker.sessions.values_mut() {
-            // best effort to send the setting to each session this isn't critical so we wouldn't
-            // want to slow the server down by awaiting it
-            let _ = sender.send(setting).await;
-        }
+        // never wait on a session, one that has not processed its previous commands is stuck
+        self.tracker.sessions.retain(|id, session| {
+            match session.sender.try_send(ServerCommand::Setting(setting)) {
+                Err(tokio::sync::mpsc::error::TrySendError::Full(_)) => {
+                    tracing::warn!("session is not processing commands, closing it: {}", id);
+                    false
+                }
+                // a closed session is removed once its task reports that it exited
+                _ => true,
+            }
+        });
     }
 
-    pub(crate) async fn run(&mut self, mut commands: tokio::sync::mpsc::Receiver<ServerSetting>) {
+    pub(crate) async fn run(&mut self, mut commands: tokio::sync::mpsc::Receiver<ServerCommand>) {
         loop {
             tokio::select! {
-               setting = commands.recv() => {
-                    match setting {
-                        Some(setting) => self.change_setting(setting).await,
+               // administrative commands take precedence over new connections
+               biased;
+
+               cmd = commands.recv() => {
+                    match cmd {
+                        Some(cmd) => {
+                            if !self.handle_command(cmd).await {
+                                return;
+                            }
+                        }
                         None => {
                             tracing::info!("server shutdown");
                             return; // shutdown signal
                         }
                     }
                }
-               shutdown = self.rx.recv() => {
+               notification = self.rx.recv() => {
                    // this will never be None b/c we always keep a tx live
-                   let id = shutdown.unwrap().0;
-
-                   self.tracker.remove(id);
+                   match notification.unwrap() {
+                       #[cfg(feature = "tls")]
+                       SessionNotification::TlsEstablished(id, peer, session) => {
+                           self.session_events.send(SessionEvent::TlsEstablished { id, peer, session });
+                       }
+                       #[cfg(feature = "tls")]
+                       SessionNotification::CertificateExpiring(id, peer, warning) => {
+                           self.session_events.send(SessionEvent::CertificateExpiring { id, peer, warning });
+                       }
+                       SessionNotification::Closed(id, peer) => {
+                           self.tracker.remove(id);
+                           self.state.remove_session(id);
+                           self.session_events.send(SessionEvent::Closed { id, peer });
+                       }
+                   }
                }
-               result = self.listener.accept() => {
+               (index, result) = accept(&self.endpoints, self.next_endpoint) => {
+                   self.next_endpoint = index + 1;
                    match result {
                         Err(err) => {
                             tracing::error!("error accepting connection: {}", err);
@@ -179,9 +539,11 @@ where
                                 if let Err(err) = socket.set_nodelay(true) {
                                     tracing::warn!("unable to enable TCP_NODELAY: {}", err);
                                 }
-                                self.handle(socket, addr).await
+                                let handler = self.endpoints[index].handler.clone();
+                                self.handle(socket, addr, handler).await
                             } else {
                                 tracing::warn!("IP address {:?} does not match filter {:?}, closing connection", addr.ip(), self.filter);
+                                self.state.on_session_rejected();
                             }
                         }
                    }
@@ -190,9 +552,24 @@ where
         }
     }
 
-    async fn handle(&mut self, socket: tokio::net::TcpStream, addr: SocketAddr) {
+    async fn handle(
+        &mut self,
+        socket: tokio::net::TcpStream,
+        addr: SocketAddr,
+        connection_handler: TcpServerConnectionHandler,
+    ) {
         let (tx, rx) = tokio::sync::mpsc::channel(8); // all we do is change settings, so a constant is fine
-        let id = self.tracker.add(tx);
+        let id = match self.tracker.add(addr.ip(), tx, &self.state) {
+            Some(id) => id,
+            None => {
+                // dropping the socket closes the connection
+                self.state.on_session_rejected();
+                return;
+            }
+        };
+        self.state.add_session(id, addr);
+        self.session_events
+            .send(SessionEvent::Opened { id, peer: addr });
         tracing::info!(
             "accepted connection from: {} - assigned session id: {}",
             addr,
@@ -201,23 +578,35 @@ where
 
         #[allow(unused_mut)]
         let mut notify_close = self.tx.clone();
-        let connection_handler = self.connection_handler.clone();
         let handler_map = self.handlers.clone();
         let decode_level = self.decode;
+        let activity = self.activity;
+        let stats = self.state.session(Some(id));
+        let completion = self.completion.clone();
+        let tls = connection_handler.is_tls();
 
         let session = async move {
+            // dropped when the session exits or is aborted
+            let _completion = completion;
+
             run_session(
                 socket,
+                id,
                 addr,
                 connection_handler,
                 decode_level,
+                activity,
                 handler_map,
                 rx,
+                stats,
+                notify_close.clone(),
             )
             .await;
 
             // no matter what happens, we send the id back to the server
-            let _ = notify_close.send(SessionClose(id)).await;
+            let _ = notify_close
+                .send(SessionNotification::Closed(id, addr))
+                .await;
 
             tracing::info!("session shutdown");
         };
@@ -226,33 +615,233 @@ where
             session.instrument(tracing::info_span!("Session", "id" = ?id, "remote" = ?addr));
 
         // spawn the session off onto another task
-        tokio::spawn(session);
+        let task = tokio::spawn(session);
+        if let Some(record) = self.tracker.sessions.get_mut(&id) {
+            record.abort = Some(task.abort_handle());
+            record.tls = tls;
+        }
     }
 }
 
+/// Accept a connection on any of the endpoints, starting with the endpoint at index `first`
+async fn accept(
+    endpoints: &[Endpoint],
+    first: usize,
+) -> (usize, std::io::Result<(tokio::net::TcpStream, SocketAddr)>) {
+    std::future::poll_fn(|cx| {
+        for offset in 0..endpoints.len() {
+            let index = (first + offset) % endpoints.len();
+            if let std::task::Poll::Ready(result) = endpoints[index].listener.poll_accept(cx) {
+                return std::task::Poll::Ready((index, result));
+            }
+        }
+        // never completes once the server stops listening
+        std::task::Poll::Pending
+    })
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn run_session<T: RequestHandler>(
     socket: tokio::net::TcpStream,
+    #[cfg_attr(not(feature = "tls"), allow(unused_variables))] id: u128,
     addr: SocketAddr,
     mut handler: TcpServerConnectionHandler,
     decode: DecodeLevel,
-    handlers: ServerHandlerMap<T>,
-    commands: tokio::sync::mpsc::Receiver<ServerSetting>,
+    activity: ActivityLimits,
+    handlers: SharedHandlerMap<T>,
+    mut commands: tokio::sync::mpsc::Receiver<ServerCommand>,
+    stats: SessionStatistics,
+    #[cfg_attr(not(feature = "tls"), allow(unused_variables))] notify: tokio::sync::mpsc::Sender<
+        SessionNotification,
+    >,
 ) {
-    match handler.handle(socket).await {
-        Err(err) => {
+    // commands received during the handshake are applied once the session task is created
+    let mut pending = Vec::new();
+    let idle_timeout = activity.idle_timeout.filter(|x| *x < HANDSHAKE_TIMEOUT);
+    let timeout = idle_timeout.unwrap_or(HANDSHAKE_TIMEOUT);
+    let handshake = tokio::time::timeout(timeout, handler.handle(socket, addr));
+    tokio::pin!(handshake);
+    let result = loop {
+        tokio::select! {
+            result = &mut handshake => break result,
+            cmd = commands.recv() => match cmd {
+                // the session was closed or the server is shutting down
+                None | Some(ServerCommand::Shutdown(_)) => {
+                    tracing::info!("session closed during the handshake");
+                    return;
+                }
+                Some(cmd) => pending.push(cmd),
+            }
+        }
+    };
+
+    match result {
+        Err(_) => {
+            tracing::warn!("handshake with {} timed out", addr);
+            if idle_timeout.is_some() {
+                stats.on_idle_timeout();
+            }
+        }
+        Ok(Err(err)) => {
             tracing::warn!("error from {}: {}", addr, err);
         }
-        Ok((mut phys, auth)) => {
-            let _ = crate::server::task::SessionTask::new(
+        Ok(Ok(mut conn)) => {
+            #[cfg(feature = "tls")]
+            if let Some(info) = conn.tls.clone() {
+                stats.set_tls_session(info.clone());
+                let _ = notify
+                    .send(SessionNotification::TlsEstablished(id, addr, info))
+                    .await;
+            }
+            #[cfg(feature = "tls")]
+            for warning in conn.expiring {
+                let _ = notify
+                    .send(SessionNotification::CertificateExpiring(id, addr, warning))
+                    .await;
+            }
+
+            let mut task = crate::server::task::SessionTask::new(
                 handlers,
-                auth,
+                conn.auth,
                 FrameWriter::tcp(),
                 FramedReader::tcp(),
                 commands,
                 decode,
+                stats,
+                Some(activity),
             )
-            .run(&mut phys)
-            .await;
+            .with_peer(addr);
+            #[cfg(feature = "tls")]
+            {
+                task = task.with_tls_session(conn.tls);
+            }
+            for cmd in pending {
+                task.apply_command(cmd);
+            }
+
+            #[cfg(feature = "tls")]
+            {
+                let mut timer = conn.expiry_timer;
+                let expiry = async {
+                    loop {
+                        for warning in timer.next().await {
+                            let _ = notify
+                                .send(SessionNotification::CertificateExpiring(id, addr, warning))
+                                .await;
+                        }
+                    }
+                };
+                // the timer never completes
+                tokio::select! {
+                    _ = task.run(&mut conn.phys) => {}
+                    _ = expiry => {}
+                }
+            }
+            #[cfg(not(feature = "tls"))]
+            let _ = task.run(&mut conn.phys).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([192, 168, 0, last])
+    }
+
+    fn sender() -> tokio::sync::mpsc::Sender<ServerCommand> {
+        tokio::sync::mpsc::channel(1).0
+    }
+
+    fn ids(tracker: &SessionTracker) -> Vec<u128> {
+        tracker.sessions.keys().copied().collect()
+    }
+
+    #[test]
+    fn evicts_oldest_session_by_default() {
+        let state = SharedServerState::default();
+        let mut tracker = SessionTracker::new(2, SessionLimits::default());
+        for _ in 0..3 {
+            assert!(tracker.add(ip(1), sender(), &state).is_some());
         }
+        assert_eq!(ids(&tracker), vec![1, 2]);
+    }
+
+    #[test]
+    fn rejects_newest_connection() {
+        let state = SharedServerState::default();
+        let mut tracker =
+            SessionTracker::new(2, SessionLimits::new(MaxSessionPolicy::RejectNewest));
+        assert_eq!(tracker.add(ip(1), sender(), &state), Some(0));
+        assert_eq!(tracker.add(ip(2), sender(), &state), Some(1));
+        assert_eq!(tracker.add(ip(3), sender(), &state), None);
+        assert_eq!(ids(&tracker), vec![0, 1]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn evicts_least_recently_active_session() {
+        let state = SharedServerState::default();
+        let mut tracker = SessionTracker::new(
+            2,
+            SessionLimits::new(MaxSessionPolicy::EvictLeastRecentlyActive),
+        );
+        for id in 0..2 {
+            assert_eq!(tracker.add(ip(1), sender(), &state), Some(id));
+            state.add_session(id, SocketAddr::new(ip(1), 5000));
+        }
+        tokio::time::advance(std::time::Duration::from_millis(1)).await;
+        state.session(Some(0)).on_request(0x01);
+
+        assert_eq!(tracker.add(ip(1), sender(), &state), Some(2));
+        assert_eq!(ids(&tracker), vec![0, 2]);
+    }
+
+    #[test]
+    fn per_ip_limit_only_affects_sessions_from_the_same_address() {
+        let state = SharedServerState::default();
+        let mut tracker = SessionTracker::new(4, SessionLimits::default().with_max_per_ip(1));
+        assert_eq!(tracker.add(ip(1), sender(), &state), Some(0));
+        assert_eq!(tracker.add(ip(2), sender(), &state), Some(1));
+        // the oldest session of the same address is evicted
+        assert_eq!(tracker.add(ip(2), sender(), &state), Some(2));
+        assert_eq!(ids(&tracker), vec![0, 2]);
+
+        let mut tracker = SessionTracker::new(
+            4,
+            SessionLimits::new(MaxSessionPolicy::RejectNewest).with_max_per_ip(1),
+        );
+        assert_eq!(tracker.add(ip(1), sender(), &state), Some(0));
+        assert_eq!(tracker.add(ip(1), sender(), &state), None);
+        assert_eq!(tracker.add(ip(2), sender(), &state), Some(1));
+    }
+
+    #[test]
+    fn reserves_sessions_for_priority_addresses() {
+        let state = SharedServerState::default();
+        let limits = SessionLimits::new(MaxSessionPolicy::RejectNewest)
+            .with_reserved_sessions(AddressFilter::Exact(ip(100)), 1);
+        let mut tracker = SessionTracker::new(2, limits);
+
+        assert_eq!(tracker.add(ip(1), sender(), &state), Some(0));
+        // the remaining session is reserved
+        assert_eq!(tracker.add(ip(2), sender(), &state), None);
+        assert_eq!(tracker.add(ip(100), sender(), &state), Some(1));
+        assert_eq!(tracker.add(ip(100), sender(), &state), None);
+    }
+
+    #[test]
+    fn priority_connection_evicts_non_priority_session_first() {
+        let state = SharedServerState::default();
+        let limits = SessionLimits::new(MaxSessionPolicy::EvictOldest)
+            .with_reserved_sessions(AddressFilter::Exact(ip(100)), 1);
+        let mut tracker = SessionTracker::new(2, limits);
+
+        assert_eq!(tracker.add(ip(100), sender(), &state), Some(0));
+        assert_eq!(tracker.add(ip(1), sender(), &state), Some(1));
+        assert_eq!(tracker.add(ip(100), sender(), &state), Some(2));
+        assert_eq!(ids(&tracker), vec![0, 2]);
     }
 }
@@ -1,18 +1,24 @@
 use std::convert::TryFrom;
 use std::net::Ipv4Addr;
 
-use sfio_rustls_config::NameVerifier;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use tokio::net::TcpStream;
 use tokio_rustls::rustls;
 use tracing::Instrument;
 
-use crate::client::{Channel, ClientState, HostAddr, Listener, RetryStrategy};
+use crate::client::statistics::SharedStatistics;
+use crate::client::{Channel, ClientState, HostAddr, Listener, NullListener, RetryStrategy};
 use crate::common::phys::PhysLayer;
 use crate::tcp::client::{TcpChannelTask, TcpTaskConnectionHandler};
-use crate::tcp::tls::{CertificateMode, MinTlsVersion, TlsError};
+use crate::tcp::tls::credentials::{self, CredentialFiles};
+use crate::tcp::tls::verifiers::{RevocationVerifier, SelfSignedVerifier, ServerCertVerifier};
+use crate::tcp::tls::{
+    CertificateMode, CertificateValidity, CryptoPolicy, ExpiryMonitor, ExpiryTimer, ExpiryWarning,
+    MinTlsVersion, RevocationLists, TlsError, TlsSessionInfo,
+};
 
 use crate::DecodeLevel;
 
@@ -20,6 +26,12 @@ use crate::DecodeLevel;
 pub struct TlsClientConfig {
     server_name: rustls::ServerName,
     config: Arc<rustls::ClientConfig>,
+    credentials: Credentials,
+    policy: CryptoPolicy,
+    session_listener: Box<dyn Listener<TlsSessionInfo>>,
+    expiry: ExpiryMonitor,
+    expiry_listener: Box<dyn Listener<ExpiryWarning>>,
+    expiry_timer: ExpiryTimer,
 }
 
 pub(crate) fn spawn_tls_channel(
@@ -51,20 +63,24 @@ pub(crate) fn create_tls_channel(
     listener: Box<dyn Listener<ClientState>>,
 ) -> (Channel, impl std::future::Future<Output = ()>) {
     let (tx, rx) = tokio::sync::mpsc::channel(max_queued_requests);
+    let stats = SharedStatistics::default();
+    let task_stats = stats.clone();
     let task = async move {
         TcpChannelTask::new(
             host.clone(),
             rx.into(),
-            TcpTaskConnectionHandler::Tls(tls_config),
+            max_queued_requests,
+            TcpTaskConnectionHandler::Tls(Box::new(tls_config)),
             connect_retry,
             decode,
             listener,
+            task_stats,
         )
         .run()
         .instrument(tracing::info_span!("Modbus-Client-TCP", endpoint = ?host))
         .await;
     };
-    (Channel { tx }, task)
+    (Channel { tx, stats }, task)
 }
 
 impl TlsClientConfig {
@@ -117,29 +133,63 @@ impl TlsClientConfig {
         password: Option<&str>,
         min_tls_version: MinTlsVersion,
     ) -> Result<Self, TlsError> {
-        let (name_verifier, server_name) = match server_subject_name {
-            None => (
-                NameVerifier::any(),
-                rustls::ServerName::IpAddress(Ipv4Addr::UNSPECIFIED.into()),
-            ),
-            Some(x) => {
-                let server_name = rustls::ServerName::try_from(x.as_str())?;
-                (NameVerifier::equal_to(x), server_name)
-            }
+        let files = CredentialFiles::read(peer_cert_path, local_cert_path, private_key_path)?;
+        Self::full_pki_from_bytes(
+            server_subject_name,
+            &files.peer_certs,
+            &files.local_certs,
+            &files.private_key,
+            password,
+            min_tls_version,
+        )
+    }
+
+    /// Create a TLS client configuration that expects a full PKI from credentials held in memory.
+    /// See [`TlsClientConfig::full_pki`] for the details of the validation.
+    ///
+    /// Certificates are either PEM encoded or a single DER encoded certificate. The private key is
+    /// PEM or DER encoded, and must be a PKCS#8 encrypted key if a password is specified.
+    pub fn full_pki_from_bytes(
+        server_subject_name: Option<String>,
+        peer_certs: &[u8],
+        local_certs: &[u8],
+        private_key: &[u8],
+        password: Option<&str>,
+        min_tls_version: MinTlsVersion,
+    ) -> Result<Self, TlsError> {
+        let server_name = match &server_subject_name {
+            None => rustls::ServerName::IpAddress(Ipv4Addr::UNSPECIFIED.into()),
+            Some(x) => rustls::ServerName::try_from(x.as_str())?,
         };
 
-        let config = sfio_rustls_config::client::authority(
-            min_tls_version.into(),
-            name_verifier,
-            peer_cert_path,
-            local_cert_path,
-            private_key_path,
-            password,
-        )?;
+        let peer_certs =
+            credentials::certificates(peer_certs).map_err(credentials::bad_peer_cert)?;
+        let local_certs =
+            credentials::certificates(local_certs).map_err(credentials::bad_local_cert)?;
+        let private_key = credentials::private_key(private_key, password)
+            .map_err(credentials::bad_private_key)?;
+        let expiry = ExpiryMonitor::new(&local_certs[0], &peer_certs)?;
+        let verifier = ServerCertVerifier::new(peer_certs, server_subject_name)
+            .map_err(credentials::bad_peer_cert)?;
+
+        let credentials = Credentials {
+            verifier: Arc::new(verifier),
+            revocation: None,
+            local_certs,
+            private_key,
+        };
+        let policy = CryptoPolicy::from(min_tls_version);
+        let config = credentials.build(&policy)?;
 
         Ok(Self {
             server_name,
             config: Arc::new(config),
+            credentials,
+            policy,
+            session_listener: NullListener::create(),
+            expiry,
+            expiry_listener: NullListener::create(),
+            expiry_timer: ExpiryTimer::default(),
         })
     }
 
@@ -158,21 +208,118 @@ impl TlsClientConfig {
         password: Option<&str>,
         min_tls_version: MinTlsVersion,
     ) -> Result<Self, TlsError> {
-        let config = sfio_rustls_config::client::self_signed(
-            min_tls_version.into(),
-            peer_cert_path,
-            local_cert_path,
-            private_key_path,
+        let files = CredentialFiles::read(peer_cert_path, local_cert_path, private_key_path)?;
+        Self::self_signed_from_bytes(
+            &files.peer_certs,
+            &files.local_certs,
+            &files.private_key,
             password,
-        )?;
+            min_tls_version,
+        )
+    }
+
+    /// Create a TLS client configuration that expects the server to present a single certificate
+    /// from credentials held in memory. See [`TlsClientConfig::self_signed`] for the details of the
+    /// validation.
+    ///
+    /// Certificates are either PEM encoded or a single DER encoded certificate. The private key is
+    /// PEM or DER encoded, and must be a PKCS#8 encrypted key if a password is specified.
+    pub fn self_signed_from_bytes(
+        peer_cert: &[u8],
+        local_cert: &[u8],
+        private_key: &[u8],
+        password: Option<&str>,
+        min_tls_version: MinTlsVersion,
+    ) -> Result<Self, TlsError> {
+        let peer_cert =
+            credentials::single_certificate(peer_cert).map_err(credentials::bad_peer_cert)?;
+        let local_cert =
+            credentials::single_certificate(local_cert).map_err(credentials::bad_local_cert)?;
+        let private_key = credentials::private_key(private_key, password)
+            .map_err(credentials::bad_private_key)?;
+        let expiry = ExpiryMonitor::new(&local_cert, std::slice::from_ref(&peer_cert))?;
+        let verifier = SelfSignedVerifier::new(peer_cert).map_err(credentials::bad_peer_cert)?;
+
+        let credentials = Credentials {
+            verifier: Arc::new(verifier),
+            revocation: None,
+            local_certs: vec![local_cert],
+            private_key,
+        };
+        let policy = CryptoPolicy::from(min_tls_version);
+        let config = credentials.build(&policy)?;
 
         Ok(Self {
             //  it doesn't matter what we put here, it just needs to be an IP so that the client won't send an SNI extension
             server_name: rustls::ServerName::IpAddress(Ipv4Addr::UNSPECIFIED.into()),
             config: Arc::new(config),
+            credentials,
+            policy,
+            session_listener: NullListener::create(),
+            expiry,
+            expiry_listener: NullListener::create(),
+            expiry_timer: ExpiryTimer::default(),
         })
     }
 
+    /// Reject servers presenting a certificate listed in one of the certificate revocation lists
+    ///
+    /// The handshake fails with a `certificate_revoked` alert, before any request is sent. Session
+    /// resumption is disabled so that the lists are checked on every connection.
+    pub fn with_revocation_lists(mut self, lists: RevocationLists) -> Result<Self, TlsError> {
+        self.credentials.revocation = Some(lists);
+        self.config = Arc::new(self.credentials.build(&self.policy)?);
+        Ok(self)
+    }
+
+    /// Receive the parameters of each TLS session once its handshake completes
+    pub fn with_session_listener(mut self, listener: Box<dyn Listener<TlsSessionInfo>>) -> Self {
+        self.session_listener = listener;
+        self
+    }
+
+    /// Restrict the protocol versions, cipher suites and key exchange groups that may be negotiated
+    ///
+    /// The policy replaces the protocol versions allowed by the [`MinTlsVersion`] of the
+    /// configuration. An error is returned if the lists are inconsistent.
+    pub fn with_crypto_policy(mut self, policy: CryptoPolicy) -> Result<Self, TlsError> {
+        self.config = Arc::new(self.credentials.build(&policy)?);
+        self.policy = policy;
+        Ok(self)
+    }
+
+    /// Protocol versions, cipher suites and key exchange groups that may be negotiated
+    pub fn crypto_policy(&self) -> &CryptoPolicy {
+        &self.policy
+    }
+
+    /// Validity of the certificate presented to the server
+    pub fn local_certificate(&self) -> &CertificateValidity {
+        &self.expiry.local
+    }
+
+    /// Validity of the certificates the server certificate is checked against, i.e. the trust
+    /// anchors or the expected self-signed certificate
+    pub fn peer_certificates(&self) -> &[CertificateValidity] {
+        self.expiry.peers.as_slice()
+    }
+
+    /// Warn when the local certificate or the certificate presented by the server expires within
+    /// `warn_before`. Defaults to [`crate::client::DEFAULT_EXPIRY_WARNING`].
+    ///
+    /// The certificates are checked at every handshake, and once more when they enter the warning
+    /// period while the session is established.
+    pub fn with_expiry_warning(mut self, warn_before: Duration) -> Self {
+        self.expiry.warn_before = warn_before;
+        self
+    }
+
+    /// Receive the warnings about certificates that are about to expire, in addition to the log messages
+    pub fn with_expiry_listener(mut self, listener: Box<dyn Listener<ExpiryWarning>>) -> Self {
+        self.expiry_listener = listener;
+        self
+    }
+
     pub(crate) async fn handle_connection(
         &mut self,
         socket: TcpStream,
@@ -183,7 +330,58 @@ impl TlsClientConfig {
             Err(err) => Err(format!(
                 "failed to establish TLS session with {endpoint}: {err}"
             )),
-            Ok(stream) => Ok(PhysLayer::new_tls(tokio_rustls::TlsStream::from(stream))),
+            Ok(stream) => {
+                let info = TlsSessionInfo::new(stream.get_ref().1)
+                    .map_err(|err| format!("rejected TLS session with {endpoint}: {err}"))?;
+                info.log();
+                let (warnings, timer) = self.expiry.check(&info);
+                self.expiry_timer = timer;
+                for warning in warnings {
+                    self.expiry_listener.update(warning).get().await;
+                }
+                self.session_listener.update(info).get().await;
+                Ok(PhysLayer::new_tls(tokio_rustls::TlsStream::from(stream)))
+            }
+        }
+    }
+
+    /// Report the certificates of the current session as they enter the warning period, never completes
+    pub(crate) async fn monitor_expiry(&mut self) {
+        loop {
+            for warning in self.expiry_timer.next().await {
+                self.expiry_listener.update(warning).get().await;
+            }
+        }
+    }
+}
+
+/// Inputs of the rustls configuration, kept so that it can be rebuilt with another crypto policy
+struct Credentials {
+    verifier: Arc<dyn rustls::client::ServerCertVerifier>,
+    revocation: Option<RevocationLists>,
+    local_certs: Vec<rustls::Certificate>,
+    private_key: rustls::PrivateKey,
+}
+
+impl Credentials {
+    fn build(&self, policy: &CryptoPolicy) -> Result<rustls::ClientConfig, TlsError> {
+        let verifier: Arc<dyn rustls::client::ServerCertVerifier> = match &self.revocation {
+            None => self.verifier.clone(),
+            Some(lists) => Arc::new(RevocationVerifier::new(
+                self.verifier.clone(),
+                lists.clone(),
+            )),
+        };
+        let mut config = policy
+            .client_builder()?
+            .with_custom_certificate_verifier(verifier)
+            .with_client_auth_cert(self.local_certs.clone(), self.private_key.clone())?;
+
+        // resumed sessions skip the verifier, so the server must present its certificate again
+        if self.revocation.is_some() {
+            config.resumption = rustls::client::Resumption::disabled();
         }
+
+        Ok(config)
     }
 }
@@ -0,0 +1,167 @@
+use std::path::Path;
+
+use tokio_rustls::rustls;
+
+use crate::tcp::tls::TlsError;
+
+/// PEM tags of the supported plain-text private keys (PKCS #8, PKCS #1 and SEC1)
+const PLAINTEXT_KEY_TAGS: &[&str] = &["PRIVATE KEY", "RSA PRIVATE KEY", "EC PRIVATE KEY"];
+/// PEM tag of PKCS #8 encrypted private keys
+const ENCRYPTED_KEY_TAG: &str = "ENCRYPTED PRIVATE KEY";
+
+/// Contents of the peer certificate, local certificate and private key files
+pub(crate) struct CredentialFiles {
+    pub(crate) peer_certs: Vec<u8>,
+    pub(crate) local_certs: Vec<u8>,
+    pub(crate) private_key: Vec<u8>,
+}
+
+impl CredentialFiles {
+    pub(crate) fn read(
+        peer_cert_path: &Path,
+        local_cert_path: &Path,
+        private_key_path: &Path,
+    ) -> Result<Self, TlsError> {
+        Ok(Self {
+            peer_certs: std::fs::read(peer_cert_path).map_err(TlsError::InvalidPeerCertificate)?,
+            local_certs: std::fs::read(local_cert_path)
+                .map_err(TlsError::InvalidLocalCertificate)?,
+            private_key: std::fs::read(private_key_path).map_err(TlsError::InvalidPrivateKey)?,
+        })
+    }
+}
+
+fn invalid_data(err: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+}
+
+pub(crate) fn bad_peer_cert(err: String) -> TlsError {
+    TlsError::InvalidPeerCertificate(invalid_data(err))
+}
+
+pub(crate) fn bad_local_cert(err: String) -> TlsError {
+    TlsError::InvalidLocalCertificate(invalid_data(err))
+}
+
+pub(crate) fn bad_private_key(err: String) -> TlsError {
+    TlsError::InvalidPrivateKey(invalid_data(err))
+}
+
+fn is_pem(bytes: &[u8]) -> bool {
+    // PEM files may start with explanatory text or whitespace
+    bytes.windows(11).any(|x| x == b"-----BEGIN ")
+}
+
+/// Read one or more certificates, either PEM encoded or a single DER encoded certificate
+pub(crate) fn certificates(bytes: &[u8]) -> Result<Vec<rustls::Certificate>, String> {
+    if !is_pem(bytes) {
+        return Ok(vec![rustls::Certificate(bytes.to_vec())]);
+    }
+
+    let certs: Vec<rustls::Certificate> = pem::parse_many(bytes)
+        .map_err(|err| format!("PEM error: {err}"))?
+        .into_iter()
+        .filter(|x| x.tag() == "CERTIFICATE")
+        .map(|x| rustls::Certificate(x.into_contents()))
+        .collect();
+
+    if certs.is_empty() {
+        return Err("PEM data does not contain a certificate".to_string());
+    }
+
+    Ok(certs)
+}
+
+/// Read exactly one certificate, either PEM or DER encoded
+pub(crate) fn single_certificate(bytes: &[u8]) -> Result<rustls::Certificate, String> {
+    let mut certs = certificates(bytes)?;
+    if certs.len() > 1 {
+        return Err("PEM data contains more than one certificate".to_string());
+    }
+    Ok(certs.remove(0))
+}
+
+/// Read a private key, either PEM or DER encoded
+///
+/// When a password is specified, the key must be a PKCS #8 encrypted private key.
+pub(crate) fn private_key(
+    bytes: &[u8],
+    password: Option<&str>,
+) -> Result<rustls::PrivateKey, String> {
+    let der = if is_pem(bytes) {
+        let tags: &[&str] = match password {
+            Some(_) => &[ENCRYPTED_KEY_TAG],
+            None => PLAINTEXT_KEY_TAGS,
+        };
+        let mut keys = pem::parse_many(bytes)
+            .map_err(|err| format!("PEM error: {err}"))?
+            .into_iter()
+            .filter(|x| tags.contains(&x.tag()));
+        let key = keys
+            .next()
+            .ok_or_else(|| "PEM data does not contain a supported private key".to_string())?;
+        if keys.next().is_some() {
+            return Err("PEM data contains more than one private key".to_string());
+        }
+        key.into_contents()
+    } else {
+        bytes.to_vec()
+    };
+
+    match password {
+        None => Ok(rustls::PrivateKey(der)),
+        Some(password) => {
+            let encrypted = pkcs8::EncryptedPrivateKeyInfo::try_from(der.as_slice())
+                .map_err(|err| format!("PKCS#8 error: {err}"))?;
+            let decrypted = encrypted
+                .decrypt(password)
+                .map_err(|err| format!("PKCS#8 error: {err}"))?;
+            Ok(rustls::PrivateKey(decrypted.as_bytes().to_vec()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIR: &str = "../certs/revocation";
+
+    fn read(name: &str) -> Vec<u8> {
+        std::fs::read(Path::new(DIR).join(name)).unwrap()
+    }
+
+    #[test]
+    fn reads_pem_and_der_certificates() {
+        let pem = certificates(&read("server_cert.pem")).unwrap();
+        assert_eq!(pem.len(), 1);
+        let der = single_certificate(&pem[0].0).unwrap();
+        assert_eq!(der, pem[0]);
+    }
+
+    #[test]
+    fn rejects_several_certificates_when_one_is_expected() {
+        let mut chain = read("server_cert.pem");
+        chain.extend(read("ca_cert.pem"));
+        assert_eq!(certificates(&chain).unwrap().len(), 2);
+        assert!(single_certificate(&chain).is_err());
+    }
+
+    #[test]
+    fn reads_private_keys() {
+        let key = private_key(&read("server_key.pem"), None).unwrap();
+        assert_eq!(private_key(&key.0, None).unwrap(), key);
+        // plain-text keys are not accepted when a password is specified
+        assert!(private_key(&read("server_key.pem"), Some("password")).is_err());
+        assert!(private_key(&read("server_cert.pem"), None).is_err());
+    }
+
+    #[test]
+    fn decrypts_pkcs8_private_keys() {
+        let plain = private_key(&read("server_key.pem"), None).unwrap();
+        let encrypted = read("server_key_encrypted.pem");
+        assert_eq!(private_key(&encrypted, Some("password")).unwrap(), plain);
+        assert!(private_key(&encrypted, Some("wrong")).is_err());
+        assert!(private_key(&encrypted, None).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use tokio_rustls::rustls;
+
+use crate::tcp::tls::{credentials, MinTlsVersion, TlsError, TlsVersion};
+
+/// Cipher suites that may be negotiated
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CipherSuite {
+    /// TLS 1.3 suite with AES-256 in GCM mode and SHA-384
+    TLS13_AES_256_GCM_SHA384,
+    /// TLS 1.3 suite with AES-128 in GCM mode and SHA-256
+    TLS13_AES_128_GCM_SHA256,
+    /// TLS 1.3 suite with ChaCha20-Poly1305 and SHA-256
+    TLS13_CHACHA20_POLY1305_SHA256,
+    /// TLS 1.2 suite with ECDHE, ECDSA, AES-256 in GCM mode and SHA-384
+    TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+    /// TLS 1.2 suite with ECDHE, ECDSA, AES-128 in GCM mode and SHA-256
+    TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+    /// TLS 1.2 suite with ECDHE, ECDSA, ChaCha20-Poly1305 and SHA-256
+    TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
+    /// TLS 1.2 suite with ECDHE, RSA, AES-256 in GCM mode and SHA-384
+    TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+    /// TLS 1.2 suite with ECDHE, RSA, AES-128 in GCM mode and SHA-256
+    TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+    /// TLS 1.2 suite with ECDHE, RSA, ChaCha20-Poly1305 and SHA-256
+    TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+}
+
+impl CipherSuite {
+    /// All the supported cipher suites, in order of preference
+    pub const ALL: &'static [CipherSuite] = &[
+        Self::TLS13_AES_256_GCM_SHA384,
+        Self::TLS13_AES_128_GCM_SHA256,
+        Self::TLS13_CHACHA20_POLY1305_SHA256,
+        Self::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+        Self::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+        Self::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
+        Self::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+        Self::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+        Self::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+    ];
+
+    /// Protocol version the suite can be negotiated with
+    pub fn version(self) -> TlsVersion {
+        match self {
+            Self::TLS13_AES_256_GCM_SHA384
+            | Self::TLS13_AES_128_GCM_SHA256
+            | Self::TLS13_CHACHA20_POLY1305_SHA256 => TlsVersion::V1_3,
+            _ => TlsVersion::V1_2,
+        }
+    }
+
+    /// Whether a server signing with this type of key can negotiate the suite
+    fn supports_key(self, key: rustls::SignatureAlgorithm) -> bool {
+        use rustls::SignatureAlgorithm::*;
+
+        match self {
+            // TLS 1.3 suites do not constrain the type of key
+            Self::TLS13_AES_256_GCM_SHA384
+            | Self::TLS13_AES_128_GCM_SHA256
+            | Self::TLS13_CHACHA20_POLY1305_SHA256 => true,
+            Self::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384
+            | Self::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256
+            | Self::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256 => {
+                matches!(key, ECDSA | ED25519)
+            }
+            Self::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384
+            | Self::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256
+            | Self::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256 => key == RSA,
+        }
+    }
+
+    fn get(self) -> rustls::SupportedCipherSuite {
+        use rustls::cipher_suite::*;
+
+        match self {
+            Self::TLS13_AES_256_GCM_SHA384 => TLS13_AES_256_GCM_SHA384,
+            Self::TLS13_AES_128_GCM_SHA256 => TLS13_AES_128_GCM_SHA256,
+            Self::TLS13_CHACHA20_POLY1305_SHA256 => TLS13_CHACHA20_POLY1305_SHA256,
+            Self::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384 => {
+                TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384
+            }
+            Self::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256 => {
+                TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256
+            }
+            Self::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256 => {
+                TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256
+            }
+            Self::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384 => TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+            Self::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256 => TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+            Self::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256 => {
+                TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256
+            }
+        }
+    }
+}
+
+/// Key exchange groups that may be negotiated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyExchangeGroup {
+    /// Curve25519
+    X25519,
+    /// NIST P-256
+    Secp256r1,
+    /// NIST P-384
+    Secp384r1,
+}
+
+impl KeyExchangeGroup {
+    /// All the supported key exchange groups, in order of preference
+    pub const ALL: &'static [KeyExchangeGroup] = &[Self::X25519, Self::Secp256r1, Self::Secp384r1];
+
+    fn get(self) -> &'static rustls::SupportedKxGroup {
+        match self {
+            Self::X25519 => &rustls::kx_group::X25519,
+            Self::Secp256r1 => &rustls::kx_group::SECP256R1,
+            Self::Secp384r1 => &rustls::kx_group::SECP384R1,
+        }
+    }
+}
+
+impl TlsVersion {
+    fn get(self) -> &'static rustls::SupportedProtocolVersion {
+        match self {
+            Self::V1_2 => &rustls::version::TLS12,
+            Self::V1_3 => &rustls::version::TLS13,
+        }
+    }
+}
+
+/// Protocol versions, cipher suites and key exchange groups permitted by a TLS configuration
+///
+/// The lists are in order of preference. By default, every supported algorithm is permitted with
+/// the protocol versions allowed by the [`MinTlsVersion`] of the configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CryptoPolicy {
+    /// Permitted protocol versions
+    pub versions: Vec<TlsVersion>,
+    /// Permitted cipher suites, each of which must belong to a permitted version
+    pub cipher_suites: Vec<CipherSuite>,
+    /// Permitted key exchange groups
+    pub kx_groups: Vec<KeyExchangeGroup>,
+}
+
+impl CryptoPolicy {
+    /// Policy that only permits TLS 1.3 with all of its cipher suites
+    pub fn tls13_only() -> Self {
+        MinTlsVersion::V1_3.into()
+    }
+
+    /// FIPS-oriented subset of the supported algorithms, i.e. AES-GCM cipher suites with the
+    /// NIST curves
+    ///
+    /// This only restricts the negotiated algorithms, the underlying cryptographic library
+    /// is not a validated module.
+    pub fn fips() -> Self {
+        Self {
+            versions: vec![TlsVersion::V1_3, TlsVersion::V1_2],
+            cipher_suites: vec![
+                CipherSuite::TLS13_AES_256_GCM_SHA384,
+                CipherSuite::TLS13_AES_128_GCM_SHA256,
+                CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+                CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+                CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+                CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+            ],
+            kx_groups: vec![KeyExchangeGroup::Secp256r1, KeyExchangeGroup::Secp384r1],
+        }
+    }
+
+    /// Check that the lists are consistent
+    pub fn validate(&self) -> Result<(), TlsError> {
+        if self.versions.is_empty() {
+            return Err(TlsError::InvalidCryptoPolicy(
+                "no protocol version is permitted".to_string(),
+            ));
+        }
+        if self.kx_groups.is_empty() {
+            return Err(TlsError::InvalidCryptoPolicy(
+                "no key exchange group is permitted".to_string(),
+            ));
+        }
+        if let Some(suite) = self
+            .cipher_suites
+            .iter()
+            .find(|x| !self.versions.contains(&x.version()))
+        {
+            return Err(TlsError::InvalidCryptoPolicy(format!(
+                "cipher suite {suite:?} requires {:?} which is not permitted",
+                suite.version()
+            )));
+        }
+        if let Some(version) = self
+            .versions
+            .iter()
+            .find(|v| !self.cipher_suites.iter().any(|x| x.version() == **v))
+        {
+            return Err(TlsError::InvalidCryptoPolicy(format!(
+                "no cipher suite is permitted for {version:?}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check that every permitted version has a cipher suite that a server can negotiate
+    /// with its private key
+    pub(crate) fn check_server_key(&self, key: &rustls::PrivateKey) -> Result<(), TlsError> {
+        let algorithm = rustls::sign::any_supported_type(key)
+            .map_err(|_| credentials::bad_private_key("unsupported private key type".to_string()))?
+            .algorithm();
+
+        if let Some(version) = self.versions.iter().find(|v| {
+            !self
+                .cipher_suites
+                .iter()
+                .any(|x| x.version() == **v && x.supports_key(algorithm))
+        }) {
+            return Err(TlsError::InvalidCryptoPolicy(format!(
+                "no cipher suite permitted for {version:?} can be used with a {algorithm:?} private key"
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn client_builder(
+        &self,
+    ) -> Result<rustls::ConfigBuilder<rustls::ClientConfig, rustls::WantsVerifier>, TlsError> {
+        self.validate()?;
+        Ok(rustls::ClientConfig::builder()
+            .with_cipher_suites(&self.rustls_cipher_suites())
+            .with_kx_groups(&self.rustls_kx_groups())
+            .with_protocol_versions(&self.rustls_versions())?)
+    }
+
+    pub(crate) fn server_builder(
+        &self,
+    ) -> Result<rustls::ConfigBuilder<rustls::ServerConfig, rustls::WantsVerifier>, TlsError> {
+        self.validate()?;
+        Ok(rustls::ServerConfig::builder()
+            .with_cipher_suites(&self.rustls_cipher_suites())
+            .with_kx_groups(&self.rustls_kx_groups())
+            .with_protocol_versions(&self.rustls_versions())?)
+    }
+
+    fn rustls_cipher_suites(&self) -> Vec<rustls::SupportedCipherSuite> {
+        self.cipher_suites.iter().map(|x| x.get()).collect()
+    }
+
+    fn rustls_kx_groups(&self) -> Vec<&'static rustls::SupportedKxGroup> {
+        self.kx_groups.iter().map(|x| x.get()).collect()
+    }
+
+    fn rustls_versions(&self) -> Vec<&'static rustls::SupportedProtocolVersion> {
+        self.versions.iter().map(|x| x.get()).collect()
+    }
+}
+
+impl From<MinTlsVersion> for CryptoPolicy {
+    fn from(min: MinTlsVersion) -> Self {
+        let versions = match min {
+            MinTlsVersion::V1_2 => vec![TlsVersion::V1_3, TlsVersion::V1_2],
+            MinTlsVersion::V1_3 => vec![TlsVersion::V1_3],
+        };
+        let cipher_suites = CipherSuite::ALL
+            .iter()
+            .copied()
+            .filter(|x| versions.contains(&x.version()))
+            .collect();
+
+        Self {
+            versions,
+            cipher_suites,
+            kx_groups: KeyExchangeGroup::ALL.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_policies_are_valid() {
+        for policy in [
+            CryptoPolicy::from(MinTlsVersion::V1_2),
+            CryptoPolicy::tls13_only(),
+            CryptoPolicy::fips(),
+        ] {
+            policy.validate().unwrap();
+            policy.client_builder().unwrap();
+            policy.server_builder().unwrap();
+        }
+        assert_eq!(CryptoPolicy::tls13_only().versions, vec![TlsVersion::V1_3]);
+    }
+
+    #[test]
+    fn rejects_inconsistent_policies() {
+        let mut policy = CryptoPolicy::tls13_only();
+        policy
+            .cipher_suites
+            .push(CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256);
+        assert_eq!(
+            policy.validate().unwrap_err().to_string(),
+            "invalid crypto policy: cipher suite TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256 requires V1_2 which is not permitted"
+        );
+
+        let mut policy = CryptoPolicy::from(MinTlsVersion::V1_2);
+        policy
+            .cipher_suites
+            .retain(|x| x.version() == TlsVersion::V1_2);
+        assert_eq!(
+            policy.validate().unwrap_err().to_string(),
+            "invalid crypto policy: no cipher suite is permitted for V1_3"
+        );
+
+        let mut policy = CryptoPolicy::tls13_only();
+        policy.kx_groups.clear();
+        assert!(matches!(
+            policy.validate(),
+            Err(TlsError::InvalidCryptoPolicy(_))
+        ));
+    }
+
+    #[test]
+    fn checks_cipher_suites_against_the_server_key() {
+        let key = |dir: &str| {
+            let bytes = std::fs::read(format!("../certs/{dir}/server_key.pem")).unwrap();
+            credentials::private_key(&bytes, None).unwrap()
+        };
+        let rsa = key("ca_chain");
+        let ecdsa = key("revocation");
+
+        let ecdsa_only = CryptoPolicy {
+            versions: vec![TlsVersion::V1_2],
+            cipher_suites: vec![CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384],
+            kx_groups: KeyExchangeGroup::ALL.to_vec(),
+        };
+        ecdsa_only.check_server_key(&ecdsa).unwrap();
+        assert_eq!(
+            ecdsa_only.check_server_key(&rsa).unwrap_err().to_string(),
+            "invalid crypto policy: no cipher suite permitted for V1_2 can be used with a RSA private key"
+        );
+
+        for policy in [
+            CryptoPolicy::from(MinTlsVersion::V1_2),
+            CryptoPolicy::tls13_only(),
+            CryptoPolicy::fips(),
+        ] {
+            policy.check_server_key(&rsa).unwrap();
+            policy.check_server_key(&ecdsa).unwrap();
+        }
+    }
+}
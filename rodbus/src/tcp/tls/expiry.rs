@@ -0,0 +1,268 @@
+use std::time::{Duration, SystemTime};
+
+use tokio_rustls::rustls;
+
+use crate::tcp::tls::{credentials, TlsError, TlsSessionInfo};
+
+/// Default time before the expiry of a certificate at which warnings are emitted
+pub const DEFAULT_EXPIRY_WARNING: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Longest sleep of an [`ExpiryTimer`], so that changes of the system clock are noticed
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Validity period of a certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateValidity {
+    /// Common Name of the subject, if present
+    pub common_name: Option<String>,
+    /// Start of the validity period
+    pub not_before: SystemTime,
+    /// End of the validity period
+    pub not_after: SystemTime,
+}
+
+impl CertificateValidity {
+    pub(crate) fn parse(der: &[u8]) -> Result<Self, String> {
+        let cert =
+            rx509::x509::Certificate::parse(der).map_err(|err| format!("ASNError: {err}"))?;
+        Self::from_tbs(&cert.tbs_certificate.value)
+    }
+
+    pub(crate) fn from_tbs(tbs: &rx509::x509::TBSCertificate) -> Result<Self, String> {
+        let subject = tbs
+            .subject
+            .parse()
+            .map_err(|err| format!("unable to parse certificate subject: {err:?}"))?;
+
+        Ok(Self {
+            common_name: subject.common_name.map(|x| x.to_string()),
+            not_before: system_time(tbs.validity.not_before),
+            not_after: system_time(tbs.validity.not_after),
+        })
+    }
+
+    /// Time left until the certificate expires, `Duration::ZERO` if it already expired
+    pub fn remaining(&self, now: SystemTime) -> Duration {
+        self.not_after.duration_since(now).unwrap_or_default()
+    }
+}
+
+fn system_time(time: rx509::der::UtcTime) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(time.value)
+}
+
+/// Which end of the connection a certificate belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateOwner {
+    /// Certificate presented by this end of the connection
+    Local,
+    /// Certificate presented by the peer
+    Peer,
+}
+
+/// Certificate that expires within the warning period of the TLS configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpiryWarning {
+    /// Which end of the connection presents the certificate
+    pub owner: CertificateOwner,
+    /// Validity of the certificate
+    pub certificate: CertificateValidity,
+    /// Time left until the certificate expires
+    pub remaining: Duration,
+}
+
+impl ExpiryWarning {
+    fn log(&self) {
+        tracing::warn!(
+            "{:?} certificate {:?} expires in {} hours",
+            self.owner,
+            self.certificate.common_name,
+            self.remaining.as_secs() / 3600
+        );
+    }
+}
+
+/// Validity of the configured certificates, checked at every handshake
+#[derive(Debug, Clone)]
+pub(crate) struct ExpiryMonitor {
+    pub(crate) local: CertificateValidity,
+    pub(crate) peers: Vec<CertificateValidity>,
+    pub(crate) warn_before: Duration,
+}
+
+impl ExpiryMonitor {
+    pub(crate) fn new(
+        local: &rustls::Certificate,
+        peers: &[rustls::Certificate],
+    ) -> Result<Self, TlsError> {
+        let local =
+            CertificateValidity::parse(local.0.as_slice()).map_err(credentials::bad_local_cert)?;
+        let peers = peers
+            .iter()
+            .map(|x| CertificateValidity::parse(x.0.as_slice()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(credentials::bad_peer_cert)?;
+
+        Ok(Self {
+            local,
+            peers,
+            warn_before: DEFAULT_EXPIRY_WARNING,
+        })
+    }
+
+    /// Check the local certificate and the certificate presented by the peer during a handshake
+    ///
+    /// Returns the warnings along with a timer for the certificates that enter the warning
+    /// period later in the session.
+    pub(crate) fn check(&self, session: &TlsSessionInfo) -> (Vec<ExpiryWarning>, ExpiryTimer) {
+        let peer = session
+            .peer_certificate
+            .as_ref()
+            .map(|x| x.validity.clone());
+        let mut timer = self.timer(peer);
+        let warnings = timer.due(SystemTime::now());
+        for warning in warnings.iter() {
+            warning.log();
+        }
+        (warnings, timer)
+    }
+
+    fn timer(&self, peer: Option<CertificateValidity>) -> ExpiryTimer {
+        let mut pending = vec![(CertificateOwner::Local, self.local.clone())];
+        pending.extend(peer.map(|x| (CertificateOwner::Peer, x)));
+        ExpiryTimer {
+            pending,
+            warn_before: self.warn_before,
+        }
+    }
+}
+
+/// Certificates of an established session that have not entered the warning period yet
+#[derive(Debug, Default)]
+pub(crate) struct ExpiryTimer {
+    pending: Vec<(CertificateOwner, CertificateValidity)>,
+    warn_before: Duration,
+}
+
+impl ExpiryTimer {
+    /// Wait until the next certificate enters the warning period, never completes once every
+    /// certificate has been reported
+    pub(crate) async fn next(&mut self) -> Vec<ExpiryWarning> {
+        loop {
+            let now = SystemTime::now();
+            let warnings = self.due(now);
+            if !warnings.is_empty() {
+                for warning in warnings.iter() {
+                    warning.log();
+                }
+                return warnings;
+            }
+            match self.delay(now) {
+                None => return std::future::pending().await,
+                Some(delay) => tokio::time::sleep(delay).await,
+            }
+        }
+    }
+
+    /// Remove and return the certificates within the warning period
+    fn due(&mut self, now: SystemTime) -> Vec<ExpiryWarning> {
+        let mut warnings = Vec::new();
+        self.pending.retain(|(owner, certificate)| {
+            let remaining = certificate.remaining(now);
+            if remaining > self.warn_before {
+                return true;
+            }
+            warnings.push(ExpiryWarning {
+                owner: *owner,
+                certificate: certificate.clone(),
+                remaining,
+            });
+            false
+        });
+        warnings
+    }
+
+    /// Time until the next certificate enters the warning period, at most one check interval
+    fn delay(&self, now: SystemTime) -> Option<Duration> {
+        self.pending
+            .iter()
+            .map(|(_, x)| x.remaining(now).saturating_sub(self.warn_before))
+            .min()
+            .map(|x| x.min(EXPIRY_CHECK_INTERVAL))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validity(not_after: SystemTime) -> CertificateValidity {
+        CertificateValidity {
+            common_name: None,
+            not_before: SystemTime::UNIX_EPOCH,
+            not_after,
+        }
+    }
+
+    #[test]
+    fn warns_about_certificates_expiring_within_the_period() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let day = Duration::from_secs(24 * 60 * 60);
+        let monitor = ExpiryMonitor {
+            local: validity(now + 2 * day),
+            peers: Vec::new(),
+            warn_before: 3 * day,
+        };
+
+        // the local certificate is always checked
+        let warnings = monitor.timer(Some(validity(now + 10 * day))).due(now);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].owner, CertificateOwner::Local);
+        assert_eq!(warnings[0].remaining, 2 * day);
+
+        // expired certificates have no time left
+        let warnings = monitor.timer(Some(validity(now - day))).due(now);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[1].owner, CertificateOwner::Peer);
+        assert_eq!(warnings[1].remaining, Duration::ZERO);
+    }
+
+    #[test]
+    fn warns_once_when_a_certificate_enters_the_period_during_a_session() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let hour = Duration::from_secs(60 * 60);
+        let minute = Duration::from_secs(60);
+        let monitor = ExpiryMonitor {
+            local: validity(now + 100 * hour),
+            peers: Vec::new(),
+            warn_before: 10 * hour,
+        };
+
+        let mut timer = monitor.timer(Some(validity(now + 10 * hour + 30 * minute)));
+        assert!(timer.due(now).is_empty());
+        assert_eq!(timer.delay(now), Some(30 * minute));
+
+        let later = now + hour;
+        let warnings = timer.due(later);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].owner, CertificateOwner::Peer);
+        assert_eq!(warnings[0].remaining, 9 * hour + 30 * minute);
+
+        // each certificate is only reported once, and the timer wakes up at least once per interval
+        assert!(timer.due(later).is_empty());
+        assert_eq!(timer.delay(later), Some(EXPIRY_CHECK_INTERVAL));
+        assert_eq!(timer.due(now + 90 * hour).len(), 1);
+        assert_eq!(timer.delay(now + 90 * hour), None);
+    }
+
+    #[test]
+    fn parses_validity_of_configured_certificates() {
+        let pem = std::fs::read("../certs/self_signed/entity1_cert.pem").unwrap();
+        let cert = rustls::Certificate(pem::parse(pem).unwrap().into_contents());
+
+        let monitor = ExpiryMonitor::new(&cert, std::slice::from_ref(&cert)).unwrap();
+        assert_eq!(monitor.local.common_name.as_deref(), Some("DO NOT USE"));
+        assert!(monitor.local.not_before < monitor.local.not_after);
+        assert_eq!(monitor.peers, vec![monitor.local.clone()]);
+    }
+}
@@ -1,9 +1,24 @@
 pub(crate) mod client;
+mod credentials;
+mod crypto;
+mod expiry;
+mod revocation;
+mod roles;
 pub(crate) mod server;
+mod session;
+mod verifiers;
+mod watcher;
 
 pub(crate) use client::*;
+pub use crypto::*;
+pub use expiry::*;
+pub use revocation::*;
+pub use roles::*;
 pub(crate) use server::*;
+pub use session::*;
+use tokio_rustls::rustls;
 use tokio_rustls::rustls::client::InvalidDnsNameError;
+pub use watcher::*;
 
 /// Determines how the certificate(s) presented by the peer are validated
 ///
@@ -28,6 +43,18 @@ pub enum CertificateMode {
     SelfSigned,
 }
 
+/// Determines what happens to established sessions when the TLS configuration is replaced
+///
+/// New handshakes always use the new configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TlsReloadPolicy {
+    /// Established sessions keep running with the configuration they were negotiated with
+    #[default]
+    KeepSessions,
+    /// Established sessions are closed so that they are negotiated again with the new configuration
+    CloseSessions,
+}
+
 /// TLS-related errors
 #[derive(Debug)]
 pub enum TlsError {
@@ -41,28 +68,36 @@ pub enum TlsError {
     InvalidDnsName,
     /// Error building TLS configuration
     BadConfig(String),
+    /// Invalid certificate revocation list
+    InvalidRevocationList(String),
+    /// Inconsistent protocol versions, cipher suites or key exchange groups
+    InvalidCryptoPolicy(String),
 }
 
 impl std::fmt::Display for TlsError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::InvalidPeerCertificate(err) => {
-                write!(f, "invalid peer certificate file: {err}")
+                write!(f, "invalid peer certificate: {err}")
             }
             Self::InvalidLocalCertificate(err) => {
-                write!(f, "invalid local certificate file: {err}")
+                write!(f, "invalid local certificate: {err}")
             }
-            Self::InvalidPrivateKey(err) => write!(f, "invalid private key file: {err}"),
+            Self::InvalidPrivateKey(err) => write!(f, "invalid private key: {err}"),
             Self::InvalidDnsName => write!(f, "invalid DNS name"),
             Self::BadConfig(err) => write!(f, "bad config: {err}"),
+            Self::InvalidRevocationList(err) => {
+                write!(f, "invalid certificate revocation list: {err}")
+            }
+            Self::InvalidCryptoPolicy(err) => write!(f, "invalid crypto policy: {err}"),
         }
     }
 }
 
 impl std::error::Error for TlsError {}
 
-impl From<sfio_rustls_config::Error> for TlsError {
-    fn from(err: sfio_rustls_config::Error) -> Self {
+impl From<rustls::Error> for TlsError {
+    fn from(err: rustls::Error) -> Self {
         Self::BadConfig(err.to_string())
     }
 }
@@ -76,15 +111,6 @@ pub enum MinTlsVersion {
     V1_3,
 }
 
-impl From<MinTlsVersion> for sfio_rustls_config::MinProtocolVersion {
-    fn from(value: MinTlsVersion) -> Self {
-        match value {
-            MinTlsVersion::V1_2 => sfio_rustls_config::MinProtocolVersion::V1_2,
-            MinTlsVersion::V1_3 => sfio_rustls_config::MinProtocolVersion::V1_3,
-        }
-    }
-}
-
 impl From<InvalidDnsNameError> for TlsError {
     fn from(_: InvalidDnsNameError) -> Self {
         Self::InvalidDnsName
@@ -0,0 +1,219 @@
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use tokio_rustls::rustls;
+use webpki::CertRevocationList;
+
+use crate::tcp::tls::verifiers::SUPPORTED_SIG_ALGS;
+use crate::tcp::tls::{credentials, TlsError};
+
+/// Certificate revocation lists (CRLs) checked against the certificates presented by the peer
+///
+/// The lists are shared by every configuration they are added to so that
+/// [`RevocationLists::reload`] applies to all subsequent handshakes. Established sessions
+/// are not checked again.
+///
+/// Each certificate of the chain presented by the peer is rejected if it is listed in a CRL
+/// from its issuer, which fails the handshake with a `certificate_revoked` alert. The
+/// `nextUpdate` field of the CRLs is not checked.
+#[derive(Clone, Default)]
+pub struct RevocationLists {
+    inner: Arc<RwLock<Vec<webpki::OwnedCertRevocationList>>>,
+}
+
+impl RevocationLists {
+    /// Load CRLs from PEM or DER encoded files
+    ///
+    /// Each CRL must be signed by one of the CA certificates in the PEM file at `ca_cert_path`,
+    /// usually the file containing the trust anchors of the configuration.
+    pub fn load<P>(ca_cert_path: &Path, crl_paths: &[P]) -> Result<Self, TlsError>
+    where
+        P: AsRef<Path>,
+    {
+        let lists = read_lists(ca_cert_path, crl_paths)?;
+        Ok(Self {
+            inner: Arc::new(RwLock::new(lists)),
+        })
+    }
+
+    /// Replace the CRLs with the ones read from the files, see [`RevocationLists::load`]
+    ///
+    /// The current CRLs are retained if any of the files is invalid.
+    pub fn reload<P>(&self, ca_cert_path: &Path, crl_paths: &[P]) -> Result<(), TlsError>
+    where
+        P: AsRef<Path>,
+    {
+        let lists = read_lists(ca_cert_path, crl_paths)?;
+        tracing::info!("loaded {} certificate revocation list(s)", lists.len());
+        *self.inner.write().unwrap() = lists;
+        Ok(())
+    }
+
+    /// Returns an error if the end-entity certificate or one of the intermediates is revoked
+    pub(crate) fn check(
+        &self,
+        end_entity: &rustls::Certificate,
+        intermediates: &[rustls::Certificate],
+    ) -> Result<(), rustls::Error> {
+        let lists = self.inner.read().unwrap();
+        if lists.is_empty() {
+            return Ok(());
+        }
+
+        for cert in std::iter::once(end_entity).chain(intermediates) {
+            let parsed = rx509::x509::Certificate::parse(cert.0.as_slice())
+                .map_err(|_| rustls::CertificateError::BadEncoding)?;
+            let tbs = &parsed.tbs_certificate.value;
+            for list in lists.iter().filter(|x| x.issuer() == tbs.issuer.inner) {
+                let revoked = list.find_serial(tbs.serial_number.bytes).map_err(|err| {
+                    rustls::Error::General(format!("invalid revocation list: {err:?}"))
+                })?;
+                if revoked.is_some() {
+                    tracing::warn!(
+                        "certificate with serial number {} is revoked",
+                        tbs.serial_number
+                    );
+                    return Err(rustls::CertificateError::Revoked.into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn read_lists<P>(
+    ca_cert_path: &Path,
+    crl_paths: &[P],
+) -> Result<Vec<webpki::OwnedCertRevocationList>, TlsError>
+where
+    P: AsRef<Path>,
+{
+    let ca_certs = read_ca_certificates(ca_cert_path)?;
+    let anchors = ca_certs
+        .iter()
+        .map(|x| webpki::TrustAnchor::try_from_cert_der(&x.0))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| TlsError::InvalidRevocationList(format!("bad CA certificate: {err:?}")))?;
+
+    let mut lists = Vec::new();
+    for path in crl_paths {
+        let path = path.as_ref();
+        for der in read_crls(path)? {
+            let list = webpki::BorrowedCertRevocationList::from_der(&der)
+                .and_then(|x| x.to_owned())
+                .map_err(|err| {
+                    TlsError::InvalidRevocationList(format!("{}: {err:?}", path.display()))
+                })?;
+
+            let signed = anchors
+                .iter()
+                .filter(|x| x.subject == list.issuer())
+                .any(|x| list.verify_signature(SUPPORTED_SIG_ALGS, x.spki).is_ok());
+            if !signed {
+                return Err(TlsError::InvalidRevocationList(format!(
+                    "{} is not signed by a configured CA",
+                    path.display()
+                )));
+            }
+
+            lists.push(list);
+        }
+    }
+
+    Ok(lists)
+}
+
+fn read_ca_certificates(path: &Path) -> Result<Vec<rustls::Certificate>, TlsError> {
+    let bytes = std::fs::read(path)
+        .map_err(|err| TlsError::InvalidRevocationList(format!("{}: {err}", path.display())))?;
+    credentials::certificates(&bytes)
+        .map_err(|err| TlsError::InvalidRevocationList(format!("bad CA certificate: {err}")))
+}
+
+/// Read the CRLs of a file which is either PEM encoded or a single DER encoded CRL
+fn read_crls(path: &Path) -> Result<Vec<Vec<u8>>, TlsError> {
+    let bytes = std::fs::read(path)
+        .map_err(|err| TlsError::InvalidRevocationList(format!("{}: {err}", path.display())))?;
+
+    if !bytes.starts_with(b"-----BEGIN") {
+        return Ok(vec![bytes]);
+    }
+
+    let crls: Vec<Vec<u8>> = pem::parse_many(bytes)
+        .map_err(|err| TlsError::InvalidRevocationList(format!("{}: {err}", path.display())))?
+        .into_iter()
+        .filter(|x| x.tag() == "X509 CRL")
+        .map(|x| x.into_contents())
+        .collect();
+
+    if crls.is_empty() {
+        return Err(TlsError::InvalidRevocationList(format!(
+            "no CRL in {}",
+            path.display()
+        )));
+    }
+
+    Ok(crls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIR: &str = "../certs/revocation";
+
+    fn cert(name: &str) -> rustls::Certificate {
+        let bytes = std::fs::read(Path::new(DIR).join(name)).unwrap();
+        rustls::Certificate(pem::parse(bytes).unwrap().into_contents())
+    }
+
+    fn lists() -> RevocationLists {
+        let dir = Path::new(DIR);
+        RevocationLists::load(&dir.join("ca_cert.pem"), &[dir.join("crl.pem")]).unwrap()
+    }
+
+    #[test]
+    fn rejects_revoked_certificates_only() {
+        let lists = lists();
+        assert_eq!(
+            lists.check(&cert("client_cert.pem"), &[]),
+            Err(rustls::CertificateError::Revoked.into())
+        );
+        assert_eq!(lists.check(&cert("server_cert.pem"), &[]), Ok(()));
+        assert!(lists.check(&cert("ca_cert.pem"), &[]).is_ok());
+        // intermediates are checked as well
+        assert!(lists
+            .check(&cert("server_cert.pem"), &[cert("client_cert.pem")])
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_lists_from_other_authorities() {
+        let dir = Path::new(DIR);
+        let other_ca = Path::new("../certs/ca_chain/ca_cert.pem");
+        assert!(RevocationLists::load(other_ca, &[dir.join("crl.pem")]).is_err());
+
+        let missing = dir.join("missing_cert.pem");
+        assert!(matches!(
+            RevocationLists::load(&missing, &[dir.join("crl.pem")]),
+            Err(TlsError::InvalidRevocationList(_))
+        ));
+    }
+
+    #[test]
+    fn reload_replaces_lists() {
+        let lists = RevocationLists::default();
+        assert!(lists.check(&cert("client_cert.pem"), &[]).is_ok());
+
+        let dir = Path::new(DIR);
+        let paths: &[&Path] = &[];
+        lists
+            .reload(&dir.join("ca_cert.pem"), &[dir.join("crl.der")])
+            .unwrap();
+        assert!(lists.check(&cert("client_cert.pem"), &[]).is_err());
+
+        lists.reload(&dir.join("ca_cert.pem"), paths).unwrap();
+        assert!(lists.check(&cert("client_cert.pem"), &[]).is_ok());
+    }
+}
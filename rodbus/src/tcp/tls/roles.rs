@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+/// Fields of the client certificate made available to a [`RoleExtractor`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateInfo<'a> {
+    /// DER encoded certificate, for extractors that need to parse other fields
+    pub der: &'a [u8],
+    /// Common Name of the subject, if present
+    pub common_name: Option<&'a str>,
+    /// Organizational Unit of the subject, if present
+    pub organizational_unit: Option<&'a str>,
+    /// DNS names in the Subject Alternative Name extension
+    pub dns_names: Vec<&'a str>,
+    /// Roles carried by the Modbus RoleOfDevice extensions
+    pub modbus_roles: Vec<&'a str>,
+}
+
+/// Determines the roles of a client from its certificate in Modbus Security mode
+///
+/// The roles are handed to the [`crate::server::AuthorizationHandler`] of the server. A connection
+/// is rejected if the extractor fails or returns no role.
+pub trait RoleExtractor: Send + Sync + 'static {
+    /// Moves a role extractor implementation into an `Arc` suitable for passing to the server
+    fn wrap(self) -> Arc<dyn RoleExtractor>
+    where
+        Self: Sized,
+    {
+        Arc::new(self)
+    }
+
+    /// Extract the roles of the client, or return an error describing why the certificate is rejected
+    fn extract(&self, cert: &CertificateInfo) -> Result<Vec<String>, String>;
+}
+
+/// Default [`RoleExtractor`] defined by the Modbus Security specification
+///
+/// The roles are read from the RoleOfDevice extensions, at least one of which must be present.
+#[derive(Debug, Clone, Copy)]
+pub struct ModbusRoleExtractor;
+
+impl RoleExtractor for ModbusRoleExtractor {
+    fn extract(&self, cert: &CertificateInfo) -> Result<Vec<String>, String> {
+        if cert.modbus_roles.is_empty() {
+            return Err("certificate doesn't have Modbus extension".to_string());
+        }
+
+        Ok(cert.modbus_roles.iter().map(|x| x.to_string()).collect())
+    }
+}
+
+pub(crate) fn extract_roles(
+    extractor: &dyn RoleExtractor,
+    der: &[u8],
+) -> Result<Vec<String>, String> {
+    let cert = rx509::x509::Certificate::parse(der).map_err(|err| format!("ASNError: {err}"))?;
+    let subject = cert
+        .tbs_certificate
+        .value
+        .subject
+        .parse()
+        .map_err(|err| format!("unable to parse certificate subject: {err:?}"))?;
+
+    let mut info = CertificateInfo {
+        der,
+        common_name: subject.common_name,
+        organizational_unit: subject.organizational_unit_name,
+        dns_names: Vec::new(),
+        modbus_roles: Vec::new(),
+    };
+
+    if let Some(extensions) = &cert.tbs_certificate.value.extensions {
+        let extensions = extensions
+            .parse()
+            .map_err(|err| format!("unable to parse cert extensions with rasn: {err:?}"))?;
+
+        for ext in extensions {
+            match ext.content {
+                rx509::x509::ext::SpecificExtension::ModbusRole(role) => {
+                    info.modbus_roles.push(role.role)
+                }
+                rx509::x509::ext::SpecificExtension::SubjectAlternativeName(san) => {
+                    for name in san.names {
+                        if let rx509::x509::ext::GeneralName::DnsName(x) = name {
+                            info.dns_names.push(x);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let roles = extractor.extract(&info)?;
+    if roles.is_empty() {
+        return Err("no role extracted from the client certificate".to_string());
+    }
+
+    Ok(roles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct CommonNameExtractor;
+
+    impl RoleExtractor for CommonNameExtractor {
+        fn extract(&self, cert: &CertificateInfo) -> Result<Vec<String>, String> {
+            Ok(cert.common_name.iter().map(|x| x.to_string()).collect())
+        }
+    }
+
+    fn client_cert() -> Vec<u8> {
+        let pem = std::fs::read("../certs/revocation/client_cert.pem").unwrap();
+        pem::parse(pem).unwrap().into_contents()
+    }
+
+    #[test]
+    fn modbus_extractor_requires_the_role_extension() {
+        let err = extract_roles(&ModbusRoleExtractor, &client_cert()).unwrap_err();
+        assert_eq!(err, "certificate doesn't have Modbus extension");
+    }
+
+    #[test]
+    fn custom_extractor_maps_certificate_fields_to_roles() {
+        let roles = extract_roles(&CommonNameExtractor, &client_cert()).unwrap();
+        assert_eq!(roles, vec!["client.test.com".to_string()]);
+    }
+}
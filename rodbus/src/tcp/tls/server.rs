@@ -1,7 +1,6 @@
-use sfio_rustls_config::NameVerifier;
-
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use tokio::net::TcpStream;
 use tokio_rustls::rustls;
@@ -9,12 +8,22 @@ use tokio_rustls::rustls;
 use crate::common::phys::PhysLayer;
 use crate::server::task::AuthorizationType;
 use crate::server::AuthorizationHandler;
-use crate::tcp::tls::{CertificateMode, MinTlsVersion, TlsError};
+use crate::tcp::tls::credentials::{self, CredentialFiles};
+use crate::tcp::tls::roles::extract_roles;
+use crate::tcp::tls::verifiers::{RevocationVerifier, SelfSignedVerifier};
+use crate::tcp::tls::{
+    CertificateMode, CertificateValidity, CryptoPolicy, ExpiryMonitor, ExpiryTimer, ExpiryWarning,
+    MinTlsVersion, ModbusRoleExtractor, RevocationLists, RoleExtractor, TlsError, TlsSessionInfo,
+};
 
 /// TLS configuration
 #[derive(Clone)]
 pub struct TlsServerConfig {
     inner: Arc<rustls::ServerConfig>,
+    credentials: Credentials,
+    policy: CryptoPolicy,
+    roles: Arc<dyn RoleExtractor>,
+    expiry: ExpiryMonitor,
 }
 
 impl TlsServerConfig {
@@ -27,42 +36,156 @@ impl TlsServerConfig {
         min_tls_version: MinTlsVersion,
         certificate_mode: CertificateMode,
     ) -> Result<Self, TlsError> {
-        let config = match certificate_mode {
-            CertificateMode::SelfSigned => sfio_rustls_config::server::self_signed(
-                min_tls_version.into(),
-                peer_cert_path,
-                local_cert_path,
-                private_key_path,
-                password,
-            )?,
-            CertificateMode::AuthorityBased => sfio_rustls_config::server::authority(
-                min_tls_version.into(),
-                NameVerifier::any(),
-                peer_cert_path,
-                local_cert_path,
-                private_key_path,
-                password,
-            )?,
+        let files = CredentialFiles::read(peer_cert_path, local_cert_path, private_key_path)?;
+        Self::from_bytes(
+            &files.peer_certs,
+            &files.local_certs,
+            &files.private_key,
+            password,
+            min_tls_version,
+            certificate_mode,
+        )
+    }
+
+    /// Create a TLS server config from credentials held in memory
+    ///
+    /// Certificates are either PEM encoded or a single DER encoded certificate. The private key is
+    /// PEM or DER encoded, and must be a PKCS#8 encrypted key if a password is specified.
+    pub fn from_bytes(
+        peer_certs: &[u8],
+        local_certs: &[u8],
+        private_key: &[u8],
+        password: Option<&str>,
+        min_tls_version: MinTlsVersion,
+        certificate_mode: CertificateMode,
+    ) -> Result<Self, TlsError> {
+        let (verifier, local_certs, expiry): (Arc<dyn rustls::server::ClientCertVerifier>, _, _) =
+            match certificate_mode {
+                CertificateMode::SelfSigned => {
+                    let peer_cert = credentials::single_certificate(peer_certs)
+                        .map_err(credentials::bad_peer_cert)?;
+                    let local_cert = credentials::single_certificate(local_certs)
+                        .map_err(credentials::bad_local_cert)?;
+                    let expiry = ExpiryMonitor::new(&local_cert, std::slice::from_ref(&peer_cert))?;
+                    let verifier =
+                        SelfSignedVerifier::new(peer_cert).map_err(credentials::bad_peer_cert)?;
+                    (Arc::new(verifier), vec![local_cert], expiry)
+                }
+                CertificateMode::AuthorityBased => {
+                    let peer_certs = credentials::certificates(peer_certs)
+                        .map_err(credentials::bad_peer_cert)?;
+                    let mut roots = rustls::RootCertStore::empty();
+                    for cert in peer_certs.iter() {
+                        roots
+                            .add(cert)
+                            .map_err(|err| credentials::bad_peer_cert(err.to_string()))?;
+                    }
+                    let local_certs = credentials::certificates(local_certs)
+                        .map_err(credentials::bad_local_cert)?;
+                    let expiry = ExpiryMonitor::new(&local_certs[0], &peer_certs)?;
+                    let verifier = rustls::server::AllowAnyAuthenticatedClient::new(roots).boxed();
+                    (verifier, local_certs, expiry)
+                }
+            };
+        let private_key = credentials::private_key(private_key, password)
+            .map_err(credentials::bad_private_key)?;
+
+        let credentials = Credentials {
+            verifier,
+            revocation: None,
+            local_certs,
+            private_key,
         };
+        let policy = CryptoPolicy::from(min_tls_version);
+        let config = credentials.build(&policy)?;
 
         Ok(TlsServerConfig {
             inner: Arc::new(config),
+            credentials,
+            policy,
+            roles: ModbusRoleExtractor.wrap(),
+            expiry,
         })
     }
 
+    /// Reject clients presenting a certificate listed in one of the certificate revocation lists
+    ///
+    /// The handshake fails with a `certificate_revoked` alert, before any request is processed.
+    /// Session resumption is disabled so that the lists are checked on every connection.
+    pub fn with_revocation_lists(mut self, lists: RevocationLists) -> Result<Self, TlsError> {
+        self.credentials.revocation = Some(lists);
+        self.inner = Arc::new(self.credentials.build(&self.policy)?);
+        Ok(self)
+    }
+
+    /// Determine the roles of the clients with a custom extractor in Modbus Security mode
+    ///
+    /// By default, the roles are read from the RoleOfDevice extensions of the client certificate.
+    pub fn with_role_extractor(mut self, extractor: Arc<dyn RoleExtractor>) -> Self {
+        self.roles = extractor;
+        self
+    }
+
+    /// Restrict the protocol versions, cipher suites and key exchange groups that may be negotiated
+    ///
+    /// The policy replaces the protocol versions allowed by the [`MinTlsVersion`] of the
+    /// configuration. An error is returned if the lists are inconsistent.
+    pub fn with_crypto_policy(mut self, policy: CryptoPolicy) -> Result<Self, TlsError> {
+        self.inner = Arc::new(self.credentials.build(&policy)?);
+        self.policy = policy;
+        Ok(self)
+    }
+
+    /// Protocol versions, cipher suites and key exchange groups that may be negotiated
+    pub fn crypto_policy(&self) -> &CryptoPolicy {
+        &self.policy
+    }
+
+    /// Validity of the certificate presented to the clients
+    pub fn local_certificate(&self) -> &CertificateValidity {
+        &self.expiry.local
+    }
+
+    /// Validity of the certificates the client certificates are checked against, i.e. the trust
+    /// anchors or the expected self-signed certificate
+    pub fn peer_certificates(&self) -> &[CertificateValidity] {
+        self.expiry.peers.as_slice()
+    }
+
+    /// Warn when the local certificate or the certificate presented by a client expires within
+    /// `warn_before`. Defaults to [`crate::server::DEFAULT_EXPIRY_WARNING`].
+    ///
+    /// The certificates are checked at every handshake, and once more when they enter the warning
+    /// period while the session is established. Warnings are logged and reported to the session
+    /// listener of the server.
+    pub fn with_expiry_warning(mut self, warn_before: Duration) -> Self {
+        self.expiry.warn_before = warn_before;
+        self
+    }
+
+    pub(crate) fn check_expiry(
+        &self,
+        session: &TlsSessionInfo,
+    ) -> (Vec<ExpiryWarning>, ExpiryTimer) {
+        self.expiry.check(session)
+    }
+
     pub(crate) async fn handle_connection(
         &mut self,
         socket: TcpStream,
         auth_handler: Option<Arc<dyn AuthorizationHandler>>,
-    ) -> Result<(PhysLayer, AuthorizationType), String> {
+    ) -> Result<(PhysLayer, AuthorizationType, TlsSessionInfo), String> {
         let connector = tokio_rustls::TlsAcceptor::from(self.inner.clone());
         match connector.accept(socket).await {
             Err(err) => Err(format!("failed to establish TLS session: {err}")),
             Ok(stream) => {
+                let info = TlsSessionInfo::new(stream.get_ref().1)?;
+                info.log();
+
                 let auth_type = match auth_handler {
                     // bare TLS mode without authz
                     None => AuthorizationType::None,
-                    // full secure modbus requires the client certificate contain a role
+                    // full secure modbus requires roles to be extracted from the client certificate
                     Some(handler) => {
                         // get the peer cert data
                         let peer_cert = stream
@@ -74,51 +197,51 @@ impl TlsServerConfig {
                             .0
                             .as_slice();
 
-                        let parsed = rx509::x509::Certificate::parse(peer_cert)
-                            .map_err(|err| format!("ASNError: {err}"))?;
-                        let role = extract_modbus_role(&parsed)?;
+                        let roles = extract_roles(self.roles.as_ref(), peer_cert)?;
 
-                        tracing::info!("client role: {}", role);
-                        AuthorizationType::Handler(handler, role)
+                        tracing::info!("client roles: {:?}", roles);
+                        AuthorizationType::Handler(handler, roles)
                     }
                 };
 
                 let layer = PhysLayer::new_tls(tokio_rustls::TlsStream::from(stream));
 
-                Ok((layer, auth_type))
+                Ok((layer, auth_type, info))
             }
         }
     }
 }
 
-fn extract_modbus_role(cert: &rx509::x509::Certificate) -> Result<String, String> {
-    // Parse the extensions
-    let extensions = cert
-        .tbs_certificate
-        .value
-        .extensions
-        .as_ref()
-        .ok_or_else(|| "certificate doesn't contain Modbus role extension".to_string())?;
-
-    let extensions = extensions
-        .parse()
-        .map_err(|err| format!("unable to parse cert extensions with rasn: {err:?}"))?;
-
-    // Extract the ModbusRole extensions
-    let mut it = extensions.into_iter().filter_map(|ext| match ext.content {
-        rx509::x509::ext::SpecificExtension::ModbusRole(role) => Some(role.role),
-        _ => None,
-    });
-
-    // Extract the first ModbusRole extension
-    let role = it
-        .next()
-        .ok_or_else(|| "certificate doesn't have Modbus extension".to_string())?;
-
-    // Check that there is only one role extension
-    if it.next().is_some() {
-        return Err("certificate has more than one Modbus extension".to_string());
-    }
+/// Inputs of the rustls configuration, kept so that it can be rebuilt with another crypto policy
+#[derive(Clone)]
+struct Credentials {
+    verifier: Arc<dyn rustls::server::ClientCertVerifier>,
+    revocation: Option<RevocationLists>,
+    local_certs: Vec<rustls::Certificate>,
+    private_key: rustls::PrivateKey,
+}
 
-    Ok(role.to_string())
+impl Credentials {
+    fn build(&self, policy: &CryptoPolicy) -> Result<rustls::ServerConfig, TlsError> {
+        policy.check_server_key(&self.private_key)?;
+        let verifier: Arc<dyn rustls::server::ClientCertVerifier> = match &self.revocation {
+            None => self.verifier.clone(),
+            Some(lists) => Arc::new(RevocationVerifier::new(
+                self.verifier.clone(),
+                lists.clone(),
+            )),
+        };
+        let mut config = policy
+            .server_builder()?
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(self.local_certs.clone(), self.private_key.clone())?;
+
+        // resumed sessions skip the verifier, so every client must present its certificate again
+        if self.revocation.is_some() {
+            config.session_storage = Arc::new(rustls::server::NoServerSessionStorage {});
+            config.send_tls13_tickets = 0;
+        }
+
+        Ok(config)
+    }
 }
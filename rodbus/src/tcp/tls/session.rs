@@ -0,0 +1,113 @@
+use tokio_rustls::rustls;
+
+use crate::tcp::tls::CertificateValidity;
+
+/// Version of the TLS protocol negotiated for a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    /// TLS 1.2
+    V1_2,
+    /// TLS 1.3
+    V1_3,
+}
+
+/// Fields of the certificate presented by the peer during the handshake
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerCertificateInfo {
+    /// DER encoded certificate, for applications that need to parse other fields
+    pub der: Vec<u8>,
+    /// Serial number of the certificate, as big-endian bytes
+    pub serial_number: Vec<u8>,
+    /// Common Name and validity period of the certificate
+    pub validity: CertificateValidity,
+}
+
+impl PeerCertificateInfo {
+    pub(crate) fn parse(der: &[u8]) -> Result<Self, String> {
+        let cert =
+            rx509::x509::Certificate::parse(der).map_err(|err| format!("ASNError: {err}"))?;
+        let tbs = &cert.tbs_certificate.value;
+
+        Ok(Self {
+            der: der.to_vec(),
+            serial_number: tbs.serial_number.bytes.to_vec(),
+            validity: CertificateValidity::from_tbs(tbs)?,
+        })
+    }
+}
+
+/// Parameters of an established TLS session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsSessionInfo {
+    /// Negotiated protocol version
+    pub version: TlsVersion,
+    /// Negotiated cipher suite, e.g. `TLS13_AES_256_GCM_SHA384`
+    pub cipher_suite: String,
+    /// End-entity certificate presented by the peer, `None` if it could not be parsed
+    pub peer_certificate: Option<PeerCertificateInfo>,
+}
+
+impl TlsSessionInfo {
+    /// Collect the parameters of a session once its handshake is complete
+    pub(crate) fn new(conn: &rustls::CommonState) -> Result<Self, String> {
+        let version = match conn.protocol_version() {
+            Some(rustls::ProtocolVersion::TLSv1_2) => TlsVersion::V1_2,
+            Some(rustls::ProtocolVersion::TLSv1_3) => TlsVersion::V1_3,
+            x => return Err(format!("unsupported TLS protocol version: {x:?}")),
+        };
+
+        let cipher_suite = conn
+            .negotiated_cipher_suite()
+            .map(|x| format!("{:?}", x.suite()))
+            .ok_or_else(|| "no cipher suite negotiated".to_string())?;
+
+        let peer_certificate = match conn.peer_certificates().and_then(|x| x.first()) {
+            None => None,
+            Some(cert) => match PeerCertificateInfo::parse(cert.0.as_slice()) {
+                Ok(info) => Some(info),
+                Err(err) => {
+                    tracing::warn!("unable to parse peer certificate: {}", err);
+                    None
+                }
+            },
+        };
+
+        Ok(Self {
+            version,
+            cipher_suite,
+            peer_certificate,
+        })
+    }
+
+    pub(crate) fn log(&self) {
+        match &self.peer_certificate {
+            Some(cert) => tracing::info!(
+                "negotiated {:?} with {} - peer certificate: {:?}",
+                self.version,
+                self.cipher_suite,
+                cert.validity.common_name
+            ),
+            None => tracing::info!("negotiated {:?} with {}", self.version, self.cipher_suite),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_peer_certificate_fields() {
+        let pem = std::fs::read("../certs/revocation/client_cert.pem").unwrap();
+        let der = pem::parse(pem).unwrap().into_contents();
+
+        let info = PeerCertificateInfo::parse(&der).unwrap();
+        assert_eq!(
+            info.validity.common_name.as_deref(),
+            Some("client.test.com")
+        );
+        assert!(!info.serial_number.is_empty());
+        assert!(info.validity.not_before < info.validity.not_after);
+        assert_eq!(info.der, der);
+    }
+}
@@ -0,0 +1,610 @@
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tokio_rustls::rustls;
+use tokio_rustls::rustls::client::{HandshakeSignatureValid, ServerCertVerified};
+use tokio_rustls::rustls::server::ClientCertVerified;
+use tokio_rustls::rustls::{
+    Certificate, CertificateError, DigitallySignedStruct, DistinguishedName, RootCertStore,
+    SignatureScheme,
+};
+
+use crate::tcp::tls::RevocationLists;
+
+pub(crate) static SUPPORTED_SIG_ALGS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+    &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+pub(crate) fn pki_error(error: webpki::Error) -> rustls::Error {
+    use webpki::Error::*;
+    match error {
+        BadDer | BadDerTime => CertificateError::BadEncoding.into(),
+        CertNotValidYet => CertificateError::NotValidYet.into(),
+        CertExpired | InvalidCertValidity => CertificateError::Expired.into(),
+        UnknownIssuer => CertificateError::UnknownIssuer.into(),
+        CertNotValidForName => CertificateError::NotValidForName.into(),
+        CertRevoked => CertificateError::Revoked.into(),
+        InvalidSignatureForPublicKey
+        | UnsupportedSignatureAlgorithm
+        | UnsupportedSignatureAlgorithmForPublicKey => CertificateError::BadSignature.into(),
+        _ => CertificateError::Other(Arc::new(error)).into(),
+    }
+}
+
+fn subjects(certs: &[Certificate]) -> Result<Vec<DistinguishedName>, rustls::Error> {
+    let mut store = RootCertStore::empty();
+    for cert in certs {
+        store.add(cert)?;
+    }
+    Ok(store.roots.iter().map(|x| x.subject().clone()).collect())
+}
+
+/// Verifier used by either side to check that the peer presents a pre-shared certificate
+pub(crate) struct SelfSignedVerifier {
+    expected_peer_cert: Certificate,
+    validity: rx509::x509::Validity,
+    subjects: Vec<DistinguishedName>,
+}
+
+impl SelfSignedVerifier {
+    pub(crate) fn new(expected: Certificate) -> Result<Self, String> {
+        let parsed = rx509::x509::Certificate::parse(&expected.0)
+            .map_err(|err| format!("ASNError: {err}"))?;
+        let validity = parsed.tbs_certificate.value.validity;
+        let subjects = subjects(std::slice::from_ref(&expected)).map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            expected_peer_cert: expected,
+            validity,
+            subjects,
+        })
+    }
+
+    fn verify_peer(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: SystemTime,
+    ) -> Result<(), rustls::Error> {
+        if !intermediates.is_empty() {
+            return Err(rustls::Error::General(format!(
+                "peer sent {} intermediate certificates, expected none",
+                intermediates.len()
+            )));
+        }
+
+        // the presented certificate must be a byte-for-byte match
+        if end_entity != &self.expected_peer_cert {
+            return Err(CertificateError::UnknownIssuer.into());
+        }
+
+        let now = now
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|_| rustls::Error::FailedToGetCurrentTime)?;
+        let now = rx509::der::UtcTime::from_seconds_since_epoch(now.as_secs());
+        if !self.validity.is_valid(now) {
+            return Err(CertificateError::Expired.into());
+        }
+
+        Ok(())
+    }
+}
+
+impl rustls::server::ClientCertVerifier for SelfSignedVerifier {
+    fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+        self.subjects.as_slice()
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.verify_peer(end_entity, intermediates, now)?;
+        Ok(ClientCertVerified::assertion())
+    }
+}
+
+impl rustls::client::ServerCertVerifier for SelfSignedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.verify_peer(end_entity, intermediates, now)?;
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Verifier used by the client to check the server's certificate chain against trust anchors
+///
+/// If a name is specified, it must be present in the SAN extension or, if the certificate
+/// has no SAN extension, in the Common Name.
+pub(crate) struct ServerCertVerifier {
+    // DER encoded trust anchors, checked when the verifier is created
+    roots: Vec<Certificate>,
+    name: Option<String>,
+}
+
+impl ServerCertVerifier {
+    pub(crate) fn new(roots: Vec<Certificate>, name: Option<String>) -> Result<Self, String> {
+        for cert in roots.iter() {
+            webpki::TrustAnchor::try_from_cert_der(&cert.0)
+                .map_err(|err| format!("invalid trust anchor: {err:?}"))?;
+        }
+        Ok(Self { roots, name })
+    }
+}
+
+impl rustls::client::ServerCertVerifier for ServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let cert = webpki::EndEntityCert::try_from(end_entity.0.as_slice()).map_err(pki_error)?;
+        let chain: Vec<&[u8]> = intermediates.iter().map(|x| x.0.as_slice()).collect();
+        let anchors = self
+            .roots
+            .iter()
+            .map(|x| webpki::TrustAnchor::try_from_cert_der(&x.0))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(pki_error)?;
+        let now = webpki::Time::try_from(now).map_err(|_| rustls::Error::FailedToGetCurrentTime)?;
+
+        cert.verify_for_usage(
+            SUPPORTED_SIG_ALGS,
+            &anchors,
+            &chain,
+            now,
+            webpki::KeyUsage::server_auth(),
+            &[],
+        )
+        .map_err(pki_error)?;
+
+        if let Some(name) = &self.name {
+            verify_dns_name(&cert, end_entity, name)?;
+        }
+
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Verifier that checks the chain accepted by another verifier against the revocation lists
+pub(crate) struct RevocationVerifier<V: ?Sized> {
+    inner: Arc<V>,
+    lists: RevocationLists,
+}
+
+impl<V: ?Sized> RevocationVerifier<V> {
+    pub(crate) fn new(inner: Arc<V>, lists: RevocationLists) -> Self {
+        Self { inner, lists }
+    }
+}
+
+impl rustls::server::ClientCertVerifier
+    for RevocationVerifier<dyn rustls::server::ClientCertVerifier>
+{
+    fn offer_client_auth(&self) -> bool {
+        self.inner.offer_client_auth()
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        self.inner.client_auth_mandatory()
+    }
+
+    fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+        self.inner.client_auth_root_subjects()
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        now: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        let verified = self
+            .inner
+            .verify_client_cert(end_entity, intermediates, now)?;
+        self.lists.check(end_entity, intermediates)?;
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &Certificate,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &Certificate,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+impl rustls::client::ServerCertVerifier
+    for RevocationVerifier<dyn rustls::client::ServerCertVerifier>
+{
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &rustls::ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        self.lists.check(end_entity, intermediates)?;
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &Certificate,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &Certificate,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+
+    fn request_scts(&self) -> bool {
+        self.inner.request_scts()
+    }
+}
+
+fn verify_dns_name(
+    cert: &webpki::EndEntityCert,
+    der: &Certificate,
+    name: &str,
+) -> Result<(), rustls::Error> {
+    let subject_name = webpki::SubjectNameRef::try_from_ascii_str(name)
+        .map_err(|_| rustls::Error::General("invalid DNS name".to_string()))?;
+
+    // webpki only checks the SAN extension
+    match cert.verify_is_valid_for_subject_name(subject_name) {
+        Ok(()) => Ok(()),
+        Err(webpki::Error::CertNotValidForName) => {
+            // fall back to the Common Name if there is no SAN extension
+            let parsed = rx509::x509::Certificate::parse(&der.0).map_err(|err| {
+                rustls::Error::General(format!("unable to parse certificate: {err:?}"))
+            })?;
+
+            if let Some(extensions) = &parsed.tbs_certificate.value.extensions {
+                let extensions = extensions.parse().map_err(|err| {
+                    rustls::Error::General(format!(
+                        "unable to parse certificate extensions: {err:?}"
+                    ))
+                })?;
+
+                if extensions.iter().any(|x| {
+                    matches!(
+                        x.content,
+                        rx509::x509::ext::SpecificExtension::SubjectAlternativeName(_)
+                    )
+                }) {
+                    return Err(rustls::Error::General(
+                        "certificate not valid for name, SAN extensions do not match".to_string(),
+                    ));
+                }
+            }
+
+            let subject = parsed
+                .tbs_certificate
+                .value
+                .subject
+                .parse()
+                .map_err(|err| {
+                    rustls::Error::General(format!("unable to parse certificate subject: {err:?}"))
+                })?;
+
+            match subject.common_name {
+                Some(cn) if cn == name => Ok(()),
+                Some(_) => Err(rustls::Error::General(
+                    "certificate not valid for name, no SAN and CN doesn't match".to_string(),
+                )),
+                None => Err(rustls::Error::General(
+                    "certificate not valid for name, no SAN and no CN present".to_string(),
+                )),
+            }
+        }
+        Err(err) => Err(pki_error(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tcp::tls::credentials;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn certs(dir: &str, file: &str) -> Vec<Certificate> {
+        let bytes = std::fs::read(Path::new("../certs").join(dir).join(file)).unwrap();
+        credentials::certificates(&bytes).unwrap()
+    }
+
+    fn key(dir: &str, file: &str) -> rustls::PrivateKey {
+        let bytes = std::fs::read(Path::new("../certs").join(dir).join(file)).unwrap();
+        credentials::private_key(&bytes, None).unwrap()
+    }
+
+    fn client_config(
+        verifier: Arc<dyn rustls::client::ServerCertVerifier>,
+        dir: &str,
+        name: &str,
+    ) -> rustls::ClientConfig {
+        rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(verifier)
+            .with_client_auth_cert(
+                certs(dir, &format!("{name}_cert.pem")),
+                key(dir, &format!("{name}_key.pem")),
+            )
+            .unwrap()
+    }
+
+    fn server_config(
+        verifier: Arc<dyn rustls::server::ClientCertVerifier>,
+        dir: &str,
+        name: &str,
+    ) -> rustls::ServerConfig {
+        rustls::ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(
+                certs(dir, &format!("{name}_cert.pem")),
+                key(dir, &format!("{name}_key.pem")),
+            )
+            .unwrap()
+    }
+
+    fn authority_server(dir: &str) -> rustls::ServerConfig {
+        let mut roots = RootCertStore::empty();
+        for cert in certs(dir, "ca_cert.pem").iter() {
+            roots.add(cert).unwrap();
+        }
+        let verifier = rustls::server::AllowAnyAuthenticatedClient::new(roots).boxed();
+        server_config(verifier, dir, "server")
+    }
+
+    fn self_signed(dir: &str, expected: &str) -> Arc<SelfSignedVerifier> {
+        let cert = certs(dir, &format!("{expected}_cert.pem")).remove(0);
+        Arc::new(SelfSignedVerifier::new(cert).unwrap())
+    }
+
+    fn at_year(year: u64) -> SystemTime {
+        // approximate, only used far from the validity boundaries
+        SystemTime::UNIX_EPOCH + Duration::from_secs((year - 1970) * 365 * 24 * 3600)
+    }
+
+    fn verify_server(
+        verifier: &dyn rustls::client::ServerCertVerifier,
+        dir: &str,
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let chain = certs(dir, "server_cert.pem");
+        verifier.verify_server_cert(
+            &chain[0],
+            &chain[1..],
+            &rustls::ServerName::try_from("test.com").unwrap(),
+            &mut std::iter::empty(),
+            &[],
+            now,
+        )
+    }
+
+    /// Run a handshake in memory, returns true if it succeeds
+    fn handshake(client: rustls::ClientConfig, server: rustls::ServerConfig, name: &str) -> bool {
+        let name = rustls::ServerName::try_from(name).unwrap();
+        let mut client = rustls::ClientConnection::new(Arc::new(client), name).unwrap();
+        let mut server = rustls::ServerConnection::new(Arc::new(server)).unwrap();
+
+        for _ in 0..10 {
+            let mut buffer = Vec::new();
+            client.write_tls(&mut buffer).unwrap();
+            server.read_tls(&mut buffer.as_slice()).unwrap();
+            if server.process_new_packets().is_err() {
+                return false;
+            }
+
+            let mut buffer = Vec::new();
+            server.write_tls(&mut buffer).unwrap();
+            client.read_tls(&mut buffer.as_slice()).unwrap();
+            if client.process_new_packets().is_err() {
+                return false;
+            }
+
+            if !client.is_handshaking() && !server.is_handshaking() {
+                return true;
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn self_signed_requires_the_expected_certificate() {
+        let server = || {
+            server_config(
+                self_signed("self_signed", "entity1"),
+                "self_signed",
+                "entity2",
+            )
+        };
+
+        let client = client_config(
+            self_signed("self_signed", "entity2"),
+            "self_signed",
+            "entity1",
+        );
+        assert!(handshake(client, server(), "test.com"));
+
+        // the client expects another certificate than the one presented by the server
+        let client = client_config(
+            self_signed("self_signed", "entity1"),
+            "self_signed",
+            "entity1",
+        );
+        assert!(!handshake(client, server(), "test.com"));
+
+        // the server expects another certificate than the one presented by the client
+        let client = client_config(
+            self_signed("self_signed", "entity2"),
+            "self_signed",
+            "entity2",
+        );
+        assert!(!handshake(client, server(), "test.com"));
+    }
+
+    #[test]
+    fn authority_checks_chain_and_name() {
+        let cases = [
+            // (server certificates, trusted CA, expected name, success)
+            ("ca_chain", "ca_chain", Some("test.com"), true),
+            ("ca_chain", "ca_chain", None, true),
+            ("ca_chain", "ca_chain", Some("other.com"), false),
+            ("ca_chain", "revocation", Some("test.com"), false),
+            ("revocation", "revocation", Some("test.com"), true),
+            ("revocation", "revocation", Some("client.test.com"), false),
+        ];
+
+        for (server_dir, ca_dir, name, success) in cases {
+            let verifier =
+                ServerCertVerifier::new(certs(ca_dir, "ca_cert.pem"), name.map(String::from))
+                    .unwrap();
+            let client = client_config(Arc::new(verifier), server_dir, "client");
+
+            assert_eq!(
+                handshake(
+                    client,
+                    authority_server(server_dir),
+                    name.unwrap_or("test.com")
+                ),
+                success,
+                "{server_dir} {ca_dir} {name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn authority_falls_back_to_the_common_name() {
+        let cases = [
+            // (expected name, success)
+            (Some("test.com"), true),
+            (Some("other.com"), false),
+            (None, true),
+        ];
+
+        for (name, success) in cases {
+            let verifier = ServerCertVerifier::new(
+                certs("common_name", "ca_cert.pem"),
+                name.map(String::from),
+            )
+            .unwrap();
+
+            assert_eq!(
+                verify_server(&verifier, "common_name", SystemTime::now()).is_ok(),
+                success,
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn authority_checks_validity_period() {
+        let verifier = ServerCertVerifier::new(
+            certs("ca_chain", "ca_cert.pem"),
+            Some("test.com".to_string()),
+        )
+        .unwrap();
+
+        assert!(verify_server(&verifier, "ca_chain", at_year(2030)).is_ok());
+        assert!(verify_server(&verifier, "ca_chain", at_year(2020)).is_err());
+        assert!(verify_server(&verifier, "ca_chain", at_year(2040)).is_err());
+    }
+
+    #[test]
+    fn authority_rejects_invalid_trust_anchors() {
+        assert!(ServerCertVerifier::new(vec![Certificate(vec![0x30, 0x00])], None).is_err());
+    }
+
+    #[test]
+    fn self_signed_checks_validity_period_and_chain() {
+        let verifier = self_signed("self_signed", "entity1");
+        let entity1 = certs("self_signed", "entity1_cert.pem").remove(0);
+        let entity2 = certs("self_signed", "entity2_cert.pem").remove(0);
+
+        let verify = |intermediates: &[Certificate], now| {
+            rustls::server::ClientCertVerifier::verify_client_cert(
+                verifier.as_ref(),
+                &entity1,
+                intermediates,
+                now,
+            )
+        };
+
+        assert!(verify(&[], at_year(2025)).is_ok());
+        assert!(matches!(
+            verify(&[], at_year(2020)),
+            Err(rustls::Error::InvalidCertificate(CertificateError::Expired))
+        ));
+        assert!(matches!(
+            verify(&[], at_year(2035)),
+            Err(rustls::Error::InvalidCertificate(CertificateError::Expired))
+        ));
+        // the expected certificate must be presented alone
+        assert!(verify(&[entity2], at_year(2025)).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// Size and modification time of a file, `None` if it cannot be read
+type FileState = Option<(u64, Option<SystemTime>)>;
+
+/// Polls certificate and key files so that the TLS configuration can be reloaded when they change
+///
+/// ```no_run
+/// # async fn reload(mut server: rodbus::server::ServerHandle) -> Result<(), Box<dyn std::error::Error>> {
+/// use std::path::Path;
+/// use std::time::Duration;
+/// use rodbus::server::*;
+///
+/// let mut watcher = CertificateWatcher::new(
+///     &[Path::new("./certs/server_cert.pem"), Path::new("./certs/server_key.pem")],
+///     Duration::from_secs(10),
+/// );
+/// loop {
+///     watcher.changed().await;
+///     let config = TlsServerConfig::new(
+///         Path::new("./certs/ca_cert.pem"),
+///         Path::new("./certs/server_cert.pem"),
+///         Path::new("./certs/server_key.pem"),
+///         None,
+///         MinTlsVersion::V1_2,
+///         CertificateMode::AuthorityBased,
+///     )?;
+///     server.set_tls_config(config, TlsReloadPolicy::KeepSessions).await?;
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CertificateWatcher {
+    paths: Vec<PathBuf>,
+    period: Duration,
+    // state observed by the last poll
+    last: Vec<FileState>,
+    // state when the watcher was created or last reported a change
+    reported: Vec<FileState>,
+}
+
+impl CertificateWatcher {
+    /// Watch the files at the specified paths, checking them for changes at the specified period
+    pub fn new<P>(paths: &[P], period: Duration) -> Self
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let paths: Vec<PathBuf> = paths.iter().map(|x| x.as_ref().to_path_buf()).collect();
+        let state = Self::read_state(&paths);
+        Self {
+            paths,
+            period,
+            last: state.clone(),
+            reported: state,
+        }
+    }
+
+    /// Wait until one of the files has changed
+    ///
+    /// Completes once the files have not changed for one period, so that the certificate and its key
+    /// are not read while they are still being replaced.
+    pub async fn changed(&mut self) {
+        loop {
+            tokio::time::sleep(self.period).await;
+            let state = Self::read_state(&self.paths);
+            if state == self.last && state != self.reported {
+                tracing::info!("TLS certificate or key file changed");
+                self.reported = state;
+                return;
+            }
+            self.last = state;
+        }
+    }
+
+    fn read_state(paths: &[PathBuf]) -> Vec<FileState> {
+        paths
+            .iter()
+            .map(|path| {
+                std::fs::metadata(path)
+                    .ok()
+                    .map(|x| (x.len(), x.modified().ok()))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn completes_once_a_file_changes() {
+        let path = std::env::temp_dir().join(format!("rodbus-watcher-{}.pem", std::process::id()));
+        std::fs::write(&path, "first").unwrap();
+
+        let mut watcher = CertificateWatcher::new(&[&path], Duration::from_millis(10));
+        let unchanged = tokio::time::timeout(Duration::from_millis(100), watcher.changed()).await;
+        assert!(unchanged.is_err());
+
+        std::fs::write(&path, "second version").unwrap();
+        tokio::time::timeout(Duration::from_secs(1), watcher.changed())
+            .await
+            .unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -249,7 +249,7 @@ impl AddressRange {
             return Err(InvalidRange::CountOfZero);
         }
 
-        let max_start = std::u16::MAX - (count - 1);
+        let max_start = u16::MAX - (count - 1);
 
         if start > max_start {
             return Err(InvalidRange::AddressOverflow(start, count));
@@ -375,7 +375,7 @@ mod tests {
 
     #[test]
     fn address_start_max_count_of_one_is_allowed() {
-        AddressRange::try_from(std::u16::MAX, 1).unwrap();
+        AddressRange::try_from(u16::MAX, 1).unwrap();
     }
 
     #[test]
@@ -229,3 +229,1240 @@ fn can_read_and_write_values() {
     let rt = Runtime::new().unwrap();
     rt.block_on(test_requests_and_responses())
 }
+
+async fn test_server_sessions_and_statistics() {
+    let handler = Handler::new().wrap();
+    let addr = SocketAddr::from_str("127.0.0.1:40001").unwrap();
+
+    let server = spawn_tcp_server_task(
+        1,
+        addr,
+        ServerHandlerMap::single(UnitId::new(1), handler),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .await
+    .unwrap();
+
+    let mut channel = spawn_tcp_client_task(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        default_retry_strategy(),
+        DecodeLevel::default(),
+        None,
+    );
+
+    channel.enable().await.unwrap();
+
+    let params = RequestParam::new(UnitId::new(0x01), Duration::from_secs(1));
+
+    channel
+        .read_coils(params, AddressRange::try_from(0, 2).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        channel
+            .read_coils(params, AddressRange::try_from(9, 2).unwrap())
+            .await,
+        Err(RequestError::Exception(ExceptionCode::IllegalDataAddress))
+    );
+    // the session processes requests in order, so the exception has been counted once this completes
+    channel
+        .read_holding_registers(params, AddressRange::try_from(0, 2).unwrap())
+        .await
+        .unwrap();
+
+    let sessions = server.get_sessions();
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].roles.is_empty());
+    assert_eq!(sessions[0].counters.requests.get(&0x01), Some(&2));
+    assert_eq!(sessions[0].counters.requests.get(&0x03), Some(&1));
+
+    let stats = server.get_statistics(true);
+    assert_eq!(stats.total.requests.get(&0x01), Some(&2));
+    assert_eq!(
+        stats
+            .total
+            .exceptions
+            .get(&ExceptionCode::IllegalDataAddress),
+        Some(&1)
+    );
+    assert_eq!(stats.total.authorization_denials, 0);
+    assert_eq!(stats.total.bad_frames, 0);
+
+    assert_eq!(server.get_statistics(false), ServerStatistics::default());
+    assert_eq!(
+        server.get_sessions()[0].counters,
+        SessionCounters::default()
+    );
+}
+
+#[test]
+fn server_reports_sessions_and_statistics() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_server_sessions_and_statistics())
+}
+
+struct SessionEvents(tokio::sync::mpsc::UnboundedSender<SessionEvent>);
+
+impl Listener<SessionEvent> for SessionEvents {
+    fn update(&mut self, value: SessionEvent) -> MaybeAsync<()> {
+        let _ = self.0.send(value);
+        MaybeAsync::ready(())
+    }
+}
+
+async fn expect_eof(stream: &mut tokio::net::TcpStream) {
+    use tokio::io::AsyncReadExt;
+
+    let mut buffer = [0; 16];
+    let count = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buffer))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+async fn test_session_administration() {
+    let handler = Handler::new().wrap();
+    let addr = SocketAddr::from_str("127.0.0.1:40002").unwrap();
+
+    let mut server = spawn_tcp_server_task(
+        2,
+        addr,
+        ServerHandlerMap::single(UnitId::new(1), handler),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .await
+    .unwrap();
+
+    let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+    server
+        .set_session_listener(Box::new(SessionEvents(tx)))
+        .await
+        .unwrap();
+
+    let mut first = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let first_id = match events.recv().await.unwrap() {
+        SessionEvent::Opened { id, peer } => {
+            assert_eq!(peer, first.local_addr().unwrap());
+            id
+        }
+        x => panic!("unexpected event: {x:?}"),
+    };
+
+    let mut second = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let second_id = match events.recv().await.unwrap() {
+        SessionEvent::Opened { id, .. } => id,
+        x => panic!("unexpected event: {x:?}"),
+    };
+
+    server.disconnect_session(first_id).await.unwrap();
+    assert_eq!(
+        events.recv().await.unwrap(),
+        SessionEvent::Closed {
+            id: first_id,
+            peer: first.local_addr().unwrap()
+        }
+    );
+    expect_eof(&mut first).await;
+    assert_eq!(server.get_sessions().len(), 1);
+
+    server.stop_listening().await.unwrap();
+    server.disconnect_all().await.unwrap();
+    assert_eq!(
+        events.recv().await.unwrap(),
+        SessionEvent::Closed {
+            id: second_id,
+            peer: second.local_addr().unwrap()
+        }
+    );
+    expect_eof(&mut second).await;
+    assert!(server.get_sessions().is_empty());
+
+    // the listening socket is closed
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
+
+#[test]
+fn can_disconnect_sessions_and_stop_listening() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_session_administration())
+}
+
+async fn test_initial_session_limits() {
+    let mut server = spawn_server_task(
+        1,
+        vec![ServerEndpoint::tcp(
+            std::net::TcpListener::bind("127.0.0.1:0").unwrap(),
+        )],
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+        ServerOptions::new()
+            .with_session_limits(SessionLimits::new(MaxSessionPolicy::RejectNewest)),
+    )
+    .unwrap();
+    let addr = server.local_addr().unwrap();
+
+    let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+    server
+        .set_session_listener(Box::new(SessionEvents(tx)))
+        .await
+        .unwrap();
+
+    let _first = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let first_id = match events.recv().await.unwrap() {
+        SessionEvent::Opened { id, .. } => id,
+        x => panic!("unexpected event: {x:?}"),
+    };
+
+    // the existing session is kept
+    let mut second = tokio::net::TcpStream::connect(addr).await.unwrap();
+    expect_eof(&mut second).await;
+    let sessions = server.get_sessions();
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].id, first_id);
+}
+
+#[test]
+fn applies_session_limits_from_spawn() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_initial_session_limits())
+}
+
+async fn test_activity_limits() {
+    let handler = Handler::new().wrap();
+    let addr = SocketAddr::from_str("127.0.0.1:40003").unwrap();
+
+    let mut server = spawn_tcp_server_task(
+        2,
+        addr,
+        ServerHandlerMap::single(UnitId::new(1), handler),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .await
+    .unwrap();
+
+    server
+        .set_activity_limits(
+            ActivityLimits::default()
+                .with_idle_timeout(Duration::from_millis(200))
+                .with_rate_limit(RateLimit::new(1, 1, RateLimitAction::ReplyBusy)),
+        )
+        .await
+        .unwrap();
+
+    let mut channel = spawn_tcp_client_task(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        default_retry_strategy(),
+        DecodeLevel::default(),
+        None,
+    );
+
+    channel.enable().await.unwrap();
+
+    let params = RequestParam::new(UnitId::new(0x01), Duration::from_secs(1));
+    let range = AddressRange::try_from(0, 2).unwrap();
+
+    channel.read_coils(params, range).await.unwrap();
+    assert_eq!(
+        channel.read_coils(params, range).await,
+        Err(RequestError::Exception(ExceptionCode::ServerDeviceBusy))
+    );
+
+    // a connection that never sends anything is closed
+    let mut idle = tokio::net::TcpStream::connect(addr).await.unwrap();
+    expect_eof(&mut idle).await;
+
+    let stats = server.get_statistics(false);
+    assert_eq!(stats.total.rate_limited, 1);
+    assert!(stats.total.idle_timeouts >= 1);
+    assert_eq!(
+        stats.total.exceptions.get(&ExceptionCode::ServerDeviceBusy),
+        Some(&1)
+    );
+}
+
+#[test]
+fn enforces_idle_timeout_and_rate_limit() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_activity_limits())
+}
+
+async fn test_shutdown(port: u16, mode: ShutdownMode) {
+    let handler = Handler::new().wrap();
+    let addr = SocketAddr::new("127.0.0.1".parse().unwrap(), port);
+
+    let server = spawn_tcp_server_task(
+        2,
+        addr,
+        ServerHandlerMap::single(UnitId::new(1), handler),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .await
+    .unwrap();
+
+    let mut channel = spawn_tcp_client_task(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        default_retry_strategy(),
+        DecodeLevel::default(),
+        None,
+    );
+    channel.enable().await.unwrap();
+    channel
+        .read_coils(
+            RequestParam::new(UnitId::new(0x01), Duration::from_secs(1)),
+            AddressRange::try_from(0, 2).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    // wait for the server to accept the connection
+    while server.get_sessions().len() < 2 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    tokio::time::timeout(Duration::from_secs(5), server.shutdown(mode))
+        .await
+        .unwrap();
+
+    // every session is closed and the listening socket is closed
+    expect_eof(&mut stream).await;
+    assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+}
+
+#[test]
+fn shutdown_waits_for_sessions_to_exit() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_shutdown(40004, ShutdownMode::FinishRequests));
+    rt.block_on(test_shutdown(40005, ShutdownMode::Abort));
+}
+
+async fn test_pre_bound_listener() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = spawn_tcp_server_task_with_listener(
+        1,
+        listener,
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .unwrap();
+    assert_eq!(server.local_addr(), Some(addr));
+
+    let mut channel = spawn_tcp_client_task(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        default_retry_strategy(),
+        DecodeLevel::default(),
+        None,
+    );
+    channel.enable().await.unwrap();
+    channel
+        .read_coils(
+            RequestParam::new(UnitId::new(0x01), Duration::from_secs(1)),
+            AddressRange::try_from(0, 2).unwrap(),
+        )
+        .await
+        .unwrap();
+}
+
+#[test]
+fn can_spawn_server_with_pre_bound_listener() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_pre_bound_listener())
+}
+
+async fn test_multiple_endpoints() {
+    let first = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let second = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+
+    let server = spawn_server_task(
+        2,
+        vec![ServerEndpoint::tcp(first), ServerEndpoint::tcp(second)],
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+        ServerOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(server.local_addrs().len(), 2);
+
+    let params = RequestParam::new(UnitId::new(0x01), Duration::from_secs(1));
+    let range = AddressRange::try_from(0, 2).unwrap();
+
+    for addr in server.local_addrs() {
+        let mut channel = spawn_tcp_client_task(
+            HostAddr::ip(addr.ip(), addr.port()),
+            10,
+            default_retry_strategy(),
+            DecodeLevel::default(),
+            None,
+        );
+        channel.enable().await.unwrap();
+        channel.read_coils(params, range).await.unwrap();
+        channel.disable().await.unwrap();
+    }
+
+    // the endpoints share the handlers and the statistics
+    assert_eq!(
+        server.get_statistics(false).total.requests.get(&0x01),
+        Some(&2)
+    );
+}
+
+#[test]
+fn can_serve_multiple_endpoints() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_multiple_endpoints())
+}
+
+async fn test_address_filter_update() {
+    let addr = SocketAddr::from_str("127.0.0.1:40006").unwrap();
+
+    let mut server = spawn_tcp_server_task(
+        2,
+        addr,
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        AddressFilter::Cidr("127.0.0.0/8".parse().unwrap()),
+        DecodeLevel::default(),
+    )
+    .await
+    .unwrap();
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    while server.get_sessions().is_empty() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    server
+        .set_address_filter(AddressFilter::Rules(
+            AddressRules::new(RuleAction::Allow).deny(AddressFilter::Exact(addr.ip())),
+        ))
+        .await
+        .unwrap();
+
+    // the active session is closed and new connections are rejected
+    expect_eof(&mut stream).await;
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    expect_eof(&mut stream).await;
+    assert_eq!(server.get_statistics(false).sessions_rejected, 1);
+}
+
+#[test]
+fn can_update_address_filter() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_address_filter_update())
+}
+
+async fn test_unmapped_units() {
+    let mut handlers = ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap());
+    handlers.set_unmapped_unit_response(UnmappedUnitResponse::GatewayTargetDeviceFailedToRespond);
+
+    let server = spawn_tcp_server_task_with_listener(
+        1,
+        std::net::TcpListener::bind("127.0.0.1:0").unwrap(),
+        handlers.clone(),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .unwrap();
+
+    handlers.set_default(Handler::new().wrap());
+    let default_server = spawn_tcp_server_task_with_listener(
+        1,
+        std::net::TcpListener::bind("127.0.0.1:0").unwrap(),
+        handlers,
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .unwrap();
+
+    let params = RequestParam::new(UnitId::new(0xFF), Duration::from_secs(1));
+    let range = AddressRange::try_from(0, 2).unwrap();
+
+    let mut results = Vec::new();
+    for addr in [server.local_addr(), default_server.local_addr()] {
+        let addr = addr.unwrap();
+        let mut channel = spawn_tcp_client_task(
+            HostAddr::ip(addr.ip(), addr.port()),
+            10,
+            default_retry_strategy(),
+            DecodeLevel::default(),
+            None,
+        );
+        channel.enable().await.unwrap();
+        results.push(channel.read_coils(params, range).await);
+    }
+
+    assert_eq!(
+        results[0],
+        Err(RequestError::Exception(
+            ExceptionCode::GatewayTargetDeviceFailedToRespond
+        ))
+    );
+    assert_eq!(
+        results[1],
+        Ok(vec![Indexed::new(0, false), Indexed::new(1, false)])
+    );
+}
+
+#[test]
+fn responds_to_unmapped_units() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_unmapped_units())
+}
+
+struct OtherHandler;
+
+impl RequestHandler for OtherHandler {}
+
+async fn test_runtime_handler_changes() {
+    let mut handlers = ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap());
+    handlers.set_unmapped_unit_response(UnmappedUnitResponse::IllegalFunction);
+
+    let mut server = spawn_tcp_server_task_with_listener(
+        1,
+        std::net::TcpListener::bind("127.0.0.1:0").unwrap(),
+        handlers,
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut channel = spawn_tcp_client_task(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        default_retry_strategy(),
+        DecodeLevel::default(),
+        None,
+    );
+    channel.enable().await.unwrap();
+
+    let range = AddressRange::try_from(0, 2).unwrap();
+    let unit = |id| RequestParam::new(UnitId::new(id), Duration::from_secs(1));
+    let unmapped = Err(RequestError::Exception(ExceptionCode::IllegalFunction));
+
+    assert_eq!(channel.read_coils(unit(2), range).await, unmapped);
+
+    server
+        .add_handler(UnitId::new(2), Handler::new().wrap())
+        .await
+        .unwrap();
+    assert_eq!(channel.read_coils(unit(2), range).await.map(|_| ()), Ok(()));
+
+    server.remove_handler(UnitId::new(1)).await.unwrap();
+    assert_eq!(channel.read_coils(unit(1), range).await, unmapped);
+
+    assert_eq!(
+        server
+            .add_handler(UnitId::new(3), OtherHandler.wrap())
+            .await,
+        Err(HandlerUpdateError::WrongHandlerType)
+    );
+}
+
+#[test]
+fn can_add_and_remove_handlers_at_runtime() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_runtime_handler_changes())
+}
+
+struct RejectCoil(u16);
+
+impl WriteValidator for RejectCoil {
+    fn validate(&self, write: &WriteEvent) -> Result<(), ExceptionCode> {
+        match write.values {
+            WriteValues::Coils(_) if write.range.start == self.0 => {
+                Err(ExceptionCode::IllegalDataValue)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+async fn test_write_observers() {
+    let server = spawn_tcp_server_task_with_listener(
+        1,
+        std::net::TcpListener::bind("127.0.0.1:0").unwrap(),
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .unwrap();
+
+    let mut writes = server.subscribe_writes();
+    server.set_write_validator(Some(RejectCoil(5).wrap()));
+
+    let addr = server.local_addr().unwrap();
+    let mut channel = spawn_tcp_client_task(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        default_retry_strategy(),
+        DecodeLevel::default(),
+        None,
+    );
+    channel.enable().await.unwrap();
+
+    let params = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+    channel
+        .write_single_coil(params, Indexed::new(1, true))
+        .await
+        .unwrap();
+    assert_eq!(
+        channel
+            .write_single_coil(params, Indexed::new(5, true))
+            .await,
+        Err(RequestError::Exception(ExceptionCode::IllegalDataValue))
+    );
+    // writes rejected by the handler are not published
+    assert!(channel
+        .write_single_coil(params, Indexed::new(20, true))
+        .await
+        .is_err());
+    channel
+        .write_multiple_registers(
+            params,
+            WriteMultiple::from(2, vec![0xCAFE, 0xBEEF]).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let session = server.get_sessions()[0].id;
+    assert_eq!(
+        writes.recv().await.unwrap(),
+        WriteEvent {
+            session: Some(session),
+            unit: UnitId::new(1),
+            range: AddressRange::try_from(1, 1).unwrap(),
+            values: WriteValues::Coils(vec![true]),
+        }
+    );
+    assert_eq!(
+        writes.recv().await.unwrap(),
+        WriteEvent {
+            session: Some(session),
+            unit: UnitId::new(1),
+            range: AddressRange::try_from(2, 2).unwrap(),
+            values: WriteValues::Registers(vec![0xCAFE, 0xBEEF]),
+        }
+    );
+    assert!(writes.try_recv().is_err());
+}
+
+#[test]
+fn publishes_and_validates_writes() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_write_observers())
+}
+
+#[derive(Clone, Default)]
+struct AuditRecords(std::sync::Arc<std::sync::Mutex<Vec<AuthorizationRecord>>>);
+
+impl AuditSink for AuditRecords {
+    fn record(&self, record: &AuthorizationRecord) {
+        self.0.lock().unwrap().push(record.clone());
+    }
+}
+
+async fn test_tcp_authorization() {
+    let records = AuditRecords::default();
+    let server = spawn_server_task(
+        1,
+        vec![ServerEndpoint::tcp_with_authz(
+            std::net::TcpListener::bind("127.0.0.1:0").unwrap(),
+            ReadOnlyAuthorizationHandler::create(),
+        )],
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+        ServerOptions::new().with_audit_sink(records.clone().wrap()),
+    )
+    .unwrap();
+
+    let addr = server.local_addr().unwrap();
+    let mut channel = spawn_tcp_client_task(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        default_retry_strategy(),
+        DecodeLevel::default(),
+        None,
+    );
+    channel.enable().await.unwrap();
+
+    let params = RequestParam::new(UnitId::new(1), Duration::from_secs(1));
+    channel
+        .read_coils(params, AddressRange::try_from(0, 2).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        channel
+            .write_single_coil(params, Indexed::new(1, true))
+            .await,
+        Err(RequestError::Exception(ExceptionCode::IllegalFunction))
+    );
+
+    // the IP address of the client is its identity
+    let sessions = server.get_sessions();
+    assert_eq!(sessions[0].roles, vec!["127.0.0.1".to_string()]);
+    assert_eq!(server.get_statistics(false).total.authorization_denials, 1);
+
+    // both decisions are recorded
+    let outcomes: Vec<Authorization> = records
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|x| x.outcome)
+        .collect();
+    assert_eq!(outcomes, vec![Authorization::Allow, Authorization::Deny]);
+}
+
+#[test]
+fn authorizes_requests_on_plain_tcp() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_tcp_authorization())
+}
+
+#[cfg(feature = "tls")]
+fn server_tls_config(peer_cert: &str) -> TlsServerConfig {
+    let dir = std::path::Path::new("../certs/self_signed");
+    TlsServerConfig::new(
+        &dir.join(peer_cert),
+        &dir.join("entity2_cert.pem"),
+        &dir.join("entity2_key.pem"),
+        None,
+        MinTlsVersion::V1_2,
+        CertificateMode::SelfSigned,
+    )
+    .unwrap()
+}
+
+#[cfg(feature = "tls")]
+fn client_tls_config(peer_cert: &str) -> TlsClientConfig {
+    let dir = std::path::Path::new("../certs/self_signed");
+    TlsClientConfig::self_signed(
+        &dir.join(peer_cert),
+        &dir.join("entity1_cert.pem"),
+        &dir.join("entity1_key.pem"),
+        None,
+        MinTlsVersion::V1_2,
+    )
+    .unwrap()
+}
+
+/// Retry reading until a request succeeds, returns false if none succeeds within a second
+#[cfg(feature = "tls")]
+async fn read_succeeds(channel: &mut Channel) -> bool {
+    let params = RequestParam::new(UnitId::new(0x01), Duration::from_millis(200));
+    let range = AddressRange::try_from(0, 2).unwrap();
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(1);
+    while tokio::time::Instant::now() < deadline {
+        if channel.read_coils(params, range).await.is_ok() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    false
+}
+
+#[cfg(feature = "tls")]
+async fn test_tls_reload() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let mut server = spawn_tls_server_task_with_listener(
+        1,
+        listener,
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        server_tls_config("entity1_cert.pem"),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .unwrap();
+
+    // the client does not trust the server certificate
+    let mut channel = spawn_tls_client_task(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        doubling_retry_strategy(Duration::from_millis(10), Duration::from_millis(10)),
+        client_tls_config("entity1_cert.pem"),
+        DecodeLevel::default(),
+        None,
+    );
+    channel.enable().await.unwrap();
+    assert!(!read_succeeds(&mut channel).await);
+
+    channel
+        .set_tls_config(
+            client_tls_config("entity2_cert.pem"),
+            TlsReloadPolicy::KeepSessions,
+        )
+        .await
+        .unwrap();
+    assert!(read_succeeds(&mut channel).await);
+
+    // the established session is closed and the client certificate is no longer trusted
+    server
+        .set_tls_config(
+            server_tls_config("entity2_cert.pem"),
+            TlsReloadPolicy::CloseSessions,
+        )
+        .await
+        .unwrap();
+    assert!(!read_succeeds(&mut channel).await);
+
+    server
+        .set_tls_config(
+            server_tls_config("entity1_cert.pem"),
+            TlsReloadPolicy::KeepSessions,
+        )
+        .await
+        .unwrap();
+    assert!(read_succeeds(&mut channel).await);
+
+    // the client closes its connection and fails to reconnect
+    channel
+        .set_tls_config(
+            client_tls_config("entity1_cert.pem"),
+            TlsReloadPolicy::CloseSessions,
+        )
+        .await
+        .unwrap();
+    assert!(!read_succeeds(&mut channel).await);
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn can_reload_tls_configuration() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_tls_reload())
+}
+
+#[cfg(feature = "tls")]
+async fn test_revocation_lists() {
+    let dir = std::path::Path::new("../certs/revocation");
+    let ca_cert = dir.join("ca_cert.pem");
+    let server_lists = RevocationLists::default();
+    let client_lists = RevocationLists::load(&ca_cert, &[dir.join("crl.pem")]).unwrap();
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut server = spawn_tls_server_task_with_listener(
+        1,
+        listener,
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        TlsServerConfig::new(
+            &ca_cert,
+            &dir.join("server_cert.pem"),
+            &dir.join("server_key.pem"),
+            None,
+            MinTlsVersion::V1_2,
+            CertificateMode::AuthorityBased,
+        )
+        .unwrap()
+        .with_revocation_lists(server_lists.clone())
+        .unwrap(),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .unwrap();
+
+    // the client checks that the server certificate is not revoked
+    let mut channel = spawn_tls_client_task(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        doubling_retry_strategy(Duration::from_millis(10), Duration::from_millis(10)),
+        TlsClientConfig::full_pki(
+            Some("test.com".to_string()),
+            &ca_cert,
+            &dir.join("client_cert.pem"),
+            &dir.join("client_key.pem"),
+            None,
+            MinTlsVersion::V1_2,
+        )
+        .unwrap()
+        .with_revocation_lists(client_lists)
+        .unwrap(),
+        DecodeLevel::default(),
+        None,
+    );
+    channel.enable().await.unwrap();
+    assert!(read_succeeds(&mut channel).await);
+
+    // the client certificate is revoked by the updated lists
+    server_lists
+        .reload(&ca_cert, &[dir.join("crl.pem")])
+        .unwrap();
+    server.disconnect_all().await.unwrap();
+    assert!(!read_succeeds(&mut channel).await);
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn rejects_revoked_certificates() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_revocation_lists())
+}
+
+#[cfg(feature = "tls")]
+struct TlsSessions(tokio::sync::mpsc::UnboundedSender<TlsSessionInfo>);
+
+#[cfg(feature = "tls")]
+impl Listener<TlsSessionInfo> for TlsSessions {
+    fn update(&mut self, value: TlsSessionInfo) -> MaybeAsync<()> {
+        let _ = self.0.send(value);
+        MaybeAsync::ready(())
+    }
+}
+
+/// Records the TLS session of the last request
+#[cfg(feature = "tls")]
+#[derive(Default)]
+struct ContextRecorder(std::sync::Arc<std::sync::Mutex<Option<(SocketAddr, TlsSessionInfo)>>>);
+
+#[cfg(feature = "tls")]
+impl RequestHandler for ContextRecorder {
+    fn set_context(&mut self, context: RequestContext) {
+        *self.0.lock().unwrap() = context.peer.zip(context.tls.cloned());
+    }
+
+    fn read_coil(&self, _address: u16) -> Result<bool, ExceptionCode> {
+        Ok(false)
+    }
+}
+
+#[cfg(feature = "tls")]
+async fn test_tls_session_info() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let recorder = ContextRecorder::default();
+    let context = recorder.0.clone();
+    let mut server = spawn_tls_server_task_with_listener(
+        1,
+        listener,
+        ServerHandlerMap::single(UnitId::new(1), recorder.wrap()),
+        server_tls_config("entity1_cert.pem"),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .unwrap();
+
+    let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+    server
+        .set_session_listener(Box::new(SessionEvents(tx)))
+        .await
+        .unwrap();
+
+    let (tx, mut sessions) = tokio::sync::mpsc::unbounded_channel();
+    let mut channel = spawn_tls_client_task(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        doubling_retry_strategy(Duration::from_millis(10), Duration::from_millis(10)),
+        client_tls_config("entity2_cert.pem").with_session_listener(Box::new(TlsSessions(tx))),
+        DecodeLevel::default(),
+        None,
+    );
+    channel.enable().await.unwrap();
+    assert!(read_succeeds(&mut channel).await);
+
+    // the client sees the server certificate
+    let client_session = sessions.recv().await.unwrap();
+    let cert = client_session.peer_certificate.as_ref().unwrap();
+    assert_eq!(cert.validity.common_name.as_deref(), Some("DO NOT USE"));
+    assert_eq!(cert.serial_number.first(), Some(&0x70));
+    assert!(cert.validity.not_before < cert.validity.not_after);
+
+    let id = match events.recv().await.unwrap() {
+        SessionEvent::Opened { id, .. } => id,
+        x => panic!("unexpected event: {x:?}"),
+    };
+    let server_session = match events.recv().await.unwrap() {
+        SessionEvent::TlsEstablished {
+            id: session_id,
+            session,
+            ..
+        } => {
+            assert_eq!(session_id, id);
+            session
+        }
+        x => panic!("unexpected event: {x:?}"),
+    };
+
+    // both ends agree on the negotiated parameters
+    assert_eq!(server_session.version, client_session.version);
+    assert_eq!(server_session.cipher_suite, client_session.cipher_suite);
+    assert!(server_session.peer_certificate.is_some());
+    assert_eq!(server.get_sessions()[0].tls.as_ref(), Some(&server_session));
+
+    // the handler received the session with the request
+    let (peer, session) = context.lock().unwrap().clone().unwrap();
+    assert_eq!(peer, server.get_sessions()[0].peer);
+    assert_eq!(session, server_session);
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn reports_tls_session_parameters() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_tls_session_info())
+}
+
+#[cfg(feature = "tls")]
+struct ExpiryWarnings(tokio::sync::mpsc::UnboundedSender<ExpiryWarning>);
+
+#[cfg(feature = "tls")]
+impl Listener<ExpiryWarning> for ExpiryWarnings {
+    fn update(&mut self, value: ExpiryWarning) -> MaybeAsync<()> {
+        let _ = self.0.send(value);
+        MaybeAsync::ready(())
+    }
+}
+
+#[cfg(feature = "tls")]
+async fn test_certificate_expiry_warnings() {
+    // every certificate expires within the warning period
+    let warn_before = Duration::from_secs(u64::MAX);
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server_config = server_tls_config("entity1_cert.pem").with_expiry_warning(warn_before);
+    assert_eq!(server_config.peer_certificates().len(), 1);
+    assert!(
+        server_config
+            .local_certificate()
+            .remaining(std::time::SystemTime::now())
+            > Duration::ZERO
+    );
+
+    let mut server = spawn_tls_server_task_with_listener(
+        1,
+        listener,
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        server_config,
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .unwrap();
+
+    let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+    server
+        .set_session_listener(Box::new(SessionEvents(tx)))
+        .await
+        .unwrap();
+
+    let (tx, mut warnings) = tokio::sync::mpsc::unbounded_channel();
+    let mut channel = spawn_tls_client_task(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        doubling_retry_strategy(Duration::from_millis(10), Duration::from_millis(10)),
+        client_tls_config("entity2_cert.pem")
+            .with_expiry_warning(warn_before)
+            .with_expiry_listener(Box::new(ExpiryWarnings(tx))),
+        DecodeLevel::default(),
+        None,
+    );
+    channel.enable().await.unwrap();
+    assert!(read_succeeds(&mut channel).await);
+
+    assert_eq!(
+        warnings.recv().await.unwrap().owner,
+        CertificateOwner::Local
+    );
+    assert_eq!(warnings.recv().await.unwrap().owner, CertificateOwner::Peer);
+
+    let mut owners = Vec::new();
+    while owners.len() < 2 {
+        match events.recv().await.unwrap() {
+            SessionEvent::CertificateExpiring { warning, .. } => owners.push(warning.owner),
+            SessionEvent::Opened { .. } | SessionEvent::TlsEstablished { .. } => {}
+            x => panic!("unexpected event: {x:?}"),
+        }
+    }
+    assert_eq!(
+        owners,
+        vec![CertificateOwner::Local, CertificateOwner::Peer]
+    );
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn warns_about_expiring_certificates() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_certificate_expiry_warnings())
+}
+
+#[cfg(feature = "tls")]
+async fn test_crypto_policies() {
+    let tls12_only = CryptoPolicy {
+        versions: vec![TlsVersion::V1_2],
+        cipher_suites: vec![
+            CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+            CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+        ],
+        kx_groups: vec![KeyExchangeGroup::Secp384r1],
+    };
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut server = spawn_tls_server_task_with_listener(
+        1,
+        listener,
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        server_tls_config("entity1_cert.pem")
+            .with_crypto_policy(tls12_only)
+            .unwrap(),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .unwrap();
+
+    let (tx, mut sessions) = tokio::sync::mpsc::unbounded_channel();
+    let mut channel = spawn_tls_client_task(
+        HostAddr::ip(addr.ip(), addr.port()),
+        10,
+        doubling_retry_strategy(Duration::from_millis(10), Duration::from_millis(10)),
+        client_tls_config("entity2_cert.pem").with_session_listener(Box::new(TlsSessions(tx))),
+        DecodeLevel::default(),
+        None,
+    );
+    channel.enable().await.unwrap();
+    assert!(read_succeeds(&mut channel).await);
+
+    let session = sessions.recv().await.unwrap();
+    assert_eq!(session.version, TlsVersion::V1_2);
+    assert!(session.cipher_suite.ends_with("_WITH_AES_256_GCM_SHA384"));
+
+    // the versions permitted by both ends do not overlap
+    channel
+        .set_tls_config(
+            client_tls_config("entity2_cert.pem")
+                .with_crypto_policy(CryptoPolicy::tls13_only())
+                .unwrap(),
+            TlsReloadPolicy::CloseSessions,
+        )
+        .await
+        .unwrap();
+    assert!(!read_succeeds(&mut channel).await);
+
+    server
+        .set_tls_config(
+            server_tls_config("entity1_cert.pem"),
+            TlsReloadPolicy::KeepSessions,
+        )
+        .await
+        .unwrap();
+    assert!(read_succeeds(&mut channel).await);
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn negotiates_within_crypto_policies() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_crypto_policies())
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn rejects_inconsistent_crypto_policies() {
+    let policy = CryptoPolicy {
+        versions: vec![TlsVersion::V1_3],
+        cipher_suites: vec![CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256],
+        kx_groups: KeyExchangeGroup::ALL.to_vec(),
+    };
+    assert!(matches!(
+        client_tls_config("entity2_cert.pem").with_crypto_policy(policy.clone()),
+        Err(TlsError::InvalidCryptoPolicy(_))
+    ));
+    assert!(matches!(
+        server_tls_config("entity1_cert.pem").with_crypto_policy(policy),
+        Err(TlsError::InvalidCryptoPolicy(_))
+    ));
+}
+
+#[cfg(feature = "tls")]
+async fn test_pending_handshakes() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let mut server = spawn_tls_server_task_with_listener(
+        2,
+        listener,
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        server_tls_config("entity1_cert.pem"),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+    )
+    .unwrap();
+
+    // neither client starts the handshake
+    let mut first = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let mut second = tokio::net::TcpStream::connect(addr).await.unwrap();
+    while server.get_sessions().len() < 2 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let id = server.get_sessions()[0].id;
+    server.disconnect_session(id).await.unwrap();
+    expect_eof(&mut first).await;
+
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        server.shutdown(ShutdownMode::FinishRequests),
+    )
+    .await
+    .unwrap();
+    expect_eof(&mut second).await;
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn closes_sessions_during_the_handshake() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_pending_handshakes())
+}
+
+#[cfg(feature = "tls")]
+async fn test_handshake_idle_timeout() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = spawn_server_task(
+        1,
+        vec![ServerEndpoint::tls(
+            listener,
+            server_tls_config("entity1_cert.pem"),
+        )],
+        ServerHandlerMap::single(UnitId::new(1), Handler::new().wrap()),
+        AddressFilter::Any,
+        DecodeLevel::default(),
+        ServerOptions::new().with_activity_limits(
+            ActivityLimits::default().with_idle_timeout(Duration::from_millis(200)),
+        ),
+    )
+    .unwrap();
+
+    // a connection that never starts the handshake is closed
+    let mut idle = tokio::net::TcpStream::connect(addr).await.unwrap();
+    expect_eof(&mut idle).await;
+    assert_eq!(server.get_statistics(false).total.idle_timeouts, 1);
+}
+
+#[cfg(feature = "tls")]
+#[test]
+fn enforces_idle_timeout_during_the_handshake() {
+    let rt = Runtime::new().unwrap();
+    rt.block_on(test_handshake_idle_timeout())
+}